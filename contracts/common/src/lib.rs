@@ -0,0 +1,15 @@
+//! State and checks shared by the contracts. The contracts export the
+//! shared methods with the `impl_*` macros, which delegate to these types,
+//! and keep their own `#[near]` methods for everything else.
+
+pub mod limits;
+mod moderation;
+mod storage;
+
+pub use moderation::Moderation;
+pub use storage::{StorageAccount, StorageAccounts};
+
+// What `impl_storage_management!` expands to, so that a contract doesn't
+// need its own dependency on the standards.
+#[doc(hidden)]
+pub use near_contract_standards::storage_management as __storage_management;
@@ -0,0 +1,22 @@
+//! Input bounds of the `denial-of-service-fixed` contract, shared with the
+//! integration tests that probe them.
+
+/// Longest note title `add_note` accepts, in bytes.
+pub const MAX_TITLE_LENGTH: usize = 128;
+
+/// Longest note body `add_note` accepts, in bytes. A note is stored as a
+/// storage key of its set, with the title it has to stay well below the
+/// 2 KiB key limit.
+pub const MAX_BODY_LENGTH: usize = 1024;
+
+/// Notes `get_notes` returns when no limit is given, and at most.
+pub const MAX_NOTES_PER_PAGE: u32 = 100;
+
+/// Jars `batch_create_jars` accepts in one call, well below the gas limit.
+pub const MAX_JARS_PER_BATCH: usize = 250;
+
+/// Notes removed by `remove_notes_batch` when no limit is given.
+pub const DEFAULT_NOTES_PER_REMOVAL: u32 = 100;
+
+/// Upper bound on the notes removed in one call, well below the gas limit.
+pub const MAX_NOTES_PER_REMOVAL: u32 = 250;
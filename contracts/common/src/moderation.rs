@@ -0,0 +1,232 @@
+use near_sdk::{
+    env, log, near, require, store::LookupSet, AccountId, IntoStorageKey,
+};
+
+/// Managers of a contract and the users they froze.
+#[near(serializers = [borsh])]
+pub struct Moderation {
+    managers: LookupSet<AccountId>,
+    frozen_users: LookupSet<AccountId>,
+}
+
+impl Moderation {
+    pub fn new(
+        managers_prefix: impl IntoStorageKey,
+        frozen_users_prefix: impl IntoStorageKey,
+        managers: Vec<AccountId>,
+    ) -> Self {
+        let mut moderation = Self {
+            managers: LookupSet::new(managers_prefix),
+            frozen_users: LookupSet::new(frozen_users_prefix),
+        };
+
+        moderation.managers.extend(managers);
+
+        moderation
+    }
+
+    pub fn assert_manager(&self) {
+        require!(
+            self.is_manager(&env::predecessor_account_id()),
+            "Only managers can moderate"
+        );
+    }
+
+    pub fn assert_not_frozen(&self, account_id: &AccountId) {
+        require!(!self.is_frozen(account_id), "User is frozen");
+    }
+
+    pub fn freeze_user(&mut self, account_id: AccountId) {
+        self.assert_manager();
+
+        self.internal_freeze_user(account_id);
+    }
+
+    /// Freezes `account_id` without checking the caller, for contracts that
+    /// authorize it themselves.
+    pub fn internal_freeze_user(&mut self, account_id: AccountId) {
+        self.frozen_users.insert(account_id.clone());
+
+        log!("Froze user: {}", account_id);
+    }
+
+    pub fn unfreeze_user(&mut self, account_id: AccountId) {
+        self.assert_manager();
+
+        self.frozen_users.remove(&account_id);
+
+        log!("Unfroze user: {}", account_id);
+    }
+
+    pub fn add_manager(&mut self, account_id: AccountId) {
+        self.assert_manager();
+
+        self.managers.insert(account_id.clone());
+
+        log!("Added manager: {}", account_id);
+    }
+
+    /// Managers can remove each other but not themselves, so there is always
+    /// at least one manager left.
+    pub fn remove_manager(&mut self, account_id: AccountId) {
+        self.assert_manager();
+
+        require!(
+            account_id != env::predecessor_account_id(),
+            "Managers can't remove themselves"
+        );
+
+        require!(self.managers.remove(&account_id), "Not a manager");
+
+        log!("Removed manager: {}", account_id);
+    }
+
+    pub fn is_manager(&self, account_id: &AccountId) -> bool {
+        self.managers.contains(account_id)
+    }
+
+    pub fn is_frozen(&self, account_id: &AccountId) -> bool {
+        self.frozen_users.contains(account_id)
+    }
+}
+
+/// Exports the manager methods of a contract that keeps its [`Moderation`]
+/// in the field `$field`. Methods that depend on the contract's own state,
+/// like deleting its notes, stay in the contract.
+#[macro_export]
+macro_rules! impl_moderation {
+    ($contract:ident, $field:ident) => {
+        #[near_sdk::near]
+        impl $contract {
+            /// Stops `account_id` from adding or editing notes.
+            pub fn freeze_user(&mut self, account_id: near_sdk::AccountId) {
+                self.$field.freeze_user(account_id);
+            }
+
+            pub fn unfreeze_user(&mut self, account_id: near_sdk::AccountId) {
+                self.$field.unfreeze_user(account_id);
+            }
+
+            pub fn add_manager(&mut self, account_id: near_sdk::AccountId) {
+                self.$field.add_manager(account_id);
+            }
+
+            pub fn remove_manager(&mut self, account_id: near_sdk::AccountId) {
+                self.$field.remove_manager(account_id);
+            }
+
+            pub fn is_manager(&self, account_id: near_sdk::AccountId) -> bool {
+                self.$field.is_manager(&account_id)
+            }
+
+            pub fn is_frozen(&self, account_id: near_sdk::AccountId) -> bool {
+                self.$field.is_frozen(&account_id)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    use super::*;
+
+    const MANAGER: &str = "manager.near";
+    const USER: &str = "user.near";
+    const ATTACKER: &str = "attacker.near";
+
+    #[test]
+    fn manager_freezes_and_unfreezes() {
+        let mut moderation = moderation();
+
+        set_context(MANAGER);
+        moderation.freeze_user(account(USER));
+
+        assert!(moderation.is_frozen(&account(USER)));
+
+        moderation.unfreeze_user(account(USER));
+
+        assert!(!moderation.is_frozen(&account(USER)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only managers can moderate")]
+    fn user_cannot_freeze() {
+        let mut moderation = moderation();
+
+        set_context(ATTACKER);
+        moderation.freeze_user(account(USER));
+    }
+
+    #[test]
+    #[should_panic(expected = "User is frozen")]
+    fn frozen_user_is_rejected() {
+        let mut moderation = moderation();
+
+        set_context(MANAGER);
+        moderation.freeze_user(account(USER));
+
+        moderation.assert_not_frozen(&account(USER));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only managers can moderate")]
+    fn user_cannot_unfreeze() {
+        let mut moderation = moderation();
+
+        set_context(MANAGER);
+        moderation.freeze_user(account(USER));
+
+        set_context(USER);
+        moderation.unfreeze_user(account(USER));
+    }
+
+    #[test]
+    fn manager_adds_and_removes_managers() {
+        let mut moderation = moderation();
+
+        set_context(MANAGER);
+        moderation.add_manager(account(USER));
+
+        assert!(moderation.is_manager(&account(USER)));
+
+        set_context(USER);
+        moderation.remove_manager(account(MANAGER));
+
+        assert!(!moderation.is_manager(&account(MANAGER)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only managers can moderate")]
+    fn user_cannot_add_manager() {
+        let mut moderation = moderation();
+
+        set_context(ATTACKER);
+        moderation.add_manager(account(ATTACKER));
+    }
+
+    #[test]
+    #[should_panic(expected = "Managers can't remove themselves")]
+    fn manager_cannot_remove_itself() {
+        let mut moderation = moderation();
+
+        set_context(MANAGER);
+        moderation.remove_manager(account(MANAGER));
+    }
+
+    fn moderation() -> Moderation {
+        Moderation::new(b"m".to_vec(), b"f".to_vec(), vec![account(MANAGER)])
+    }
+
+    fn account(account_id: &str) -> AccountId {
+        account_id.parse().unwrap()
+    }
+
+    fn set_context(predecessor: &str) {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(account(predecessor));
+
+        testing_env!(builder.build());
+    }
+}
@@ -0,0 +1,293 @@
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds,
+};
+use near_sdk::{
+    env, log, near, require, store::LookupMap, AccountId, IntoStorageKey,
+    NearToken, Promise,
+};
+
+/// Storage deposit of a registered account and the bytes it is charged for,
+/// including its own registration.
+#[near(serializers = [borsh])]
+#[derive(Debug, Clone)]
+pub struct StorageAccount {
+    pub deposit: NearToken,
+    pub storage_used: u64,
+}
+
+/// NEP-145 storage balances. A contract implements `StorageManagement` with
+/// [`impl_storage_management!`] and charges every write on behalf of an
+/// account with [`StorageAccounts::charge`].
+#[near(serializers = [borsh])]
+pub struct StorageAccounts {
+    accounts: LookupMap<AccountId, StorageAccount>,
+    /// Bytes a registration of the longest possible account id takes.
+    account_storage_usage: u64,
+}
+
+impl StorageAccounts {
+    pub fn new(prefix: impl IntoStorageKey) -> Self {
+        let mut accounts = LookupMap::new(prefix);
+
+        let storage_usage = env::storage_usage();
+        let account_id: AccountId = "a".repeat(64).parse().unwrap();
+
+        accounts.insert(
+            account_id.clone(),
+            StorageAccount {
+                deposit: NearToken::from_yoctonear(0),
+                storage_used: 0,
+            },
+        );
+        accounts.flush();
+
+        let account_storage_usage = env::storage_usage() - storage_usage;
+
+        accounts.remove(&account_id);
+        accounts.flush();
+
+        Self {
+            accounts,
+            account_storage_usage,
+        }
+    }
+
+    /// `storage_deposit` of the attached deposit.
+    pub fn deposit(
+        &mut self,
+        account_id: AccountId,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let registration_only = registration_only.unwrap_or(false);
+
+        let (mut account, refund) = match self.accounts.get(&account_id) {
+            Some(account) if registration_only => (account.clone(), amount),
+            Some(account) => (account.clone(), NearToken::from_yoctonear(0)),
+            None => {
+                let min_balance = self.balance_bounds().min;
+
+                require!(
+                    amount >= min_balance,
+                    "The attached deposit is less than the minimum storage \
+                     balance"
+                );
+
+                let account = StorageAccount {
+                    deposit: NearToken::from_yoctonear(0),
+                    storage_used: self.account_storage_usage,
+                };
+
+                if registration_only {
+                    (account, amount.saturating_sub(min_balance))
+                } else {
+                    (account, NearToken::from_yoctonear(0))
+                }
+            }
+        };
+
+        account.deposit = account
+            .deposit
+            .saturating_add(amount.saturating_sub(refund));
+
+        self.accounts.insert(account_id.clone(), account);
+
+        if !refund.is_zero() {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        self.balance(&account_id)
+    }
+
+    /// `storage_withdraw` of the caller. The caller checks the attached
+    /// yoctoNEAR.
+    pub fn withdraw(&mut self, amount: Option<NearToken>) -> StorageBalance {
+        let account_id = env::predecessor_account_id();
+        let available = self.balance(&account_id).available;
+        let amount = amount.unwrap_or(available);
+
+        require!(
+            amount <= available,
+            "The amount is greater than the available storage balance"
+        );
+
+        let account = self.accounts.get_mut(&account_id).unwrap();
+        account.deposit = account.deposit.saturating_sub(amount);
+
+        if !amount.is_zero() {
+            Promise::new(account_id.clone()).transfer(amount);
+        }
+
+        self.balance(&account_id)
+    }
+
+    /// Removes the registration of `account_id`. The caller removes what the
+    /// account stored and pays out the returned deposit.
+    pub fn unregister(
+        &mut self,
+        account_id: &AccountId,
+    ) -> Option<StorageAccount> {
+        let account = self.accounts.remove(account_id);
+
+        if account.is_none() {
+            log!("The account {} is not registered", account_id);
+        }
+
+        account
+    }
+
+    pub fn balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: env::storage_byte_cost()
+                .saturating_mul(self.account_storage_usage.into()),
+            max: None,
+        }
+    }
+
+    pub fn balance_of(&self, account_id: &AccountId) -> Option<StorageBalance> {
+        self.accounts
+            .contains_key(account_id)
+            .then(|| self.balance(account_id))
+    }
+
+    pub fn assert_registered(&self, account_id: &AccountId) {
+        require!(
+            self.accounts.contains_key(account_id),
+            "The account is not registered"
+        );
+    }
+
+    /// Charges `account_id` for the bytes added since `storage_usage` was
+    /// measured, or releases the bytes freed, and panics if its deposit no
+    /// longer covers what it uses.
+    ///
+    /// `store` collections only write on flush: flush the ones written to,
+    /// and [`StorageAccounts::flush`], before calling it.
+    pub fn charge(&mut self, account_id: &AccountId, storage_usage: u64) {
+        let storage_after = env::storage_usage();
+
+        let account = self
+            .accounts
+            .get_mut(account_id)
+            .unwrap_or_else(|| env::panic_str("The account is not registered"));
+
+        account.storage_used = (account.storage_used + storage_after)
+            .checked_sub(storage_usage)
+            .unwrap_or_else(|| env::panic_str("Storage usage underflow"));
+
+        require!(
+            env::storage_byte_cost()
+                .saturating_mul(account.storage_used.into())
+                <= account.deposit,
+            "Insufficient storage balance"
+        );
+    }
+
+    pub fn flush(&mut self) {
+        self.accounts.flush();
+    }
+
+    fn balance(&self, account_id: &AccountId) -> StorageBalance {
+        let account = self
+            .accounts
+            .get(account_id)
+            .unwrap_or_else(|| env::panic_str("The account is not registered"));
+
+        let used = env::storage_byte_cost()
+            .saturating_mul(account.storage_used.into());
+
+        StorageBalance {
+            total: account.deposit,
+            available: account.deposit.saturating_sub(used),
+        }
+    }
+}
+
+/// Implements `StorageManagement` for a contract that keeps its
+/// [`StorageAccounts`] in the field `$field`, and the contract's
+/// `internal_update_storage`, which charges an account for a write. The
+/// contract provides the parts that depend on what it stores:
+///
+/// - `internal_flush(&mut self, &AccountId)` flushes the collections written
+///   on behalf of the account and the storage accounts.
+/// - `internal_unregister(&mut self, &AccountId, force: bool) -> NearToken`
+///   removes what the account stored, refuses to without `force`, and
+///   returns what the account is owed on top of its deposit.
+#[macro_export]
+macro_rules! impl_storage_management {
+    ($contract:ident, $field:ident) => {
+        #[near_sdk::near]
+        impl $crate::__storage_management::StorageManagement for $contract {
+            #[payable]
+            fn storage_deposit(
+                &mut self,
+                account_id: Option<near_sdk::AccountId>,
+                registration_only: Option<bool>,
+            ) -> $crate::__storage_management::StorageBalance {
+                let account_id = account_id
+                    .unwrap_or_else(near_sdk::env::predecessor_account_id);
+
+                self.$field.deposit(account_id, registration_only)
+            }
+
+            #[payable]
+            fn storage_withdraw(
+                &mut self,
+                amount: Option<near_sdk::NearToken>,
+            ) -> $crate::__storage_management::StorageBalance {
+                near_sdk::assert_one_yocto();
+
+                self.$field.withdraw(amount)
+            }
+
+            #[payable]
+            fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+                near_sdk::assert_one_yocto();
+
+                let account_id = near_sdk::env::predecessor_account_id();
+
+                let Some(account) = self.$field.unregister(&account_id) else {
+                    return false;
+                };
+
+                let owed = self
+                    .internal_unregister(&account_id, force.unwrap_or(false));
+
+                near_sdk::Promise::new(account_id)
+                    .transfer(account.deposit.saturating_add(owed));
+
+                true
+            }
+
+            fn storage_balance_bounds(
+                &self,
+            ) -> $crate::__storage_management::StorageBalanceBounds {
+                self.$field.balance_bounds()
+            }
+
+            fn storage_balance_of(
+                &self,
+                account_id: near_sdk::AccountId,
+            ) -> Option<$crate::__storage_management::StorageBalance> {
+                self.$field.balance_of(&account_id)
+            }
+        }
+
+        impl $contract {
+            /// Charges `account_id` for the bytes added since
+            /// `storage_usage` was measured, or releases the bytes freed,
+            /// and panics if its deposit no longer covers what it uses.
+            pub(crate) fn internal_update_storage(
+                &mut self,
+                account_id: &near_sdk::AccountId,
+                storage_usage: u64,
+            ) {
+                // `store` collections only write on flush, flush them so
+                // that `storage_usage` reflects this call.
+                self.internal_flush(account_id);
+
+                self.$field.charge(account_id, storage_usage);
+            }
+        }
+    };
+}
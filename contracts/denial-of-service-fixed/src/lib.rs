@@ -0,0 +1,713 @@
+mod payouts;
+mod rewards;
+mod storage;
+
+use contract_common::{
+    limits::{
+        DEFAULT_NOTES_PER_REMOVAL, MAX_BODY_LENGTH, MAX_JARS_PER_BATCH,
+        MAX_NOTES_PER_PAGE, MAX_NOTES_PER_REMOVAL, MAX_TITLE_LENGTH,
+    },
+    StorageAccounts,
+};
+use near_sdk::{
+    env,
+    json_types::{U128, U64},
+    log, near, require,
+    store::{IterableMap, IterableSet, LookupMap},
+    AccountId, BorshStorageKey, CryptoHash, NearToken, PanicOnDefault, Promise,
+};
+use payouts::MAX_JARS_PER_CLAIM;
+use rewards::Distribution;
+
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PostedNote {
+    pub id: Option<U64>,
+    pub title: String,
+    pub body: String,
+}
+
+impl PostedNote {
+    pub fn new(title: String, body: String, id: Option<U64>) -> Self {
+        Self { title, body, id }
+    }
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MoneyJar {
+    pub amount: U128,
+    pub id: U128,
+}
+
+impl MoneyJar {
+    pub fn new(amount: U128, id: U128) -> Self {
+        Self { amount, id }
+    }
+}
+
+#[near]
+#[derive(BorshStorageKey)]
+pub enum StorageKey {
+    NotesPerUser,
+    Notes { account_hash: CryptoHash },
+    JarsPerUser,
+    Jars { account_hash: CryptoHash },
+    Accounts,
+    Withdrawable,
+}
+
+impl StorageKey {
+    pub fn notes(account_id: &AccountId) -> Self {
+        Self::Notes {
+            account_hash: env::sha256_array(account_id.as_bytes()),
+        }
+    }
+
+    pub fn jars(account_id: &AccountId) -> Self {
+        Self::Jars {
+            account_hash: env::sha256_array(account_id.as_bytes()),
+        }
+    }
+}
+
+/// Same notes and jars as the `denial-of-service` contract, except that
+/// every byte written on behalf of an account is paid from that account's
+/// NEP-145 storage balance instead of the contract's own balance.
+#[near(contract_state)]
+#[derive(PanicOnDefault)]
+pub struct Contract {
+    note_book: IterableMap<AccountId, IterableSet<PostedNote>>,
+    /// Jars keyed by id. A map rather than a set so that a jar can be
+    /// updated in place without moving it, which the reward cursor relies on.
+    jars_per_user: IterableMap<AccountId, IterableMap<u128, MoneyJar>>,
+    next_entry_id: u64,
+    storage: StorageAccounts,
+    jar_count: u64,
+    distribution: Option<Distribution>,
+    /// Claimed jar amounts waiting to be withdrawn.
+    withdrawable: LookupMap<AccountId, u128>,
+}
+
+#[near]
+impl Contract {
+    #[init]
+    pub fn new() -> Self {
+        Self {
+            note_book: IterableMap::new(StorageKey::NotesPerUser),
+            jars_per_user: IterableMap::new(StorageKey::JarsPerUser),
+            next_entry_id: 0,
+            storage: StorageAccounts::new(StorageKey::Accounts),
+            jar_count: 0,
+            distribution: None,
+            withdrawable: LookupMap::new(StorageKey::Withdrawable),
+        }
+    }
+
+    pub fn add_note(&mut self, title: String, body: String) {
+        let account_id = env::predecessor_account_id();
+        self.storage.assert_registered(&account_id);
+
+        require!(title.len() <= MAX_TITLE_LENGTH, "Note title is too long");
+        require!(body.len() <= MAX_BODY_LENGTH, "Note body is too long");
+
+        let storage_usage = env::storage_usage();
+
+        let note =
+            PostedNote::new(title, body, Some(self.next_entry_id.into()));
+
+        if let Some(notes) = self.note_book.get_mut(&account_id) {
+            notes.insert(note.clone());
+        } else {
+            let mut notes = IterableSet::new(StorageKey::notes(&account_id));
+            notes.insert(note.clone());
+
+            self.note_book.insert(account_id.clone(), notes);
+        }
+
+        self.next_entry_id += 1;
+
+        self.internal_update_storage(&account_id, storage_usage);
+
+        log!("Added note to the note book: {}", note.title);
+    }
+
+    /// Removes every note of the caller in one call. Clearing a set costs
+    /// gas per note, above `MAX_NOTES_PER_REMOVAL` notes the call could run
+    /// out of gas every time, use `remove_notes_batch` instead.
+    pub fn remove_all_notes(&mut self) {
+        let account_id = env::predecessor_account_id();
+
+        let storage_usage = env::storage_usage();
+
+        let mut removed = self
+            .note_book
+            .remove(&account_id)
+            .unwrap_or_else(|| env::panic_str("No user found"));
+
+        Self::assert_removable_at_once(removed.len());
+
+        removed.clear();
+        // The set is no longer reachable from `note_book`, flush it here
+        // rather than on drop so that the freed bytes are measured.
+        removed.flush();
+
+        self.internal_update_storage(&account_id, storage_usage);
+    }
+
+    /// Removes up to `limit` notes of the caller and returns how many are
+    /// left. Each call does a bounded amount of work, so any number of notes
+    /// can be removed in enough calls.
+    pub fn remove_notes_batch(&mut self, limit: Option<u32>) -> u32 {
+        let account_id = env::predecessor_account_id();
+
+        let limit = limit
+            .unwrap_or(DEFAULT_NOTES_PER_REMOVAL)
+            .min(MAX_NOTES_PER_REMOVAL);
+
+        require!(limit > 0, "Limit must be greater than 0");
+
+        let storage_usage = env::storage_usage();
+
+        let notes = self
+            .note_book
+            .get_mut(&account_id)
+            .unwrap_or_else(|| env::panic_str("No user found"));
+
+        // Remove notes from the end, removing the last element of a set
+        // doesn't move any other one.
+        let removed = notes
+            .iter()
+            .skip(notes.len().saturating_sub(limit) as usize)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for note in removed.iter().rev() {
+            notes.remove(note);
+        }
+
+        let left = notes.len();
+
+        if left == 0 {
+            if let Some(mut notes) = self.note_book.remove(&account_id) {
+                // Flush the removals, the set is no longer reachable from
+                // `note_book`.
+                notes.flush();
+            }
+        }
+
+        self.internal_update_storage(&account_id, storage_usage);
+
+        log!(
+            "Removed {} notes of {}, {} left",
+            removed.len(),
+            account_id,
+            left
+        );
+
+        left
+    }
+
+    /// Returns at most `MAX_NOTES_PER_PAGE` notes, so that however many
+    /// notes an account posts a page stays within the view gas limit.
+    pub fn get_notes(
+        &self,
+        account_id: AccountId,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<&PostedNote> {
+        let limit = limit.unwrap_or(MAX_NOTES_PER_PAGE).min(MAX_NOTES_PER_PAGE);
+
+        self.note_book
+            .get(&account_id)
+            .map(|notes| {
+                notes
+                    .iter()
+                    .skip(from_index.unwrap_or(0) as usize)
+                    .take(limit as usize)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The jar amount has to be attached, `claim_all_jars` pays it back.
+    #[payable]
+    pub fn create_jar(&mut self, amount: U128, id: U128) {
+        let account_id = env::predecessor_account_id();
+        self.storage.assert_registered(&account_id);
+        self.internal_fund_jars(&account_id, amount.0);
+
+        let storage_usage = env::storage_usage();
+
+        self.internal_insert_jar(&account_id, MoneyJar::new(amount, id));
+
+        self.internal_update_storage(&account_id, storage_usage);
+
+        log!("Created jar for user: {}", account_id);
+    }
+
+    #[payable]
+    pub fn batch_create_jars(&mut self, jars: Vec<(U128, U128)>) {
+        let account_id = env::predecessor_account_id();
+        self.storage.assert_registered(&account_id);
+
+        require!(jars.len() <= MAX_JARS_PER_BATCH, "Too many jars in a batch");
+
+        self.internal_fund_jars(
+            &account_id,
+            jars.iter()
+                .try_fold(0u128, |total, (amount, _)| {
+                    total.checked_add(amount.0)
+                })
+                .unwrap_or_else(|| env::panic_str("Jar amounts overflow")),
+        );
+
+        let storage_usage = env::storage_usage();
+
+        for (amount, id) in jars {
+            self.internal_insert_jar(&account_id, MoneyJar::new(amount, id));
+        }
+
+        self.internal_update_storage(&account_id, storage_usage);
+
+        log!("Created jars for user: {}", account_id);
+    }
+
+    /// Pays out and removes every jar of the caller. Unlike the
+    /// `denial-of-service` contract, which sums up clones and leaves the
+    /// stored jars untouched, a second call has nothing left to claim.
+    /// Clearing the jars costs gas per jar, above `MAX_JARS_PER_CLAIM` jars
+    /// the call is refused, use `claim_jars` instead.
+    pub fn claim_all_jars(&mut self) -> Promise {
+        let account_id = env::predecessor_account_id();
+
+        let storage_usage = env::storage_usage();
+
+        let jars = self
+            .jars_per_user
+            .get_mut(&account_id)
+            .filter(|jars| !jars.is_empty())
+            .unwrap_or_else(|| env::panic_str("No jars found for account"));
+
+        require!(
+            jars.len() <= MAX_JARS_PER_CLAIM,
+            "Too many jars to claim at once, use claim_jars"
+        );
+
+        let total_amount = jars.values().map(|jar| jar.amount.0).sum::<u128>();
+        let claimed = jars.len();
+
+        // Keep the emptied map in `jars_per_user`, removing the entry would
+        // move another account under the reward cursor.
+        jars.clear();
+
+        self.jar_count -= u64::from(claimed);
+        self.internal_rewind_cursor(&account_id);
+
+        self.internal_update_storage(&account_id, storage_usage);
+
+        log!("Claimed {} jars of {}", claimed, account_id);
+
+        Promise::new(account_id)
+            .transfer(NearToken::from_yoctonear(total_amount))
+    }
+
+    pub fn get_jars(&self, account_id: AccountId) -> Vec<&MoneyJar> {
+        self.jars_per_user
+            .get(&account_id)
+            .map(|jars| jars.values().collect())
+            .unwrap_or_default()
+    }
+
+    /// Refunds whatever was attached on top of `amount`.
+    fn internal_fund_jars(&self, account_id: &AccountId, amount: u128) {
+        let deposit = env::attached_deposit().as_yoctonear();
+
+        require!(
+            deposit >= amount,
+            "Attached deposit must cover the jar amounts"
+        );
+
+        if deposit > amount {
+            Promise::new(account_id.clone())
+                .transfer(NearToken::from_yoctonear(deposit - amount));
+        }
+    }
+
+    fn internal_insert_jar(&mut self, account_id: &AccountId, jar: MoneyJar) {
+        if let Some(jars) = self.jars_per_user.get_mut(account_id) {
+            // Overwriting a jar would lock its funded amount.
+            require!(!jars.contains_key(&jar.id.0), "Jar already exists");
+
+            jars.insert(jar.id.0, jar);
+        } else {
+            let mut jars = IterableMap::new(StorageKey::jars(account_id));
+            jars.insert(jar.id.0, jar);
+
+            self.jars_per_user.insert(account_id.clone(), jars);
+        }
+
+        self.jar_count += 1;
+    }
+
+    pub(crate) fn assert_removable_at_once(notes: u32) {
+        require!(
+            notes <= MAX_NOTES_PER_REMOVAL,
+            "Too many notes to remove at once, use remove_notes_batch"
+        );
+    }
+
+    fn internal_flush(&mut self, account_id: &AccountId) {
+        if let Some(notes) = self.note_book.get_mut(account_id) {
+            notes.flush();
+        }
+
+        if let Some(jars) = self.jars_per_user.get_mut(account_id) {
+            jars.flush();
+        }
+
+        self.note_book.flush();
+        self.jars_per_user.flush();
+        self.storage.flush();
+        self.withdrawable.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::{
+        mock::MockAction,
+        test_utils::{get_created_receipts, VMContextBuilder},
+        testing_env, NearToken,
+    };
+
+    use super::*;
+
+    #[test]
+    fn batch_create_jars_charges_storage_balance() {
+        let mut contract = Contract::new();
+        let account_id: AccountId = "account_id".parse().unwrap();
+
+        set_context(account_id.as_str(), NearToken::from_millinear(100));
+        let registered = contract.storage_deposit(None, None);
+
+        contract
+            .batch_create_jars((0..10).map(|i| (U128(2), U128(i))).collect());
+
+        let balance = contract.storage_balance_of(account_id.clone()).unwrap();
+
+        assert_eq!(contract.get_jars(account_id).len(), 10);
+        assert!(balance.available < registered.available);
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient storage balance")]
+    fn batch_create_jars_insufficient_storage_balance() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_millinear(10));
+        contract.storage_deposit(None, None);
+
+        // ~200 bytes per jar, 10 mNEAR only cover 1000 bytes.
+        contract
+            .batch_create_jars((0..100).map(|i| (U128(2), U128(i))).collect());
+    }
+
+    #[test]
+    #[should_panic(expected = "Attached deposit must cover the jar amounts")]
+    fn batch_create_jars_requires_funding() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_millinear(100));
+        contract.storage_deposit(None, None);
+
+        contract.batch_create_jars(vec![(
+            U128(NearToken::from_near(1).as_yoctonear()),
+            U128(0),
+        )]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Jar already exists")]
+    fn create_jar_same_id() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_millinear(100));
+        contract.storage_deposit(None, None);
+
+        contract.create_jar(U128(2), U128(0));
+        contract.create_jar(U128(2), U128(0));
+    }
+
+    #[test]
+    fn claim_all_jars_removes_jars() {
+        let mut contract = Contract::new();
+        let account_id: AccountId = "account_id".parse().unwrap();
+
+        set_context(account_id.as_str(), NearToken::from_millinear(100));
+        contract.storage_deposit(None, None);
+
+        contract
+            .batch_create_jars((0..10).map(|i| (U128(2), U128(i))).collect());
+
+        let created = contract.storage_balance_of(account_id.clone()).unwrap();
+
+        contract.claim_all_jars();
+
+        assert!(contract.get_jars(account_id.clone()).is_empty());
+        assert_eq!(contract.jar_count, 0);
+
+        // The jars' bytes are released back to the storage balance.
+        assert!(
+            contract.storage_balance_of(account_id).unwrap().available
+                > created.available
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "No jars found for account")]
+    fn claim_all_jars_twice() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_millinear(100));
+        contract.storage_deposit(None, None);
+
+        contract.create_jar(U128(2), U128(0));
+        contract.claim_all_jars();
+        contract.claim_all_jars();
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many jars to claim at once, use claim_jars")]
+    fn claim_all_jars_too_many() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_near(10));
+        contract.storage_deposit(None, None);
+
+        for chunk in (0..=MAX_JARS_PER_CLAIM as u128)
+            .map(|i| (U128(2), U128(i)))
+            .collect::<Vec<_>>()
+            .chunks(MAX_JARS_PER_BATCH)
+        {
+            contract.batch_create_jars(chunk.to_vec());
+        }
+
+        contract.claim_all_jars();
+    }
+
+    #[test]
+    fn storage_unregister_force_pays_out_jars() {
+        let mut contract = Contract::new();
+        let account_id: AccountId = "account_id".parse().unwrap();
+
+        set_context(account_id.as_str(), NearToken::from_millinear(100));
+        let registered = contract.storage_deposit(None, None);
+
+        contract
+            .batch_create_jars((0..10).map(|i| (U128(2), U128(i))).collect());
+
+        set_context(account_id.as_str(), NearToken::from_yoctonear(1));
+        assert!(contract.storage_unregister(Some(true)));
+
+        assert!(contract.get_jars(account_id).is_empty());
+        assert_eq!(contract.jar_count, 0);
+
+        // The deposit and the 10 jars of 2 yoctoNEAR in one transfer.
+        let receipts = get_created_receipts();
+
+        assert_eq!(receipts.len(), 1);
+        assert!(matches!(
+            receipts[0].actions[..],
+            [MockAction::Transfer { deposit, .. }]
+                if deposit == registered.total.saturating_add(
+                    NearToken::from_yoctonear(20)
+                )
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "The account is not registered")]
+    fn add_note_requires_registration() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_yoctonear(0));
+        contract.add_note("title".into(), "body".into());
+    }
+
+    #[test]
+    fn add_note_longest_allowed() {
+        let mut contract = Contract::new();
+        let account_id: AccountId = "account_id".parse().unwrap();
+
+        set_context(account_id.as_str(), NearToken::from_millinear(100));
+        contract.storage_deposit(None, None);
+
+        contract.add_note(
+            "t".repeat(MAX_TITLE_LENGTH),
+            "b".repeat(MAX_BODY_LENGTH),
+        );
+
+        assert_eq!(contract.get_notes(account_id, None, None).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Note title is too long")]
+    fn add_note_title_too_long() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_millinear(100));
+        contract.storage_deposit(None, None);
+
+        contract.add_note("t".repeat(MAX_TITLE_LENGTH + 1), "body".into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Note body is too long")]
+    fn add_note_body_too_long() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_millinear(100));
+        contract.storage_deposit(None, None);
+
+        contract.add_note("title".into(), "b".repeat(MAX_BODY_LENGTH + 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many jars in a batch")]
+    fn batch_create_jars_too_many() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_near(1));
+        contract.storage_deposit(None, None);
+
+        contract.batch_create_jars(
+            (0..=MAX_JARS_PER_BATCH as u128)
+                .map(|i| (U128(0), U128(i)))
+                .collect(),
+        );
+    }
+
+    #[test]
+    fn get_notes_is_paged() {
+        let mut contract = Contract::new();
+        let account_id: AccountId = "account_id".parse().unwrap();
+
+        set_context(account_id.as_str(), NearToken::from_near(1));
+        contract.storage_deposit(None, None);
+
+        for i in 0..MAX_NOTES_PER_PAGE + 1 {
+            contract.add_note(format!("title{i}"), "body".into());
+        }
+
+        assert_eq!(
+            contract.get_notes(account_id.clone(), None, None).len() as u32,
+            MAX_NOTES_PER_PAGE
+        );
+        assert_eq!(
+            contract
+                .get_notes(account_id.clone(), None, Some(u32::MAX))
+                .len() as u32,
+            MAX_NOTES_PER_PAGE
+        );
+        assert_eq!(
+            contract
+                .get_notes(account_id, Some(MAX_NOTES_PER_PAGE), None)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn remove_all_notes_releases_storage() {
+        let mut contract = Contract::new();
+        let account_id: AccountId = "account_id".parse().unwrap();
+
+        set_context(account_id.as_str(), NearToken::from_millinear(100));
+        let registered = contract.storage_deposit(None, None);
+
+        contract.add_note("title".into(), "body".into());
+        contract.add_note("title2".into(), "body2".into());
+
+        assert!(
+            contract
+                .storage_balance_of(account_id.clone())
+                .unwrap()
+                .available
+                < registered.available
+        );
+
+        contract.remove_all_notes();
+
+        assert_eq!(
+            contract.storage_balance_of(account_id).unwrap().available,
+            registered.available
+        );
+    }
+
+    #[test]
+    fn remove_notes_batch_in_chunks() {
+        let mut contract = Contract::new();
+        let account_id: AccountId = "account_id".parse().unwrap();
+
+        set_context(account_id.as_str(), NearToken::from_near(1));
+        let registered = contract.storage_deposit(None, None);
+
+        for i in 0..5 {
+            contract.add_note(format!("title{i}"), "body".into());
+        }
+
+        assert_eq!(contract.remove_notes_batch(Some(3)), 2);
+        assert_eq!(contract.get_notes(account_id.clone(), None, None).len(), 2);
+
+        assert_eq!(contract.remove_notes_batch(None), 0);
+        assert!(contract
+            .get_notes(account_id.clone(), None, None)
+            .is_empty());
+
+        assert_eq!(
+            contract.storage_balance_of(account_id).unwrap().available,
+            registered.available
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "No user found")]
+    fn remove_notes_batch_nothing_left() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_near(1));
+        contract.storage_deposit(None, None);
+
+        contract.add_note("title".into(), "body".into());
+
+        contract.remove_notes_batch(None);
+        contract.remove_notes_batch(None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Too many notes to remove at once, use remove_notes_batch"
+    )]
+    fn remove_all_notes_too_many() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_near(1));
+        contract.storage_deposit(None, None);
+
+        for i in 0..=MAX_NOTES_PER_REMOVAL {
+            contract.add_note(format!("title{i}"), "body".into());
+        }
+
+        contract.remove_all_notes();
+    }
+
+    fn set_context(predecessor: &str, amount: NearToken) {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor.parse().unwrap());
+        builder.attached_deposit(amount);
+
+        testing_env!(builder.build());
+    }
+}
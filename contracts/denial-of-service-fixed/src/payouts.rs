@@ -0,0 +1,200 @@
+use near_sdk::{
+    env, is_promise_success, json_types::U128, log, near, require, AccountId,
+    Gas, NearToken, Promise,
+};
+
+use crate::{Contract, ContractExt};
+
+/// Jars claimed by `claim_jars` when no limit is given.
+pub const DEFAULT_JARS_PER_CLAIM: u32 = 100;
+
+/// Upper bound on the jars claimed in one call, well below the gas limit.
+pub const MAX_JARS_PER_CLAIM: u32 = 500;
+
+const ON_WITHDRAW_GAS: Gas = Gas::from_tgas(10);
+
+/// Pull payments, replacing the one promise per jar of
+/// `claim_all_jars_per_jar` in the `denial-of-service` contract. Claiming
+/// only moves amounts from jars into a withdrawable balance, a bounded
+/// amount of work per call that creates no promises. `withdraw` then pays
+/// the whole balance with one transfer and restores it if the transfer
+/// fails.
+#[near]
+impl Contract {
+    /// Removes up to `limit` jars of the caller and credits their amounts to
+    /// its withdrawable balance, which it returns.
+    pub fn claim_jars(&mut self, limit: Option<u32>) -> U128 {
+        let account_id = env::predecessor_account_id();
+
+        let limit = limit
+            .unwrap_or(DEFAULT_JARS_PER_CLAIM)
+            .min(MAX_JARS_PER_CLAIM);
+
+        require!(limit > 0, "Limit must be greater than 0");
+
+        let storage_usage = env::storage_usage();
+
+        let jars = self
+            .jars_per_user
+            .get_mut(&account_id)
+            .filter(|jars| !jars.is_empty())
+            .unwrap_or_else(|| env::panic_str("No jars found for account"));
+
+        // Take jars from the end and remove the last one first, removing the
+        // last entry doesn't move any other jar under the reward cursor.
+        let ids = jars
+            .keys()
+            .skip(jars.len().saturating_sub(limit) as usize)
+            .copied()
+            .collect::<Vec<_>>();
+
+        let claimed = ids
+            .iter()
+            .rev()
+            .map(|id| jars.remove(id).unwrap().amount.0)
+            .sum::<u128>();
+
+        self.jar_count -= ids.len() as u64;
+
+        let withdrawable =
+            self.withdrawable.entry(account_id.clone()).or_insert(0);
+        *withdrawable += claimed;
+        let withdrawable = *withdrawable;
+
+        self.internal_update_storage(&account_id, storage_usage);
+
+        log!("Claimed {} jars of {}", ids.len(), account_id);
+
+        U128(withdrawable)
+    }
+
+    /// Pays out the caller's whole withdrawable balance.
+    pub fn withdraw(&mut self) -> Promise {
+        let account_id = env::predecessor_account_id();
+
+        let amount = self
+            .withdrawable
+            .get_mut(&account_id)
+            .map(std::mem::take)
+            .filter(|amount| *amount > 0)
+            .unwrap_or_else(|| env::panic_str("Nothing to withdraw"));
+
+        Promise::new(account_id.clone())
+            .transfer(NearToken::from_yoctonear(amount))
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(ON_WITHDRAW_GAS)
+                    .on_withdraw(account_id, U128(amount)),
+            )
+    }
+
+    /// Restores the balance if the transfer failed, e.g. because the account
+    /// was deleted in the meantime.
+    #[private]
+    pub fn on_withdraw(&mut self, account_id: AccountId, amount: U128) -> bool {
+        if is_promise_success() {
+            log!("Withdrew {} for {}", amount.0, account_id);
+
+            return true;
+        }
+
+        let withdrawable =
+            self.withdrawable.entry(account_id.clone()).or_insert(0);
+        *withdrawable += amount.0;
+
+        log!(
+            "Withdrawal failed, restored {} for {}",
+            amount.0,
+            account_id
+        );
+
+        false
+    }
+
+    pub fn get_withdrawable(&self, account_id: AccountId) -> U128 {
+        U128(self.withdrawable.get(&account_id).copied().unwrap_or(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::{
+        test_utils::VMContextBuilder, test_vm_config, testing_env,
+        PromiseResult, RuntimeFeesConfig,
+    };
+
+    use super::*;
+
+    const ALICE: &str = "alice.near";
+
+    #[test]
+    fn claim_jars_in_chunks() {
+        let mut contract = contract_with_jars(5);
+
+        assert_eq!(contract.claim_jars(Some(3)), U128(30));
+        assert_eq!(contract.get_jars(ALICE.parse().unwrap()).len(), 2);
+
+        assert_eq!(contract.claim_jars(None), U128(50));
+        assert!(contract.get_jars(ALICE.parse().unwrap()).is_empty());
+        assert_eq!(contract.jar_count, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "No jars found for account")]
+    fn claim_jars_nothing_left() {
+        let mut contract = contract_with_jars(1);
+
+        contract.claim_jars(None);
+        contract.claim_jars(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Nothing to withdraw")]
+    fn withdraw_twice() {
+        let mut contract = contract_with_jars(1);
+
+        contract.claim_jars(None);
+        contract.withdraw();
+
+        assert_eq!(contract.get_withdrawable(ALICE.parse().unwrap()), U128(0));
+
+        contract.withdraw();
+    }
+
+    #[test]
+    fn failed_withdrawal_is_restored() {
+        let mut contract = contract_with_jars(2);
+
+        contract.claim_jars(None);
+        contract.withdraw();
+
+        testing_env!(
+            VMContextBuilder::new().build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        assert!(!contract.on_withdraw(ALICE.parse().unwrap(), U128(20)));
+        assert_eq!(contract.get_withdrawable(ALICE.parse().unwrap()), U128(20));
+    }
+
+    fn contract_with_jars(count: u128) -> Contract {
+        let mut contract = Contract::new();
+
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(ALICE.parse().unwrap());
+        builder.attached_deposit(NearToken::from_near(1));
+
+        testing_env!(builder.build());
+
+        contract.storage_deposit(None, None);
+        contract.batch_create_jars(
+            (0..count).map(|id| (U128(10), U128(id))).collect(),
+        );
+
+        contract
+    }
+}
@@ -0,0 +1,296 @@
+use near_sdk::{env, json_types::U128, log, near, require, AccountId};
+
+use crate::{Contract, ContractExt};
+
+/// Jars rewarded by `distribute_rewards` when no limit is given.
+pub const DEFAULT_JARS_PER_CALL: u32 = 100;
+
+/// Upper bound on the jars rewarded in one call, well below the gas limit.
+pub const MAX_JARS_PER_CALL: u32 = 500;
+
+/// Progress of a reward distribution. The cursor points at the next jar to
+/// reward: the `jar_index`th jar of the `account_index`th account.
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Distribution {
+    pub reward_per_jar: U128,
+    pub account_index: u32,
+    pub jar_index: u32,
+    pub jars_rewarded: u64,
+    /// Jars there were when the distribution started.
+    pub jars_total: u64,
+    pub finished: bool,
+}
+
+/// Replaces `add_reward_to_each_jar` of the `denial-of-service` contract.
+/// Instead of rewarding every jar in one call, each `distribute_rewards`
+/// call rewards a bounded chunk and persists the cursor. A chunk that fails
+/// reverts with its transaction and leaves the cursor where it was, so the
+/// next call resumes from the same jar and nothing is rewarded twice.
+#[near]
+impl Contract {
+    #[private]
+    pub fn start_reward_distribution(&mut self, reward_per_jar: U128) {
+        require!(
+            !self.is_distributing(),
+            "A reward distribution is already in progress"
+        );
+
+        self.distribution = Some(Distribution {
+            reward_per_jar,
+            account_index: 0,
+            jar_index: 0,
+            jars_rewarded: 0,
+            jars_total: self.jar_count,
+            finished: false,
+        });
+    }
+
+    /// Rewards up to `limit` jars from the cursor on. Anyone can call it,
+    /// the reward was fixed when the distribution started.
+    pub fn distribute_rewards(&mut self, limit: Option<u32>) -> Distribution {
+        let mut distribution = self
+            .distribution
+            .clone()
+            .filter(|distribution| !distribution.finished)
+            .unwrap_or_else(|| {
+                env::panic_str("No reward distribution in progress")
+            });
+
+        let mut remaining = limit
+            .unwrap_or(DEFAULT_JARS_PER_CALL)
+            .min(MAX_JARS_PER_CALL);
+
+        require!(remaining > 0, "Limit must be greater than 0");
+
+        while remaining > 0
+            && distribution.account_index < self.jars_per_user.len()
+        {
+            let account_id = self
+                .jars_per_user
+                .keys()
+                .nth(distribution.account_index as usize)
+                .cloned()
+                .unwrap_or_else(|| env::panic_str("Cursor out of bounds"));
+
+            let jars = self
+                .jars_per_user
+                .get_mut(&account_id)
+                .unwrap_or_else(|| env::panic_str("No jars found for account"));
+
+            let ids = jars
+                .keys()
+                .skip(distribution.jar_index as usize)
+                .take(remaining as usize)
+                .copied()
+                .collect::<Vec<_>>();
+
+            for id in &ids {
+                let jar = jars.get_mut(id).unwrap();
+                jar.amount.0 += distribution.reward_per_jar.0;
+            }
+
+            distribution.jar_index += ids.len() as u32;
+            distribution.jars_rewarded += ids.len() as u64;
+            remaining -= ids.len() as u32;
+
+            if distribution.jar_index >= jars.len() {
+                distribution.account_index += 1;
+                distribution.jar_index = 0;
+            }
+        }
+
+        distribution.finished =
+            distribution.account_index >= self.jars_per_user.len();
+
+        log!(
+            "Rewarded {} of {} jars",
+            distribution.jars_rewarded,
+            distribution.jars_total
+        );
+
+        self.distribution = Some(distribution.clone());
+
+        distribution
+    }
+
+    pub fn get_reward_distribution(&self) -> Option<&Distribution> {
+        self.distribution.as_ref()
+    }
+}
+
+impl Contract {
+    pub(crate) fn is_distributing(&self) -> bool {
+        self.distribution
+            .as_ref()
+            .is_some_and(|distribution| !distribution.finished)
+    }
+
+    /// Moves the cursor back to the first jar if it is within the jars of
+    /// `account_id`, which were just cleared. New jars start at index 0.
+    pub(crate) fn internal_rewind_cursor(&mut self, account_id: &AccountId) {
+        let Some(distribution) = self
+            .distribution
+            .as_mut()
+            .filter(|distribution| !distribution.finished)
+        else {
+            return;
+        };
+
+        if self
+            .jars_per_user
+            .keys()
+            .nth(distribution.account_index as usize)
+            == Some(account_id)
+        {
+            distribution.jar_index = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::{test_utils::VMContextBuilder, testing_env, NearToken};
+
+    use super::*;
+
+    const ALICE: &str = "alice.near";
+    const BOB: &str = "bob.near";
+
+    #[test]
+    fn distributes_in_chunks() {
+        let mut contract = contract_with_jars(&[(ALICE, 5), (BOB, 3)]);
+
+        contract.start_reward_distribution(U128(10));
+
+        let progress = contract.distribute_rewards(Some(4));
+
+        assert_eq!((progress.account_index, progress.jar_index), (0, 4));
+        assert_eq!(progress.jars_rewarded, 4);
+        assert!(!progress.finished);
+
+        // Crosses into bob's jars.
+        let progress = contract.distribute_rewards(Some(3));
+
+        assert_eq!((progress.account_index, progress.jar_index), (1, 2));
+        assert!(!progress.finished);
+
+        let progress = contract.distribute_rewards(None);
+
+        assert_eq!(progress.jars_rewarded, 8);
+        assert_eq!(progress.jars_total, 8);
+        assert!(progress.finished);
+
+        for account_id in [ALICE, BOB] {
+            assert!(contract
+                .get_jars(account_id.parse().unwrap())
+                .iter()
+                .all(|jar| jar.amount.0 == 12));
+        }
+    }
+
+    #[test]
+    fn finishes_on_exact_chunk() {
+        let mut contract = contract_with_jars(&[(ALICE, 4)]);
+
+        contract.start_reward_distribution(U128(10));
+
+        assert!(contract.distribute_rewards(Some(4)).finished);
+    }
+
+    #[test]
+    fn limit_is_capped() {
+        let mut contract =
+            contract_with_jars(&[(ALICE, MAX_JARS_PER_CALL as u128 + 1)]);
+
+        contract.start_reward_distribution(U128(10));
+
+        let progress = contract.distribute_rewards(Some(u32::MAX));
+
+        assert_eq!(progress.jars_rewarded, MAX_JARS_PER_CALL as u64);
+        assert!(!progress.finished);
+    }
+
+    #[test]
+    fn claim_rewinds_cursor() {
+        let mut contract = contract_with_jars(&[(ALICE, 4), (BOB, 2)]);
+
+        contract.start_reward_distribution(U128(10));
+        contract.distribute_rewards(Some(2));
+
+        set_context(ALICE, NearToken::from_near(1));
+        contract.claim_all_jars();
+        contract
+            .batch_create_jars(vec![(U128(2), U128(4)), (U128(2), U128(5))]);
+
+        let progress = contract.distribute_rewards(None);
+
+        assert_eq!(progress.jars_rewarded, 6);
+        assert!(progress.finished);
+
+        // Alice's new jars were not skipped.
+        assert!(contract
+            .get_jars(ALICE.parse().unwrap())
+            .iter()
+            .all(|jar| jar.amount.0 == 12));
+    }
+
+    #[test]
+    #[should_panic(expected = "No reward distribution in progress")]
+    fn distribute_after_finished() {
+        let mut contract = contract_with_jars(&[(ALICE, 1)]);
+
+        contract.start_reward_distribution(U128(10));
+        contract.distribute_rewards(None);
+        contract.distribute_rewards(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "A reward distribution is already in progress")]
+    fn start_while_distributing() {
+        let mut contract = contract_with_jars(&[(ALICE, 2)]);
+
+        contract.start_reward_distribution(U128(10));
+        contract.distribute_rewards(Some(1));
+        contract.start_reward_distribution(U128(20));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Can't unregister the account with jars while rewards are \
+                    being distributed"
+    )]
+    fn unregister_while_distributing() {
+        let mut contract = contract_with_jars(&[(ALICE, 2), (BOB, 2)]);
+
+        contract.start_reward_distribution(U128(10));
+        contract.distribute_rewards(Some(1));
+
+        set_context(ALICE, NearToken::from_yoctonear(1));
+        contract.storage_unregister(Some(true));
+    }
+
+    fn contract_with_jars(jars: &[(&str, u128)]) -> Contract {
+        let mut contract = Contract::new();
+
+        for (account_id, count) in jars {
+            set_context(account_id, NearToken::from_near(10));
+            contract.storage_deposit(None, None);
+
+            contract.batch_create_jars(
+                (0..*count).map(|id| (U128(2), U128(id))).collect(),
+            );
+        }
+
+        contract
+    }
+
+    fn set_context(predecessor: &str, amount: NearToken) {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor.parse().unwrap());
+        builder.attached_deposit(amount);
+
+        testing_env!(builder.build());
+    }
+}
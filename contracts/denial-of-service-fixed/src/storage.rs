@@ -0,0 +1,60 @@
+use contract_common::impl_storage_management;
+use near_sdk::{require, AccountId, NearToken};
+
+use crate::{payouts::MAX_JARS_PER_CLAIM, Contract, ContractExt};
+
+impl_storage_management!(Contract, storage);
+
+impl Contract {
+    /// Removes the notes and jars of `account_id` for `storage_unregister`
+    /// and returns what it is owed on top of its deposit: the funded amounts
+    /// of its jars and its withdrawable balance.
+    fn internal_unregister(
+        &mut self,
+        account_id: &AccountId,
+        force: bool,
+    ) -> NearToken {
+        // Removing an account moves the last one into its slot, which would
+        // make the reward cursor skip it.
+        require!(
+            !self.jars_per_user.contains_key(account_id)
+                || !self.is_distributing(),
+            "Can't unregister the account with jars while rewards are being \
+             distributed"
+        );
+
+        let notes = self.note_book.remove(account_id);
+        let jars = self.jars_per_user.remove(account_id);
+
+        if notes.is_some() || jars.as_ref().is_some_and(|jars| !jars.is_empty())
+        {
+            require!(
+                force,
+                "Can't unregister the account with notes or jars, use force"
+            );
+        }
+
+        if let Some(mut notes) = notes {
+            Self::assert_removable_at_once(notes.len());
+            notes.clear();
+        }
+
+        let mut owed = self.withdrawable.remove(account_id).unwrap_or(0);
+
+        if let Some(mut jars) = jars {
+            require!(
+                jars.len() <= MAX_JARS_PER_CLAIM,
+                "Too many jars to claim at once, use claim_jars"
+            );
+
+            // Every jar was paid in, deleting it without paying it out would
+            // lock its amount in the contract.
+            owed += jars.values().map(|jar| jar.amount.0).sum::<u128>();
+
+            self.jar_count -= u64::from(jars.len());
+            jars.clear();
+        }
+
+        NearToken::from_yoctonear(owed)
+    }
+}
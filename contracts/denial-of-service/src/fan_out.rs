@@ -0,0 +1,46 @@
+use near_sdk::{
+    env, is_promise_success, log, near, AccountId, NearToken, Promise,
+};
+
+use crate::{Contract, ContractExt, MoneyJar};
+
+#[near]
+impl Contract {
+    /// Vulnerable: pays out every jar with its own transfer and removes the
+    /// jar in a callback. Each jar costs two receipts, so the call runs out
+    /// of gas at a few dozen jars and the jars can never be claimed. Below
+    /// that, the callbacks split whatever gas is left: with enough jars they
+    /// fail after the transfers went through, and the paid jars stay
+    /// claimable.
+    pub fn claim_all_jars_per_jar(&mut self) {
+        let account_id = env::predecessor_account_id();
+
+        let jars = self
+            .jars_per_user
+            .get(&account_id)
+            .unwrap_or_else(|| env::panic_str("No jars found for account"));
+
+        for jar in jars.iter() {
+            Promise::new(account_id.clone())
+                .transfer(NearToken::from_yoctonear(jar.amount.0))
+                .then(
+                    Self::ext(env::current_account_id())
+                        .on_jar_paid(account_id.clone(), jar.clone()),
+                );
+        }
+    }
+
+    #[private]
+    pub fn on_jar_paid(&mut self, account_id: AccountId, jar: MoneyJar) {
+        if is_promise_success() {
+            self.jars_per_user
+                .get_mut(&account_id)
+                .unwrap_or_else(|| env::panic_str("No jars found for account"))
+                .remove(&jar);
+
+            log!("Paid jar: {}", jar.id.0);
+        } else {
+            log!("Payout failed for jar: {}", jar.id.0);
+        }
+    }
+}
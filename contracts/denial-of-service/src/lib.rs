@@ -1,11 +1,16 @@
+mod fan_out;
+mod moderation;
+mod probes;
+
 use std::{fmt::Display, u32};
 
+use contract_common::Moderation;
 use near_sdk::{
     collections::{LookupMap as LookUpMapCollections, UnorderedSet},
     env,
     json_types::{U128, U64},
     log, near, require,
-    store::{IterableMap, IterableSet, LookupSet},
+    store::{IterableMap, IterableSet, LookupMap},
     AccountId, BorshStorageKey, NearToken, PanicOnDefault, Promise,
 };
 
@@ -51,6 +56,8 @@ pub enum StorageKey {
     Jars(String),
     Managers,
     UserPoints,
+    FrozenUsers,
+    NotesById,
 }
 
 // Define the contract structure
@@ -62,7 +69,8 @@ pub struct Contract {
         LookUpMapCollections<AccountId, UnorderedSet<PostedNote>>,
     jars_per_user: IterableMap<AccountId, IterableSet<MoneyJar>>,
     next_entry_id: Option<u64>,
-    managers: LookupSet<AccountId>,
+    moderation: Moderation,
+    notes_by_id: LookupMap<u64, (AccountId, PostedNote)>,
 }
 
 // Implement the contract structure
@@ -70,24 +78,24 @@ pub struct Contract {
 impl Contract {
     #[init]
     pub fn new(managers: Vec<AccountId>) -> Self {
-        let mut managers_set = LookupSet::new(StorageKey::Managers);
-
-        managers.into_iter().for_each(|manager| {
-            managers_set.insert(manager);
-        });
-
         Self {
             note_book: IterableMap::new(StorageKey::NotesPerUser),
             note_book_collections: LookUpMapCollections::new(b"mm".to_vec()),
             jars_per_user: IterableMap::new(StorageKey::JarsPerUser),
-            managers: managers_set,
+            moderation: Moderation::new(
+                StorageKey::Managers,
+                StorageKey::FrozenUsers,
+                managers,
+            ),
             next_entry_id: None,
+            notes_by_id: LookupMap::new(StorageKey::NotesById),
         }
     }
 
     #[payable]
     pub fn add_note(&mut self, title: String, body: String) {
         let account_id = env::predecessor_account_id();
+        self.assert_not_frozen(&account_id);
 
         let next_entry_id = self.next_entry_id.unwrap_or(0);
 
@@ -229,7 +237,13 @@ impl Contract {
     pub fn remove_all_notes(&mut self) {
         let account_id = env::predecessor_account_id();
 
-        self.note_book.remove(&account_id);
+        // The set is dropped without being cleared, its elements stay in
+        // storage.
+        if let Some(removed) = self.note_book.remove(&account_id) {
+            for note in removed.iter() {
+                self.notes_by_id.remove(&note.id.unwrap().0);
+            }
+        }
     }
 
     pub fn remove_all_notes_correct(&mut self) {
@@ -240,11 +254,16 @@ impl Contract {
             .remove(&account_id)
             .unwrap_or_else(|| env::panic_str("No user found"));
 
+        for note in removed.iter() {
+            self.notes_by_id.remove(&note.id.unwrap().0);
+        }
+
         removed.clear();
     }
 
     pub fn add_note_collection(&mut self, title: String, body: String) {
         let account_id = env::predecessor_account_id();
+        self.assert_not_frozen(&account_id);
 
         let next_entry_id = self.next_entry_id.unwrap_or(0);
 
@@ -313,6 +332,9 @@ impl Contract {
             notes.insert(note.clone());
         }
 
+        self.notes_by_id
+            .insert(next_entry_id, (account_id.clone(), note.clone()));
+
         self.next_entry_id = Some(next_entry_id + 1);
 
         let storage_after = env::storage_usage(); // storage after addition
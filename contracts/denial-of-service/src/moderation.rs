@@ -0,0 +1,57 @@
+use contract_common::impl_moderation;
+use near_sdk::{env, json_types::U64, log, near, require, AccountId};
+
+use crate::{Contract, ContractExt};
+
+impl_moderation!(Contract, moderation);
+
+#[near]
+impl Contract {
+    /// Removes a note of `account_id`, e.g. spam. Only managers can call it.
+    pub fn moderator_delete_note(&mut self, account_id: AccountId, id: U64) {
+        self.moderation.assert_manager();
+
+        self.internal_moderator_delete_note(&account_id, id);
+    }
+
+    /// Vulnerable: the manager check is missing, anyone can delete anyone's
+    /// notes.
+    pub fn moderator_delete_note_unchecked(
+        &mut self,
+        account_id: AccountId,
+        id: U64,
+    ) {
+        self.internal_moderator_delete_note(&account_id, id);
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_not_frozen(&self, account_id: &AccountId) {
+        self.moderation.assert_not_frozen(account_id);
+    }
+
+    fn internal_moderator_delete_note(
+        &mut self,
+        account_id: &AccountId,
+        id: U64,
+    ) {
+        let (owner_id, note) = self
+            .notes_by_id
+            .remove(&id.0)
+            .unwrap_or_else(|| env::panic_str("Note does not exist"));
+
+        require!(&owner_id == account_id, "Note does not belong to the user");
+
+        self.note_book
+            .get_mut(account_id)
+            .unwrap_or_else(|| env::panic_str("no entry"))
+            .remove(&note);
+
+        log!(
+            "Moderator {} deleted note {} of {}",
+            env::predecessor_account_id(),
+            id.0,
+            account_id
+        );
+    }
+}
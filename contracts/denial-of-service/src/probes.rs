@@ -0,0 +1,56 @@
+use near_sdk::{env, near, Gas, NearToken};
+
+use crate::{Contract, ContractExt};
+
+/// Gas attached to the `probe_sink` call of `probe_arguments`.
+const SINK_GAS: Gas = Gas::from_tgas(10);
+
+/// Each method pushes one runtime limit to exactly the size it is given, so
+/// that the integration tests can call it at the limit and one past it.
+#[near]
+impl Contract {
+    /// Emits `count` one byte logs.
+    pub fn probe_logs(&self, count: u32) {
+        for _ in 0..count {
+            env::log_str("a");
+        }
+    }
+
+    /// Emits a single log of `length` bytes.
+    pub fn probe_log_bytes(&self, length: u32) {
+        env::log_str(&"a".repeat(length as usize));
+    }
+
+    /// Returns `length` bytes of raw data.
+    pub fn probe_return_data(&self, length: u32) {
+        env::value_return(&vec![b'a'; length as usize]);
+    }
+
+    /// Writes an empty value under a key of `length` bytes.
+    pub fn probe_storage_key(&mut self, length: u32) {
+        env::storage_write(&vec![b'a'; length as usize], &[]);
+    }
+
+    /// Calls `probe_sink` on itself with `length` bytes of arguments.
+    /// Transactions are capped by their total size, so arguments near the
+    /// limit can only be produced by a contract.
+    pub fn probe_arguments(&self, length: u32) {
+        env::promise_create(
+            env::current_account_id(),
+            "probe_sink",
+            &vec![b'a'; length as usize],
+            NearToken::from_yoctonear(0),
+            SINK_GAS,
+        );
+    }
+
+    /// Accepts any input without reading it.
+    pub fn probe_sink(&self) {}
+
+    /// Creates `count` empty promises to itself.
+    pub fn probe_promises(&self, count: u32) {
+        for _ in 0..count {
+            env::promise_batch_create(&env::current_account_id());
+        }
+    }
+}
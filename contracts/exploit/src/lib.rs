@@ -44,6 +44,13 @@ impl Exploit {
             .then(Self::ext(env::current_account_id()).exploit_callback())
     }
 
+    /// Griefing payout beneficiary: rejects every payout made through a
+    /// function call, the deposit bounces back to the payer.
+    #[payable]
+    pub fn on_payout(&mut self) {
+        env::panic_str("Exploit rejects payouts");
+    }
+
     #[private]
     pub fn panic(&mut self) {
         env::panic_str("Exploit panic");
@@ -0,0 +1,387 @@
+use near_sdk::{
+    env, ext_contract, is_promise_success,
+    json_types::U128,
+    log, near, require,
+    store::{LookupMap, Vector},
+    AccountId, BorshStorageKey, Gas, NearToken, PanicOnDefault, Promise,
+};
+
+const ON_PAYOUT_GAS: Gas = Gas::from_tgas(10);
+const RESOLVE_GAS: Gas = Gas::from_tgas(10);
+
+/// Payouts released by `release` when no limit is given.
+pub const DEFAULT_PAYOUTS_PER_RELEASE: u32 = 100;
+
+/// Interface of beneficiaries that want to be notified when they are paid.
+#[ext_contract(payout_receiver)]
+pub trait PayoutReceiver {
+    fn on_payout(&mut self);
+}
+
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payout {
+    pub beneficiary: AccountId,
+    pub amount: U128,
+    /// Paid through `on_payout` instead of a plain transfer.
+    pub notify: bool,
+}
+
+#[near]
+#[derive(BorshStorageKey)]
+pub enum StorageKey {
+    Queue,
+    Withdrawable,
+}
+
+/// Pays out a queue of beneficiaries in order, e.g. refunds of an auction.
+/// The owner funds every payout when it is queued.
+#[near(contract_state)]
+#[derive(PanicOnDefault)]
+pub struct Contract {
+    owner: AccountId,
+    queue: Vector<Payout>,
+    /// Index of the next payout in `queue`.
+    next: u32,
+    /// A push payout is waiting for its callback.
+    pending: bool,
+    paid_out: u128,
+    withdrawable: LookupMap<AccountId, u128>,
+}
+
+#[near]
+impl Contract {
+    #[init]
+    pub fn new(owner: AccountId) -> Self {
+        Self {
+            owner,
+            queue: Vector::new(StorageKey::Queue),
+            next: 0,
+            pending: false,
+            paid_out: 0,
+            withdrawable: LookupMap::new(StorageKey::Withdrawable),
+        }
+    }
+
+    /// Queues the attached deposit for `beneficiary`.
+    #[payable]
+    pub fn enqueue(&mut self, beneficiary: AccountId, notify: Option<bool>) {
+        require!(
+            env::predecessor_account_id() == self.owner,
+            "Only the owner can queue payouts"
+        );
+
+        let amount = env::attached_deposit().as_yoctonear();
+
+        require!(amount > 0, "Attach the amount to pay out");
+
+        self.queue.push(Payout {
+            beneficiary: beneficiary.clone(),
+            amount: U128(amount),
+            notify: notify.unwrap_or(false),
+        });
+
+        log!("Queued {} for {}", amount, beneficiary);
+    }
+
+    /// Vulnerable: pushes the next payout and only moves on once it went
+    /// through. A beneficiary that can't be paid, a deleted account or a
+    /// contract whose `on_payout` panics, stays at the head of the queue and
+    /// every payout behind it is blocked for good.
+    pub fn pay_next(&mut self) -> Promise {
+        require!(!self.pending, "A payout is in flight");
+        require!(self.next < self.queue.len(), "The queue is empty");
+
+        self.pending = true;
+
+        let payout = self.queue[self.next].clone();
+
+        Self::internal_push(&payout).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(RESOLVE_GAS)
+                .resolve_payout(self.next),
+        )
+    }
+
+    #[private]
+    pub fn resolve_payout(&mut self, index: u32) -> bool {
+        self.pending = false;
+
+        let payout = &self.queue[index];
+
+        if !is_promise_success() {
+            log!("Payout to {} failed, retrying later", payout.beneficiary);
+
+            return false;
+        }
+
+        self.paid_out += payout.amount.0;
+        self.next = index + 1;
+
+        log!("Paid {} to {}", payout.amount.0, payout.beneficiary);
+
+        true
+    }
+
+    /// Vulnerable: moves on without waiting for the result. The queue never
+    /// blocks, but a failed payout is refunded to the contract while
+    /// `paid_out` counts it as paid and nobody can get it back.
+    pub fn pay_next_unchecked(&mut self) -> Promise {
+        require!(!self.pending, "A payout is in flight");
+        require!(self.next < self.queue.len(), "The queue is empty");
+
+        let payout = self.queue[self.next].clone();
+
+        self.paid_out += payout.amount.0;
+        self.next += 1;
+
+        Self::internal_push(&payout)
+    }
+
+    /// Credits up to `limit` payouts to their beneficiaries' withdrawable
+    /// balances. Makes no external calls, so no beneficiary can stop it.
+    pub fn release(&mut self, limit: Option<u32>) -> u32 {
+        require!(!self.pending, "A payout is in flight");
+
+        let end = self
+            .next
+            .saturating_add(limit.unwrap_or(DEFAULT_PAYOUTS_PER_RELEASE))
+            .min(self.queue.len());
+
+        for index in self.next..end {
+            let payout = &self.queue[index];
+
+            let withdrawable = self
+                .withdrawable
+                .entry(payout.beneficiary.clone())
+                .or_insert(0);
+            *withdrawable += payout.amount.0;
+        }
+
+        let released = end - self.next;
+        self.next = end;
+
+        log!("Released {} payouts", released);
+
+        released
+    }
+
+    /// Pays out the caller's own withdrawable balance. A beneficiary that
+    /// can't be paid only blocks itself.
+    pub fn withdraw(&mut self) -> Promise {
+        let account_id = env::predecessor_account_id();
+
+        let amount = self
+            .withdrawable
+            .remove(&account_id)
+            .filter(|amount| *amount > 0)
+            .unwrap_or_else(|| env::panic_str("Nothing to withdraw"));
+
+        Promise::new(account_id.clone())
+            .transfer(NearToken::from_yoctonear(amount))
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(RESOLVE_GAS)
+                    .resolve_withdraw(account_id, U128(amount)),
+            )
+    }
+
+    #[private]
+    pub fn resolve_withdraw(
+        &mut self,
+        account_id: AccountId,
+        amount: U128,
+    ) -> bool {
+        if is_promise_success() {
+            self.paid_out += amount.0;
+
+            log!("Withdrew {} for {}", amount.0, account_id);
+
+            return true;
+        }
+
+        let withdrawable =
+            self.withdrawable.entry(account_id.clone()).or_insert(0);
+        *withdrawable += amount.0;
+
+        log!(
+            "Withdrawal failed, restored {} for {}",
+            amount.0,
+            account_id
+        );
+
+        false
+    }
+
+    pub fn get_queue(
+        &self,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<&Payout> {
+        self.queue
+            .iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(u32::MAX) as usize)
+            .collect()
+    }
+
+    pub fn get_next(&self) -> u32 {
+        self.next
+    }
+
+    pub fn get_paid_out(&self) -> U128 {
+        U128(self.paid_out)
+    }
+
+    pub fn get_withdrawable(&self, account_id: AccountId) -> U128 {
+        U128(self.withdrawable.get(&account_id).copied().unwrap_or(0))
+    }
+}
+
+impl Contract {
+    fn internal_push(payout: &Payout) -> Promise {
+        let amount = NearToken::from_yoctonear(payout.amount.0);
+
+        if payout.notify {
+            payout_receiver::ext(payout.beneficiary.clone())
+                .with_attached_deposit(amount)
+                .with_static_gas(ON_PAYOUT_GAS)
+                .on_payout()
+        } else {
+            Promise::new(payout.beneficiary.clone()).transfer(amount)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{
+        test_utils::VMContextBuilder, test_vm_config, testing_env,
+        PromiseResult, RuntimeFeesConfig,
+    };
+
+    use super::*;
+
+    const OWNER: &str = "owner.near";
+    const ALICE: &str = "alice.near";
+    const BOB: &str = "bob.near";
+
+    #[test]
+    fn failed_payout_blocks_queue() {
+        let mut contract = contract_with_queue();
+
+        contract.pay_next();
+        set_promise_result(PromiseResult::Failed);
+
+        assert!(!contract.resolve_payout(0));
+        assert_eq!(contract.get_next(), 0);
+
+        // Retrying pays the same head again.
+        contract.pay_next();
+        set_promise_result(PromiseResult::Failed);
+        contract.resolve_payout(0);
+
+        assert_eq!(contract.get_next(), 0);
+        assert_eq!(contract.get_paid_out(), U128(0));
+    }
+
+    #[test]
+    fn successful_payout_moves_on() {
+        let mut contract = contract_with_queue();
+
+        contract.pay_next();
+        set_promise_result(PromiseResult::Successful(vec![]));
+
+        assert!(contract.resolve_payout(0));
+        assert_eq!(contract.get_next(), 1);
+        assert_eq!(contract.get_paid_out(), U128(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "A payout is in flight")]
+    fn pay_next_twice() {
+        let mut contract = contract_with_queue();
+
+        contract.pay_next();
+        contract.pay_next();
+    }
+
+    #[test]
+    fn unchecked_payout_counts_failures_as_paid() {
+        let mut contract = contract_with_queue();
+
+        contract.pay_next_unchecked();
+        contract.pay_next_unchecked();
+
+        assert_eq!(contract.get_next(), 2);
+        assert_eq!(contract.get_paid_out(), U128(30));
+    }
+
+    #[test]
+    fn release_credits_beneficiaries() {
+        let mut contract = contract_with_queue();
+
+        assert_eq!(contract.release(Some(1)), 1);
+        assert_eq!(contract.release(None), 1);
+        assert_eq!(contract.release(None), 0);
+
+        assert_eq!(contract.get_withdrawable(ALICE.parse().unwrap()), U128(10));
+        assert_eq!(contract.get_withdrawable(BOB.parse().unwrap()), U128(20));
+    }
+
+    #[test]
+    fn failed_withdrawal_is_restored() {
+        let mut contract = contract_with_queue();
+
+        contract.release(None);
+
+        set_context(ALICE, NearToken::from_yoctonear(0));
+        contract.withdraw();
+
+        assert_eq!(contract.get_withdrawable(ALICE.parse().unwrap()), U128(0));
+
+        set_promise_result(PromiseResult::Failed);
+
+        assert!(!contract.resolve_withdraw(ALICE.parse().unwrap(), U128(10)));
+        assert_eq!(contract.get_withdrawable(ALICE.parse().unwrap()), U128(10));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can queue payouts")]
+    fn enqueue_not_owner() {
+        let mut contract = Contract::new(OWNER.parse().unwrap());
+
+        set_context(ALICE, NearToken::from_yoctonear(10));
+        contract.enqueue(ALICE.parse().unwrap(), None);
+    }
+
+    fn contract_with_queue() -> Contract {
+        let mut contract = Contract::new(OWNER.parse().unwrap());
+
+        set_context(OWNER, NearToken::from_yoctonear(10));
+        contract.enqueue(ALICE.parse().unwrap(), None);
+
+        set_context(OWNER, NearToken::from_yoctonear(20));
+        contract.enqueue(BOB.parse().unwrap(), Some(true));
+
+        contract
+    }
+
+    fn set_context(predecessor: &str, amount: NearToken) {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor.parse().unwrap());
+        builder.attached_deposit(amount);
+
+        testing_env!(builder.build());
+    }
+
+    fn set_promise_result(result: PromiseResult) {
+        testing_env!(
+            VMContextBuilder::new().build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+    }
+}
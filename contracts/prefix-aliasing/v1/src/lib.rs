@@ -0,0 +1,109 @@
+use near_sdk::{
+    env, json_types::U128, log, near, require, store::LookupMap, AccountId,
+    BorshStorageKey, NearToken, PanicOnDefault, Promise,
+};
+
+#[near]
+#[derive(BorshStorageKey)]
+pub enum StorageKey {
+    Balances,
+    Stakes,
+}
+
+/// First version of the staking vault. Deposits sit in `balances` until
+/// they are staked.
+#[near(contract_state)]
+#[derive(PanicOnDefault)]
+pub struct Contract {
+    balances: LookupMap<AccountId, u128>,
+    stakes: LookupMap<AccountId, u128>,
+}
+
+#[near]
+impl Contract {
+    #[init]
+    pub fn new() -> Self {
+        Self {
+            balances: LookupMap::new(StorageKey::Balances),
+            stakes: LookupMap::new(StorageKey::Stakes),
+        }
+    }
+
+    #[payable]
+    pub fn deposit(&mut self) {
+        let account_id = env::predecessor_account_id();
+        let amount = env::attached_deposit().as_yoctonear();
+
+        let balance = self.balances.entry(account_id).or_insert(0);
+        *balance += amount;
+    }
+
+    pub fn stake(&mut self, amount: U128) {
+        let account_id = env::predecessor_account_id();
+
+        let balance = self.balances.get(&account_id).copied().unwrap_or(0);
+
+        require!(balance >= amount.0, "Not enough balance");
+
+        self.balances.insert(account_id.clone(), balance - amount.0);
+
+        let stake = self.stakes.entry(account_id.clone()).or_insert(0);
+        *stake += amount.0;
+
+        log!("{} staked {}", account_id, amount.0);
+    }
+
+    pub fn unstake(&mut self, amount: U128) -> Promise {
+        let account_id = env::predecessor_account_id();
+
+        let stake = self.stakes.get(&account_id).copied().unwrap_or(0);
+
+        require!(stake >= amount.0, "Not enough stake");
+
+        self.stakes.insert(account_id.clone(), stake - amount.0);
+
+        Promise::new(account_id).transfer(NearToken::from_yoctonear(amount.0))
+    }
+
+    pub fn view_balance(&self, account_id: AccountId) -> U128 {
+        U128(self.balances.get(&account_id).copied().unwrap_or(0))
+    }
+
+    pub fn view_stake(&self, account_id: AccountId) -> U128 {
+        U128(self.stakes.get(&account_id).copied().unwrap_or(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    use super::*;
+
+    #[test]
+    fn deposit_and_stake() {
+        let mut contract = Contract::new();
+        let account_id: AccountId = "alice.near".parse().unwrap();
+
+        set_context(account_id.as_str(), NearToken::from_near(10));
+        contract.deposit();
+        contract.stake(U128(NearToken::from_near(4).as_yoctonear()));
+
+        assert_eq!(
+            contract.view_balance(account_id.clone()),
+            U128(NearToken::from_near(6).as_yoctonear())
+        );
+        assert_eq!(
+            contract.view_stake(account_id),
+            U128(NearToken::from_near(4).as_yoctonear())
+        );
+    }
+
+    fn set_context(predecessor: &str, amount: NearToken) {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor.parse().unwrap());
+        builder.attached_deposit(amount);
+
+        testing_env!(builder.build());
+    }
+}
@@ -0,0 +1,217 @@
+mod prefixes;
+
+use near_sdk::{
+    env, json_types::U128, log, near, require, store::LookupMap, AccountId,
+    BorshStorageKey, NearToken, PanicOnDefault, Promise,
+};
+use prefixes::{assert_disjoint_prefixes, prefix_of};
+
+/// Vulnerable: `Rewards` was added in the middle of the enum, so it gets
+/// discriminant 1, which is the `Stakes` prefix v1 persisted. `Stakes` moved
+/// to 2 but the deserialized `stakes` map keeps its stored prefix `[1]`.
+#[near]
+#[derive(BorshStorageKey)]
+pub enum StorageKey {
+    Balances,
+    Rewards,
+    Stakes,
+}
+
+/// Second version of the staking vault, adds rewards that stakers can claim.
+#[near(contract_state)]
+#[derive(PanicOnDefault)]
+pub struct Contract {
+    balances: LookupMap<AccountId, u128>,
+    stakes: LookupMap<AccountId, u128>,
+    rewards: LookupMap<AccountId, u128>,
+}
+
+/// State layout of v1.
+#[near]
+pub struct OldContract {
+    balances: LookupMap<AccountId, u128>,
+    stakes: LookupMap<AccountId, u128>,
+}
+
+#[near]
+impl Contract {
+    #[init]
+    pub fn new() -> Self {
+        Self {
+            balances: LookupMap::new(StorageKey::Balances),
+            stakes: LookupMap::new(StorageKey::Stakes),
+            rewards: LookupMap::new(StorageKey::Rewards),
+        }
+    }
+
+    /// Vulnerable: the new `rewards` map shares the prefix of the existing
+    /// `stakes`, so every stake shows up as a claimable reward.
+    #[init(ignore_state)]
+    #[private]
+    pub fn migrate() -> Self {
+        let old: OldContract = env::state_read()
+            .unwrap_or_else(|| env::panic_str("Failed to read old state"));
+
+        Self {
+            balances: old.balances,
+            stakes: old.stakes,
+            rewards: LookupMap::new(StorageKey::Rewards),
+        }
+    }
+
+    /// Same migration, but refuses to write a state whose collections
+    /// overlap, which reverts the upgrade it is batched with.
+    #[init(ignore_state)]
+    #[private]
+    pub fn migrate_checked() -> Self {
+        let contract = Self::migrate();
+
+        contract.assert_disjoint_prefixes();
+
+        contract
+    }
+
+    #[payable]
+    pub fn deposit(&mut self) {
+        let account_id = env::predecessor_account_id();
+        let amount = env::attached_deposit().as_yoctonear();
+
+        let balance = self.balances.entry(account_id).or_insert(0);
+        *balance += amount;
+    }
+
+    pub fn stake(&mut self, amount: U128) {
+        let account_id = env::predecessor_account_id();
+
+        let balance = self.balances.get(&account_id).copied().unwrap_or(0);
+
+        require!(balance >= amount.0, "Not enough balance");
+
+        self.balances.insert(account_id.clone(), balance - amount.0);
+
+        let stake = self.stakes.entry(account_id.clone()).or_insert(0);
+        *stake += amount.0;
+
+        log!("{} staked {}", account_id, amount.0);
+    }
+
+    pub fn unstake(&mut self, amount: U128) -> Promise {
+        let account_id = env::predecessor_account_id();
+
+        let stake = self.stakes.get(&account_id).copied().unwrap_or(0);
+
+        require!(stake >= amount.0, "Not enough stake");
+
+        self.stakes.insert(account_id.clone(), stake - amount.0);
+
+        Promise::new(account_id).transfer(NearToken::from_yoctonear(amount.0))
+    }
+
+    pub fn claim_rewards(&mut self) -> Promise {
+        let account_id = env::predecessor_account_id();
+
+        let reward = self
+            .rewards
+            .remove(&account_id)
+            .filter(|reward| *reward > 0)
+            .unwrap_or_else(|| env::panic_str("No rewards to claim"));
+
+        log!("{} claimed {}", account_id, reward);
+
+        Promise::new(account_id).transfer(NearToken::from_yoctonear(reward))
+    }
+
+    pub fn view_balance(&self, account_id: AccountId) -> U128 {
+        U128(self.balances.get(&account_id).copied().unwrap_or(0))
+    }
+
+    pub fn view_stake(&self, account_id: AccountId) -> U128 {
+        U128(self.stakes.get(&account_id).copied().unwrap_or(0))
+    }
+
+    pub fn view_rewards(&self, account_id: AccountId) -> U128 {
+        U128(self.rewards.get(&account_id).copied().unwrap_or(0))
+    }
+}
+
+impl Contract {
+    fn assert_disjoint_prefixes(&self) {
+        assert_disjoint_prefixes(&[
+            ("balances", prefix_of(&self.balances)),
+            ("stakes", prefix_of(&self.stakes)),
+            ("rewards", prefix_of(&self.rewards)),
+        ]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    use super::*;
+
+    #[test]
+    fn migrated_rewards_alias_stakes() {
+        set_context("alice.near", NearToken::from_near(10));
+        write_v1_state();
+
+        let mut contract = Contract::migrate();
+        let account_id: AccountId = "alice.near".parse().unwrap();
+
+        assert_eq!(
+            contract.view_rewards(account_id.clone()),
+            contract.view_stake(account_id.clone())
+        );
+
+        contract.claim_rewards();
+
+        // Each map caches its own entries, drop the handles so the removal
+        // is flushed and read the stake back from storage.
+        drop(contract);
+        let contract = Contract::migrate();
+
+        // Claiming the "reward" wiped the stake it aliases.
+        assert_eq!(contract.view_stake(account_id), U128(0));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Overlapping storage prefixes: `stakes` and `rewards`"
+    )]
+    fn migrate_checked_rejects_aliasing() {
+        set_context("alice.near", NearToken::from_near(10));
+        write_v1_state();
+
+        Contract::migrate_checked();
+    }
+
+    #[test]
+    fn fresh_state_is_disjoint() {
+        Contract::new().assert_disjoint_prefixes();
+    }
+
+    /// Writes the state v1 would have after alice staked 4 NEAR.
+    fn write_v1_state() {
+        let mut balances = LookupMap::new(vec![0]);
+        let mut stakes = LookupMap::new(vec![1]);
+
+        balances.insert(
+            "alice.near".parse::<AccountId>().unwrap(),
+            NearToken::from_near(6).as_yoctonear(),
+        );
+        stakes.insert(
+            "alice.near".parse::<AccountId>().unwrap(),
+            NearToken::from_near(4).as_yoctonear(),
+        );
+
+        env::state_write(&OldContract { balances, stakes });
+    }
+
+    fn set_context(predecessor: &str, amount: NearToken) {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor.parse().unwrap());
+        builder.attached_deposit(amount);
+
+        testing_env!(builder.build());
+    }
+}
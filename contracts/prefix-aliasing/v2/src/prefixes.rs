@@ -0,0 +1,129 @@
+//! Startup check for collection prefixes that share a keyspace.
+//!
+//! A collection stores its entries under `prefix ++ key`. If one prefix is a
+//! prefix of another, e.g. a raw `b"\x01"` next to the Borsh encoded
+//! `StorageKey::Stakes` (`[1]`), or two variants ending up with the same
+//! discriminant after an enum was reordered, both collections read and write
+//! the same keys without any error.
+
+use near_sdk::{
+    borsh::{self, BorshSerialize},
+    env,
+};
+
+/// Key the contract struct itself is stored under.
+const STATE_KEY: &[u8] = b"STATE";
+
+/// Prefix of a `store` `LookupMap`/`LookupSet`, which serialize as nothing
+/// but their prefix. This is the prefix actually in use, read back from the
+/// deserialized state, not the one the current code would construct.
+pub fn prefix_of<T: BorshSerialize>(collection: &T) -> Vec<u8> {
+    let serialized = borsh::to_vec(collection).unwrap();
+
+    borsh::from_slice(&serialized)
+        .unwrap_or_else(|_| env::panic_str("Not a lookup collection"))
+}
+
+/// Pairs of names whose prefixes overlap, including with `STATE`.
+pub fn overlapping_prefixes<'a>(
+    prefixes: &[(&'a str, Vec<u8>)],
+) -> Vec<(&'a str, &'a str)> {
+    let state = ("STATE", STATE_KEY.to_vec());
+    let all: Vec<_> = prefixes.iter().chain([&state]).collect();
+
+    let mut overlaps = Vec::new();
+
+    for (i, (name, prefix)) in all.iter().enumerate() {
+        for (other_name, other_prefix) in &all[i + 1..] {
+            if prefix.starts_with(other_prefix)
+                || other_prefix.starts_with(prefix)
+            {
+                overlaps.push((*name, *other_name));
+            }
+        }
+    }
+
+    overlaps
+}
+
+pub fn assert_disjoint_prefixes(prefixes: &[(&str, Vec<u8>)]) {
+    let overlaps = overlapping_prefixes(prefixes);
+
+    if !overlaps.is_empty() {
+        let overlaps = overlaps
+            .iter()
+            .map(|(name, other)| format!("`{name}` and `{other}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        env::panic_str(&format!("Overlapping storage prefixes: {overlaps}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{store::LookupMap, AccountId};
+
+    use super::*;
+    use crate::StorageKey;
+
+    #[test]
+    fn reordered_variant_overlaps_persisted_prefix() {
+        // `Stakes` was variant 1 in v1 and its prefix is stored in `STATE`,
+        // `Rewards` took variant 1 in v2.
+        let stakes: LookupMap<AccountId, u128> = LookupMap::new(vec![1]);
+
+        assert_eq!(
+            overlapping_prefixes(&[
+                ("stakes", prefix_of(&stakes)),
+                ("rewards", borsh::to_vec(&StorageKey::Rewards).unwrap()),
+            ]),
+            vec![("stakes", "rewards")]
+        );
+    }
+
+    #[test]
+    fn raw_prefix_overlaps_variant_with_data() {
+        #[derive(BorshSerialize)]
+        #[borsh(crate = "near_sdk::borsh")]
+        enum Nested {
+            _Balances,
+            Notes(String),
+        }
+
+        let notes = borsh::to_vec(&Nested::Notes("alice.near".into())).unwrap();
+
+        assert_eq!(
+            overlapping_prefixes(&[("notes", notes), ("raw", vec![1])]),
+            vec![("notes", "raw")]
+        );
+    }
+
+    #[test]
+    fn raw_prefix_overlaps_state() {
+        assert_eq!(
+            overlapping_prefixes(&[("settings", b"ST".to_vec())]),
+            vec![("settings", "STATE")]
+        );
+    }
+
+    #[test]
+    fn disjoint_prefixes() {
+        // The raw prefixes of the staking contract, and `b"mm"` of the
+        // note contracts next to their enum prefixes.
+        assert!(overlapping_prefixes(&[
+            ("stake_map", b"u".to_vec()),
+            ("allowlist", b"a".to_vec()),
+            ("note_book_collections", b"mm".to_vec()),
+            ("note_book", vec![0]),
+            ("notes", [vec![2], 5u32.to_le_bytes().to_vec()].concat()),
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Overlapping storage prefixes: `a` and `b`")]
+    fn assert_disjoint_prefixes_panics() {
+        assert_disjoint_prefixes(&[("a", vec![1]), ("b", vec![1, 2])]);
+    }
+}
@@ -2,20 +2,28 @@ mod storage;
 
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
-    collections::UnorderedMap,
+    collections::{LookupMap, UnorderedMap},
     env, ext_contract,
     json_types::U128,
     log, near_bindgen, require, AccountId, Balance, Gas, PanicOnDefault,
     Promise, PromiseError, ONE_NEAR,
 };
+use storage::TokenStorageKey;
 
 pub const TGAS: u64 = 1_000_000_000_000;
 
+/// Gas reserved for the staking call made by [`Contract::stake_safe`].
+pub const STAKE_SAFE_GAS: u64 = 10 * TGAS;
+
+/// Gas reserved for [`Contract::resolve_staking_safe`].
+pub const RESOLVE_STAKE_SAFE_GAS: u64 = 10 * TGAS;
+
 #[near_bindgen]
 #[derive(PanicOnDefault, BorshDeserialize, BorshSerialize)]
 pub struct Contract {
     user_near: UnorderedMap<AccountId, U128>,
     staking_contract: AccountId,
+    allowances: LookupMap<AccountId, UnorderedMap<AccountId, U128>>,
 }
 #[ext_contract(staking)]
 trait Staking {
@@ -31,6 +39,7 @@ impl Contract {
         Self {
             user_near,
             staking_contract,
+            allowances: LookupMap::new(TokenStorageKey::Accounts),
         }
     }
 
@@ -83,6 +92,163 @@ impl Contract {
             );
     }
 
+    /// Same flow as [`Contract::stake`], but the callback expects the staking
+    /// contract to return the new total stake. `Staking::stake` returns
+    /// nothing, so the callback panics while deserializing its input *after*
+    /// the stake has been committed on the staking side. The panic only rolls
+    /// back the callback receipt, so the deposit is never decreased.
+    pub fn stake_and_track(&self, validator: AccountId, amount: U128) {
+        let beneficiary = env::predecessor_account_id();
+
+        let near_deposit = self
+            .user_near
+            .get(&beneficiary)
+            .unwrap_or_else(|| env::panic_str("User does not exist"));
+
+        require!(amount <= near_deposit, "Not enough money");
+
+        staking::ext(self.staking_contract.clone())
+            .with_static_gas(Gas(3 * TGAS))
+            .stake(beneficiary.clone(), validator, amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(Gas(3 * TGAS))
+                    .resolve_staking_tracked(amount, beneficiary),
+            );
+    }
+
+    /// Fixed staking flow. The deposit is debited before the cross-contract
+    /// call and only credited back if staking fails, so a callback that never
+    /// runs (or fails) cannot leave the staked amount spendable here. The
+    /// callback itself never panics.
+    pub fn stake_safe(
+        &mut self,
+        validator: AccountId,
+        amount: U128,
+    ) -> Promise {
+        Self::assert_enough_gas_for_staking();
+
+        let beneficiary = env::predecessor_account_id();
+
+        self.decrease_balance(beneficiary.clone(), amount);
+
+        staking::ext(self.staking_contract.clone())
+            .with_static_gas(Gas(STAKE_SAFE_GAS))
+            .stake(beneficiary.clone(), validator, amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(Gas(RESOLVE_STAKE_SAFE_GAS))
+                    .resolve_staking_safe(amount, beneficiary),
+            )
+    }
+
+    /// Sets the amount `spender_id` may stake out of the caller's deposit,
+    /// overwriting the previous allowance. Changing a non-zero allowance this
+    /// way can be front-run: the spender uses the old allowance before the
+    /// change lands and the new one after it.
+    pub fn approve(&mut self, spender_id: AccountId, amount: U128) {
+        let owner_id = env::predecessor_account_id();
+
+        self.internal_set_allowance(&owner_id, &spender_id, amount);
+
+        log!(format!(
+            "Approved {} for {} by {}",
+            amount.0, spender_id, owner_id
+        ));
+    }
+
+    pub fn increase_allowance(&mut self, spender_id: AccountId, amount: U128) {
+        let owner_id = env::predecessor_account_id();
+        let allowance =
+            self.view_allowance(owner_id.clone(), spender_id.clone());
+
+        let new_allowance = U128::from(
+            allowance
+                .0
+                .checked_add(amount.0)
+                .unwrap_or_else(|| env::panic_str("Allowance overflow")),
+        );
+
+        self.internal_set_allowance(&owner_id, &spender_id, new_allowance);
+
+        log!(format!(
+            "Allowance of {} for {} increased to {}",
+            spender_id, owner_id, new_allowance.0
+        ));
+    }
+
+    /// Reduces the allowance relative to its current value. If the spender has
+    /// already used more than the remaining amount, the call fails instead of
+    /// handing out a fresh allowance.
+    pub fn decrease_allowance(&mut self, spender_id: AccountId, amount: U128) {
+        let owner_id = env::predecessor_account_id();
+        let allowance =
+            self.view_allowance(owner_id.clone(), spender_id.clone());
+
+        let new_allowance = U128::from(
+            allowance
+                .0
+                .checked_sub(amount.0)
+                .unwrap_or_else(|| env::panic_str("Allowance below zero")),
+        );
+
+        self.internal_set_allowance(&owner_id, &spender_id, new_allowance);
+
+        log!(format!(
+            "Allowance of {} for {} decreased to {}",
+            spender_id, owner_id, new_allowance.0
+        ));
+    }
+
+    pub fn view_allowance(
+        &self,
+        owner_id: AccountId,
+        spender_id: AccountId,
+    ) -> U128 {
+        self.allowances
+            .get(&owner_id)
+            .and_then(|allowances| allowances.get(&spender_id))
+            .unwrap_or(U128(0))
+    }
+
+    /// Stakes `amount` out of `owner_id`'s deposit with the caller as the
+    /// beneficiary, spending the caller's allowance.
+    pub fn stake_from(
+        &mut self,
+        owner_id: AccountId,
+        validator: AccountId,
+        amount: U128,
+    ) -> Promise {
+        Self::assert_enough_gas_for_staking();
+
+        let spender_id = env::predecessor_account_id();
+        let allowance =
+            self.view_allowance(owner_id.clone(), spender_id.clone());
+
+        require!(amount <= allowance, "Not enough allowance");
+
+        self.internal_set_allowance(
+            &owner_id,
+            &spender_id,
+            U128::from(allowance.0 - amount.0),
+        );
+        self.decrease_balance(owner_id.clone(), amount);
+
+        log!(format!(
+            "Staked by {:?} from {:?}, For {:?}, Amount {:?}",
+            spender_id, owner_id, validator, amount
+        ));
+
+        staking::ext(self.staking_contract.clone())
+            .with_static_gas(Gas(STAKE_SAFE_GAS))
+            .stake(spender_id.clone(), validator, amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(Gas(RESOLVE_STAKE_SAFE_GAS))
+                    .resolve_stake_from(owner_id, spender_id, amount),
+            )
+    }
+
     pub fn get_current_balance(&self) -> Balance {
         env::account_balance()
     }
@@ -123,6 +289,82 @@ impl Contract {
         }
     }
 
+    /// Callback of [`Contract::stake_and_track`]. The `U128` is deliberately
+    /// wrong: `Staking::stake` returns nothing, and `#[callback_result]` only
+    /// maps a failed promise to `Err`, a successful one with an empty result
+    /// fails to deserialize and panics before the body runs. Neither arm is
+    /// ever reached, which is the bug the PoC demonstrates.
+    #[private]
+    pub fn resolve_staking_tracked(
+        &mut self,
+        #[callback_result] call_result: Result<U128, PromiseError>,
+        amount: U128,
+        caller: AccountId,
+    ) {
+        match call_result {
+            Ok(total) => {
+                log!(format!("Total stake of {}: {}", caller, total.0));
+                self.decrease_balance(caller, amount)
+            }
+            Err(err) => {
+                env::panic_str(format!("ERROR STAKING: {:?}", err).as_str())
+            }
+        }
+    }
+
+    #[private]
+    pub fn resolve_staking_safe(
+        &mut self,
+        #[callback_result] call_result: Result<(), PromiseError>,
+        amount: U128,
+        caller: AccountId,
+    ) -> bool {
+        if call_result.is_ok() {
+            log!(format!("Staked {} for {}", amount.0, caller));
+
+            return true;
+        }
+
+        self.increase_balance(&caller, amount);
+
+        log!(format!(
+            "Staking failed, refunded {} to {}",
+            amount.0, caller
+        ));
+
+        false
+    }
+
+    #[private]
+    pub fn resolve_stake_from(
+        &mut self,
+        #[callback_result] call_result: Result<(), PromiseError>,
+        owner_id: AccountId,
+        spender_id: AccountId,
+        amount: U128,
+    ) -> bool {
+        if call_result.is_ok() {
+            return true;
+        }
+
+        self.increase_balance(&owner_id, amount);
+
+        let allowance =
+            self.view_allowance(owner_id.clone(), spender_id.clone());
+        self.internal_set_allowance(
+            &owner_id,
+            &spender_id,
+            U128::from(allowance.0 + amount.0),
+        );
+
+        log!(format!(
+            "Staking failed, refunded {} to {}",
+            amount.0, owner_id
+        ));
+
+        false
+    }
+
     pub fn view_near_deposit(&self, acc: AccountId) -> U128 {
         let near_deposit = self
             .user_near
@@ -150,4 +392,43 @@ impl Contract {
 
         log!(format!("Decreased {} of {}", new_deposit.0, account))
     }
+
+    fn assert_enough_gas_for_staking() {
+        require!(
+            env::prepaid_gas()
+                >= Gas(STAKE_SAFE_GAS + RESOLVE_STAKE_SAFE_GAS + 5 * TGAS),
+            "Not enough gas attached"
+        );
+    }
+
+    /// The nested map is a legacy collection, so it has to be written back to
+    /// the outer map after every mutation to persist its length.
+    fn internal_set_allowance(
+        &mut self,
+        owner_id: &AccountId,
+        spender_id: &AccountId,
+        amount: U128,
+    ) {
+        let mut allowances =
+            self.allowances.get(owner_id).unwrap_or_else(|| {
+                UnorderedMap::new(TokenStorageKey::Allowance {
+                    account_id: owner_id.clone(),
+                })
+            });
+
+        if amount.0 == 0 {
+            allowances.remove(spender_id);
+        } else {
+            allowances.insert(spender_id, &amount);
+        }
+
+        self.allowances.insert(owner_id, &allowances);
+    }
+
+    fn increase_balance(&mut self, account: &AccountId, amount: U128) {
+        let near_deposit = self.user_near.get(account).unwrap_or(U128(0));
+
+        self.user_near
+            .insert(account, &U128::from(near_deposit.0 + amount.0));
+    }
 }
@@ -0,0 +1,250 @@
+mod storage;
+
+use contract_common::StorageAccounts;
+use near_sdk::{
+    env,
+    json_types::U128,
+    log, near,
+    store::{IterableMap, IterableSet},
+    AccountId, BorshStorageKey, CryptoHash, PanicOnDefault,
+};
+
+#[near(serializers = [borsh, json])]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MoneyJar {
+    pub amount: U128,
+    pub id: U128,
+}
+
+impl MoneyJar {
+    pub fn new(amount: U128, id: U128) -> Self {
+        Self { amount, id }
+    }
+}
+
+/// Every nested collection prefix is a fixed-size field of an enum variant,
+/// so two different inputs can never serialize to the same bytes.
+#[near]
+#[derive(BorshStorageKey)]
+pub enum StorageKey {
+    JarsPerUser,
+    Jars { account_hash: CryptoHash },
+    Accounts,
+}
+
+impl StorageKey {
+    /// Prefix of the jar set owned by `account_id`. The jar id is not part of
+    /// the key, and the account is hashed to 32 bytes, so the prefix can't be
+    /// shifted into another account's keyspace by moving characters between
+    /// the id and the account.
+    pub fn jars(account_id: &AccountId) -> Self {
+        Self::Jars {
+            account_hash: env::sha256_array(account_id.as_bytes()),
+        }
+    }
+}
+
+#[near(contract_state)]
+#[derive(PanicOnDefault)]
+pub struct Contract {
+    jars_per_user: IterableMap<AccountId, IterableSet<MoneyJar>>,
+    /// NEP-145 storage balances, every jar is paid for by its owner.
+    storage: StorageAccounts,
+}
+
+#[near]
+impl Contract {
+    #[init]
+    pub fn new() -> Self {
+        Self {
+            jars_per_user: IterableMap::new(StorageKey::JarsPerUser),
+            storage: StorageAccounts::new(StorageKey::Accounts),
+        }
+    }
+
+    pub fn create_jar(&mut self, amount: U128, id: U128) {
+        let account_id = env::predecessor_account_id();
+        self.storage.assert_registered(&account_id);
+
+        let storage_usage = env::storage_usage();
+
+        self.internal_insert_jar(&account_id, MoneyJar::new(amount, id));
+
+        self.internal_update_storage(&account_id, storage_usage);
+
+        log!("Created jar for user: {}", account_id);
+    }
+
+    /// Counterpart of the vulnerable `create_jar_timestamp`, which stages the
+    /// jar in two `IterableSet`s prefixed with `Jars(block_timestamp_ms)`.
+    /// Those sets flush to storage when they go out of scope, so every call
+    /// leaves unreachable entries behind that all calls in the same block
+    /// share. Scratch data belongs in memory; the only collection written is
+    /// the caller's own set.
+    pub fn create_jar_timestamp(&mut self, amount: U128, id: U128) {
+        let account_id = env::predecessor_account_id();
+        self.storage.assert_registered(&account_id);
+
+        let storage_usage = env::storage_usage();
+
+        self.internal_insert_jar(&account_id, MoneyJar::new(amount, id));
+
+        self.internal_update_storage(&account_id, storage_usage);
+
+        log!(
+            "Created jar for user: {} at {}",
+            account_id,
+            env::block_timestamp_ms()
+        );
+    }
+
+    pub fn get_jars(&self, account_id: AccountId) -> Vec<&MoneyJar> {
+        self.jars_per_user
+            .get(&account_id)
+            .map(|jars| jars.iter().collect())
+            .unwrap_or_default()
+    }
+
+    fn internal_insert_jar(&mut self, account_id: &AccountId, jar: MoneyJar) {
+        if let Some(jars) = self.jars_per_user.get_mut(account_id) {
+            jars.insert(jar);
+        } else {
+            let mut jars = IterableSet::new(StorageKey::jars(account_id));
+            jars.insert(jar);
+
+            self.jars_per_user.insert(account_id.clone(), jars);
+        }
+    }
+
+    fn internal_flush(&mut self, account_id: &AccountId) {
+        if let Some(jars) = self.jars_per_user.get_mut(account_id) {
+            jars.flush();
+        }
+
+        self.jars_per_user.flush();
+        self.storage.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::{
+        borsh, test_utils::VMContextBuilder, testing_env, NearToken,
+    };
+
+    use super::*;
+
+    #[test]
+    fn create_jar_isolated_per_account() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_millinear(100));
+        contract.storage_deposit(None, None);
+        contract.create_jar(U128(2), U128(11));
+
+        set_context("1account_id", NearToken::from_millinear(100));
+        contract.storage_deposit(None, None);
+        contract.create_jar(U128(6), U128(1));
+
+        let jars = contract.get_jars("account_id".parse().unwrap());
+        let jars_2 = contract.get_jars("1account_id".parse().unwrap());
+
+        assert_eq!(jars, vec![&MoneyJar::new(U128(2), U128(11))]);
+        assert_eq!(jars_2, vec![&MoneyJar::new(U128(6), U128(1))]);
+    }
+
+    #[test]
+    fn create_jar_timestamp_same_block() {
+        let mut contract = Contract::new();
+        let account_id: AccountId = "account_id".parse().unwrap();
+
+        set_context(account_id.as_str(), NearToken::from_millinear(100));
+        contract.storage_deposit(None, None);
+
+        contract.create_jar_timestamp(U128(2), U128(11));
+        contract.create_jar_timestamp(U128(7), U128(1));
+
+        assert_eq!(
+            contract.get_jars(account_id),
+            vec![
+                &MoneyJar::new(U128(2), U128(11)),
+                &MoneyJar::new(U128(7), U128(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn jar_prefixes_are_fixed_size() {
+        let prefix = |account_id: &str| {
+            borsh::to_vec(&StorageKey::jars(&account_id.parse().unwrap()))
+                .unwrap()
+        };
+
+        assert_ne!(prefix("account_id"), prefix("1account_id"));
+        assert_eq!(prefix("a1"), prefix("a1"));
+        assert_eq!(prefix("a1").len(), 1 + 32);
+        assert_eq!(prefix("a-much-longer-account-id.near").len(), 1 + 32);
+    }
+
+    #[test]
+    fn create_jar_charges_storage_balance() {
+        let mut contract = Contract::new();
+        let account_id: AccountId = "account_id".parse().unwrap();
+
+        set_context(account_id.as_str(), NearToken::from_millinear(100));
+        let registered = contract.storage_deposit(None, None);
+
+        contract.create_jar(U128(2), U128(11));
+
+        let balance = contract.storage_balance_of(account_id).unwrap();
+
+        assert_eq!(balance.total, NearToken::from_millinear(100));
+        assert!(balance.available < registered.available);
+    }
+
+    #[test]
+    #[should_panic(expected = "The account is not registered")]
+    fn create_jar_requires_registration() {
+        let mut contract = Contract::new();
+
+        set_context("account_id", NearToken::from_yoctonear(0));
+        contract.create_jar(U128(2), U128(11));
+    }
+
+    #[test]
+    #[should_panic(expected = "Insufficient storage balance")]
+    fn create_jar_insufficient_storage_balance() {
+        let mut contract = Contract::new();
+
+        let min_balance = contract.storage_balance_bounds().min;
+
+        set_context("account_id", min_balance);
+        contract.storage_deposit(None, Some(true));
+        contract.create_jar(U128(2), U128(11));
+    }
+
+    #[test]
+    fn storage_unregister_force_removes_jars() {
+        let mut contract = Contract::new();
+        let account_id: AccountId = "account_id".parse().unwrap();
+
+        set_context(account_id.as_str(), NearToken::from_millinear(100));
+        contract.storage_deposit(None, None);
+        contract.create_jar(U128(2), U128(11));
+
+        set_context(account_id.as_str(), NearToken::from_yoctonear(1));
+        assert!(contract.storage_unregister(Some(true)));
+
+        assert!(contract.storage_balance_of(account_id.clone()).is_none());
+        assert!(contract.get_jars(account_id).is_empty());
+    }
+
+    fn set_context(predecessor: &str, amount: NearToken) {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor.parse().unwrap());
+        builder.attached_deposit(amount);
+
+        testing_env!(builder.build());
+    }
+}
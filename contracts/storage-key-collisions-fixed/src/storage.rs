@@ -0,0 +1,27 @@
+use contract_common::impl_storage_management;
+use near_sdk::{require, AccountId, NearToken};
+
+use crate::{Contract, ContractExt};
+
+impl_storage_management!(Contract, storage);
+
+impl Contract {
+    /// Removes the jars of `account_id` for `storage_unregister`. Jars are
+    /// not funded here, the account is owed nothing on top of its deposit.
+    fn internal_unregister(
+        &mut self,
+        account_id: &AccountId,
+        force: bool,
+    ) -> NearToken {
+        if let Some(mut jars) = self.jars_per_user.remove(account_id) {
+            require!(
+                force,
+                "Can't unregister the account with jars, use force"
+            );
+
+            jars.clear();
+        }
+
+        NearToken::from_yoctonear(0)
+    }
+}
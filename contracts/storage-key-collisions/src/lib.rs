@@ -1,14 +1,21 @@
+mod moderation;
+
 use std::u32;
 
+use contract_common::Moderation;
 use near_sdk::{
+    borsh,
     collections::{LookupMap as LookUpMapCollections, UnorderedSet},
     env,
     json_types::{U128, U64},
     log, near, require,
-    store::{IterableMap, IterableSet, LookupSet},
+    store::{IterableMap, IterableSet, LookupMap, LookupSet},
     AccountId, BorshStorageKey, NearToken, PanicOnDefault, Promise,
 };
 
+/// Maximum number of notes a user can keep in `note_book_collections`.
+const MAX_NOTES_PER_USER: u64 = 3;
+
 #[near(serializers = [borsh, json])]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct PostedNote {
@@ -45,6 +52,9 @@ pub enum StorageKey {
     Jars(String),
     Managers,
     UserPoints,
+    NotesById,
+    FrozenUsers,
+    UnpaidNotes,
 }
 
 // Define the contract structure
@@ -56,7 +66,11 @@ pub struct Contract {
         LookUpMapCollections<AccountId, UnorderedSet<PostedNote>>,
     jars_per_user: IterableMap<AccountId, IterableSet<MoneyJar>>,
     next_entry_id: Option<u64>,
-    managers: LookupSet<AccountId>,
+    moderation: Moderation,
+    notes_by_id: LookupMap<u64, (AccountId, PostedNote)>,
+    /// Ids of the notes moved in by `migrate_note_collections`, whose
+    /// storage the authors never paid for.
+    unpaid_notes: LookupSet<u64>,
 }
 
 // Implement the contract structure
@@ -64,24 +78,25 @@ pub struct Contract {
 impl Contract {
     #[init]
     pub fn new(managers: Vec<AccountId>) -> Self {
-        let mut managers_set = LookupSet::new(StorageKey::Managers);
-
-        managers.into_iter().for_each(|manager| {
-            managers_set.insert(manager);
-        });
-
         Self {
             note_book: IterableMap::new(StorageKey::NotesPerUser),
             note_book_collections: LookUpMapCollections::new(b"mm".to_vec()),
             jars_per_user: IterableMap::new(StorageKey::JarsPerUser),
-            managers: managers_set,
+            moderation: Moderation::new(
+                StorageKey::Managers,
+                StorageKey::FrozenUsers,
+                managers,
+            ),
             next_entry_id: None,
+            notes_by_id: LookupMap::new(StorageKey::NotesById),
+            unpaid_notes: LookupSet::new(StorageKey::UnpaidNotes),
         }
     }
 
     #[payable]
     pub fn add_note(&mut self, title: String, body: String) {
         let account_id = env::predecessor_account_id();
+        self.assert_not_frozen(&account_id);
 
         let next_entry_id = self.next_entry_id.unwrap_or(0);
 
@@ -119,6 +134,14 @@ impl Contract {
         log!("Created jar for user: {}", account_id);
     }
 
+    /// Both temporary sets use the `Jars(block_timestamp_ms)` prefix. Each
+    /// handle keeps its own cache and `len` and only writes on drop, so
+    /// `temp_storage_even` flushes first and `temp_storage` overwrites its
+    /// `elements[0]`. Nothing references the prefix afterwards: the next call
+    /// in the same block starts from `len` 0 again, overwrites `elements[0]`
+    /// and adds its index entries to the pile. The user's set, prefixed with
+    /// `id ++ account ++ timestamp`, can collide with another user's set
+    /// created in the same block, as in `create_jar`.
     pub fn create_jar_timestamp(&mut self, amount: U128, id: U128) {
         let account_id = env::predecessor_account_id();
 
@@ -172,7 +195,13 @@ impl Contract {
     pub fn remove_all_notes(&mut self) {
         let account_id = env::predecessor_account_id();
 
-        self.note_book.remove(&account_id);
+        // The set is dropped without being cleared, its elements stay in
+        // storage.
+        if let Some(removed) = self.note_book.remove(&account_id) {
+            for note in removed.iter() {
+                self.internal_forget_note(note.id.unwrap().0);
+            }
+        }
     }
 
     pub fn remove_all_notes_correct(&mut self) {
@@ -183,11 +212,80 @@ impl Contract {
             .remove(&account_id)
             .unwrap_or_else(|| env::panic_str("No user found"));
 
+        for note in removed.iter() {
+            self.internal_forget_note(note.id.unwrap().0);
+        }
+
         removed.clear();
     }
 
+    #[payable]
+    pub fn edit_note(&mut self, id: U64, title: String, body: String) {
+        let account_id = env::predecessor_account_id();
+        self.assert_not_frozen(&account_id);
+
+        let storage_usage = env::storage_usage();
+
+        let (owner_id, note) = self
+            .notes_by_id
+            .get(&id.0)
+            .cloned()
+            .unwrap_or_else(|| env::panic_str("Note does not exist"));
+
+        require!(owner_id == account_id, "Only the author can edit a note");
+
+        let edited = PostedNote::new(title, body, note.id);
+
+        let notes = self
+            .note_book
+            .get_mut(&account_id)
+            .unwrap_or_else(|| env::panic_str("no entry"));
+
+        notes.remove(&note);
+        notes.insert(edited.clone());
+
+        self.notes_by_id.insert(id.0, (account_id.clone(), edited));
+
+        let paid = !self.unpaid_notes.contains(&id.0);
+
+        self.internal_settle_storage(
+            &account_id,
+            storage_usage,
+            env::attached_deposit().as_yoctonear(),
+            paid,
+        );
+
+        log!("Edited note: {}", id.0);
+    }
+
+    pub fn delete_note(&mut self, id: U64) {
+        let account_id = env::predecessor_account_id();
+        self.assert_not_frozen(&account_id);
+
+        let storage_usage = env::storage_usage();
+
+        let (owner_id, note) = self
+            .notes_by_id
+            .remove(&id.0)
+            .unwrap_or_else(|| env::panic_str("Note does not exist"));
+
+        require!(owner_id == account_id, "Only the author can delete a note");
+
+        self.note_book
+            .get_mut(&account_id)
+            .unwrap_or_else(|| env::panic_str("no entry"))
+            .remove(&note);
+
+        let paid = !self.unpaid_notes.remove(&id.0);
+
+        self.internal_settle_storage(&account_id, storage_usage, 0, paid);
+
+        log!("Deleted note: {}", id.0);
+    }
+
     pub fn add_note_collection(&mut self, title: String, body: String) {
         let account_id = env::predecessor_account_id();
+        self.assert_not_frozen(&account_id);
 
         let next_entry_id = self.next_entry_id.unwrap_or(0);
 
@@ -201,6 +299,92 @@ impl Contract {
         log!("Added note to the note book: {}", note.title);
     }
 
+    pub fn add_note_collection_correct(&mut self, title: String, body: String) {
+        let account_id = env::predecessor_account_id();
+        self.assert_not_frozen(&account_id);
+
+        let next_entry_id = self.next_entry_id.unwrap_or(0);
+
+        let note =
+            PostedNote::new(title.clone(), body, Some(U64(next_entry_id)));
+
+        self.internal_add_note_collection_correct(&account_id, &note);
+
+        self.next_entry_id = Some(next_entry_id + 1);
+
+        log!("Added note to the note book: {}", note.title);
+    }
+
+    pub fn get_notes_collection(
+        &self,
+        account_id: AccountId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<PostedNote> {
+        let notes = self
+            .note_book_collections
+            .get(&account_id)
+            .unwrap_or_else(|| env::panic_str("no entry"));
+
+        // Iteration is bounded by the persisted `len`, which is 0 for sets
+        // that were only ever mutated through a copy.
+        notes
+            .iter()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(u64::MAX) as usize)
+            .collect()
+    }
+
+    /// Moves the legacy note sets of `account_ids` into `note_book`.
+    ///
+    /// Sets written by `add_note_collection` have a persisted `len` of 0 and
+    /// every element stored at index 0, so only the last note added is
+    /// recoverable. The index entries of the overwritten notes can't be
+    /// enumerated on-chain and stay behind.
+    pub fn migrate_note_collections(&mut self, account_ids: Vec<AccountId>) {
+        require!(
+            self.moderation.is_manager(&env::predecessor_account_id()),
+            "Only managers can migrate notes"
+        );
+
+        for account_id in account_ids {
+            if self.note_book_collections.remove(&account_id).is_none() {
+                continue;
+            }
+
+            let prefix =
+                borsh::to_vec(&StorageKey::Notes(account_id.clone())).unwrap();
+
+            let elements = legacy_set_elements(&prefix);
+
+            log!("Migrating {} notes of {}", elements.len(), account_id);
+
+            for (element_key, note) in elements {
+                env::storage_remove(&element_key);
+                env::storage_remove(
+                    &[prefix.as_slice(), b"i", &borsh::to_vec(&note).unwrap()]
+                        .concat(),
+                );
+
+                if let Some(id) = note.id {
+                    self.notes_by_id
+                        .insert(id.0, (account_id.clone(), note.clone()));
+                    self.unpaid_notes.insert(id.0);
+                }
+
+                if let Some(notes) = self.note_book.get_mut(&account_id) {
+                    notes.insert(note);
+                } else {
+                    let mut notes =
+                        IterableSet::new(StorageKey::Notes(account_id.clone()));
+                    notes.insert(note);
+
+                    self.note_book.insert(account_id.clone(), notes);
+                }
+            }
+        }
+    }
+
     pub fn get_note(&self, account_id: AccountId, id: U64) -> &PostedNote {
         let id = id.0;
 
@@ -217,6 +401,15 @@ impl Contract {
             .unwrap_or_else(|| env::panic_str("no entry"))
     }
 
+    pub fn get_note_by_id(&self, id: U64) -> &PostedNote {
+        let (_, note) = self
+            .notes_by_id
+            .get(&id.0)
+            .unwrap_or_else(|| env::panic_str("no entry"));
+
+        note
+    }
+
     pub fn get_notes(
         &self,
         account_id: AccountId,
@@ -256,24 +449,65 @@ impl Contract {
             notes.insert(note.clone());
         }
 
+        self.notes_by_id
+            .insert(next_entry_id, (account_id.clone(), note.clone()));
+
         self.next_entry_id = Some(next_entry_id + 1);
 
-        let storage_after = env::storage_usage(); // storage after addition
+        self.internal_settle_storage(
+            &account_id,
+            storage_usage,
+            deposit.unwrap_or(0),
+            true,
+        );
 
-        let storage_cost = env::storage_byte_cost().as_yoctonear()
-            * (storage_after - storage_usage) as u128;
+        log!("Added note to the note book: {}", note.title);
+    }
 
-        let to_refund = deposit
-            .unwrap_or(0)
-            .checked_sub(storage_cost)
-            .expect("not enough attached deposit");
+    /// Charges the storage used since `storage_usage` was measured against
+    /// `deposit`, or adds the freed storage to the refund if `paid`, i.e. the
+    /// caller paid for it.
+    fn internal_settle_storage(
+        &mut self,
+        account_id: &AccountId,
+        storage_usage: u64,
+        deposit: u128,
+        paid: bool,
+    ) {
+        // `store` collections only write on flush, flush them so that
+        // `storage_usage` reflects this call.
+        if let Some(notes) = self.note_book.get_mut(account_id) {
+            notes.flush();
+        }
+        self.note_book.flush();
+        self.notes_by_id.flush();
+
+        let storage_after = env::storage_usage(); // storage after the change
+        let byte_cost = env::storage_byte_cost().as_yoctonear();
+
+        let to_refund = if storage_after >= storage_usage {
+            let storage_cost =
+                byte_cost * (storage_after - storage_usage) as u128;
+
+            deposit
+                .checked_sub(storage_cost)
+                .expect("not enough attached deposit")
+        } else if paid {
+            deposit + byte_cost * (storage_usage - storage_after) as u128
+        } else {
+            deposit
+        };
 
         if to_refund != 0 {
-            Promise::new(account_id)
+            Promise::new(account_id.clone())
                 .transfer(NearToken::from_yoctonear(to_refund));
         }
+    }
 
-        log!("Added note to the note book: {}", note.title);
+    /// Drops the index entries of a removed note.
+    pub(crate) fn internal_forget_note(&mut self, id: u64) {
+        self.notes_by_id.remove(&id);
+        self.unpaid_notes.remove(&id);
     }
 
     fn internal_add_note_collection(
@@ -282,6 +516,8 @@ impl Contract {
         note: &PostedNote,
     ) {
         if let Some(mut notes) = self.note_book_collections.get(&account_id) {
+            require!(notes.len() < MAX_NOTES_PER_USER, "Too many notes");
+
             notes.insert(note);
         } else {
             self.note_book_collections.insert(
@@ -294,11 +530,57 @@ impl Contract {
             notes.insert(note);
         }
     }
+
+    fn internal_add_note_collection_correct(
+        &mut self,
+        account_id: &AccountId,
+        note: &PostedNote,
+    ) {
+        let mut notes = self
+            .note_book_collections
+            .get(account_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(StorageKey::Notes(account_id.clone()))
+            });
+
+        require!(notes.len() < MAX_NOTES_PER_USER, "Too many notes");
+
+        notes.insert(note);
+
+        // `get` returns a copy, the new `len` only persists once the set is
+        // written back.
+        self.note_book_collections.insert(account_id, &notes);
+    }
+}
+
+/// Reads the elements of a legacy `UnorderedSet` with `prefix` straight from
+/// storage, probing `prefix ++ 'e' ++ index` until the first gap, since the
+/// persisted `len` can't be trusted.
+fn legacy_set_elements(prefix: &[u8]) -> Vec<(Vec<u8>, PostedNote)> {
+    let mut elements = Vec::new();
+
+    for index in 0u64.. {
+        let key = [prefix, b"e", &index.to_le_bytes()].concat();
+
+        let Some(raw) = env::storage_read(&key) else {
+            break;
+        };
+
+        let note = borsh::from_slice(&raw)
+            .unwrap_or_else(|_| env::panic_str("Cannot deserialize note"));
+
+        elements.push((key, note));
+    }
+
+    elements
 }
 
 #[cfg(test)]
 mod tests {
-    use near_sdk::{test_utils::VMContextBuilder, testing_env, NearToken};
+    use near_sdk::{
+        test_utils::{get_created_receipts, VMContextBuilder},
+        testing_env, NearToken,
+    };
 
     use super::*;
 
@@ -400,6 +682,124 @@ mod tests {
         assert_eq!(notes.len(), 0);
         assert!(notes.contains(&posted_note));
     }
+
+    #[test]
+    fn add_note_collection_bypasses_limit() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        let account_id = "account_id1";
+        set_context(account_id, NearToken::from_near(1));
+
+        for i in 0..MAX_NOTES_PER_USER + 2 {
+            contract.add_note_collection(format!("title{i}"), "body".into());
+        }
+
+        let notes = contract.get_notes_collection(
+            account_id.parse().unwrap(),
+            None,
+            None,
+        );
+
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn add_note_collection_correct() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        let account_id = "account_id1";
+        set_context(account_id, NearToken::from_near(1));
+
+        for i in 0..MAX_NOTES_PER_USER {
+            contract.add_note_collection_correct(
+                format!("title{i}"),
+                "body".into(),
+            );
+        }
+
+        let notes = contract.get_notes_collection(
+            account_id.parse().unwrap(),
+            Some(1),
+            None,
+        );
+
+        assert_eq!(notes.len(), MAX_NOTES_PER_USER as usize - 1);
+        assert_eq!(notes[0].title, "title1");
+    }
+
+    #[test]
+    #[should_panic(expected = "Too many notes")]
+    fn add_note_collection_correct_enforces_limit() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        set_context("account_id1", NearToken::from_near(1));
+
+        for i in 0..MAX_NOTES_PER_USER + 1 {
+            contract.add_note_collection_correct(
+                format!("title{i}"),
+                "body".into(),
+            );
+        }
+    }
+
+    #[test]
+    fn migrate_note_collections() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        let account_id: AccountId = "account_id1".parse().unwrap();
+        set_context(account_id.as_str(), NearToken::from_near(1));
+
+        contract.add_note_collection("lost".into(), "body".into());
+        contract.add_note_collection("last".into(), "body".into());
+
+        let account_id_2: AccountId = "account_id2".parse().unwrap();
+        set_context(account_id_2.as_str(), NearToken::from_near(1));
+
+        contract.add_note_collection_correct("first".into(), "body".into());
+        contract.add_note_collection_correct("second".into(), "body".into());
+
+        set_context("some_acc.near", NearToken::from_near(0));
+        contract.migrate_note_collections(vec![
+            account_id.clone(),
+            account_id_2.clone(),
+        ]);
+
+        let titles = |account_id: &AccountId| -> Vec<String> {
+            contract
+                .note_book
+                .get(account_id)
+                .unwrap()
+                .iter()
+                .map(|note| note.title.clone())
+                .collect()
+        };
+
+        // The first note was overwritten at index 0 before the migration.
+        assert_eq!(titles(&account_id), vec!["last".to_string()]);
+        assert_eq!(
+            titles(&account_id_2),
+            vec!["first".to_string(), "second".to_string()]
+        );
+
+        assert!(contract.note_book_collections.get(&account_id_2).is_none());
+
+        let prefix = borsh::to_vec(&StorageKey::Notes(account_id_2)).unwrap();
+        assert!(legacy_set_elements(&prefix).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only managers can migrate notes")]
+    fn migrate_note_collections_only_managers() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        set_context("account_id1", NearToken::from_near(0));
+        contract.migrate_note_collections(vec!["account_id1".parse().unwrap()]);
+    }
     #[test]
     fn remove_all_notes() {
         let mut contract =
@@ -500,6 +900,255 @@ mod tests {
         assert!(notes.contains(&posted_note2));
     }
 
+    #[test]
+    fn get_note_by_id() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        let account_id: AccountId = "account_id1".parse().unwrap();
+        set_context(account_id.as_str(), NearToken::from_near(1));
+
+        for i in 0..10 {
+            contract.add_note(format!("title{i}"), "body".into());
+        }
+
+        assert_eq!(
+            contract.get_note_by_id(U64(7)),
+            contract.get_note(account_id, U64(7))
+        );
+        assert_eq!(contract.get_note_by_id(U64(7)).title, "title7");
+    }
+
+    #[test]
+    fn edit_note() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        let account_id: AccountId = "account_id1".parse().unwrap();
+        set_context(account_id.as_str(), NearToken::from_near(1));
+
+        contract.add_note("title".into(), "body".into());
+
+        let storage_usage = env::storage_usage();
+
+        contract.edit_note(U64(0), "title".into(), "a longer body".into());
+
+        let edited = PostedNote::new(
+            "title".into(),
+            "a longer body".into(),
+            Some(U64(0)),
+        );
+
+        let notes = contract.note_book.get(&account_id).unwrap();
+
+        assert_eq!(notes.len(), 1);
+        assert!(notes.contains(&edited));
+        assert_eq!(contract.get_note_by_id(U64(0)), &edited);
+        // Both the set element and the index entry grew
+        assert!(env::storage_usage() > storage_usage);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the author can edit a note")]
+    fn edit_note_not_author() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        set_context("account_id1", NearToken::from_near(1));
+        contract.add_note("title".into(), "body".into());
+
+        set_context("account_id2", NearToken::from_near(1));
+        contract.edit_note(U64(0), "title".into(), "body".into());
+    }
+
+    #[test]
+    fn delete_note() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        let account_id: AccountId = "account_id1".parse().unwrap();
+        set_context(account_id.as_str(), NearToken::from_near(1));
+
+        contract.add_note("title".into(), "body".into());
+
+        let storage_usage = env::storage_usage();
+
+        contract.add_note("title2".into(), "body2".into());
+        contract.delete_note(U64(1));
+
+        let notes = contract.note_book.get(&account_id).unwrap();
+
+        assert_eq!(notes.len(), 1);
+        assert!(contract.notes_by_id.get(&1).is_none());
+        assert_eq!(env::storage_usage(), storage_usage);
+    }
+
+    #[test]
+    #[should_panic(expected = "User is frozen")]
+    fn delete_note_frozen() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        set_context("account_id1", NearToken::from_near(1));
+        contract.add_note("title".into(), "body".into());
+
+        set_context("some_acc.near", NearToken::from_near(0));
+        contract.freeze_user("account_id1".parse().unwrap());
+
+        set_context("account_id1", NearToken::from_near(0));
+        contract.delete_note(U64(0));
+    }
+
+    #[test]
+    fn delete_note_refunds_only_paid_storage() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        let account_id: AccountId = "account_id1".parse().unwrap();
+        set_context(account_id.as_str(), NearToken::from_near(1));
+
+        contract.add_note_collection_correct("migrated".into(), "body".into());
+
+        set_context("some_acc.near", NearToken::from_near(0));
+        contract.migrate_note_collections(vec![account_id.clone()]);
+
+        set_context(account_id.as_str(), NearToken::from_near(1));
+        contract.add_note("paid".into(), "body".into());
+
+        // The migrated note was never paid for, deleting it refunds nothing.
+        set_context(account_id.as_str(), NearToken::from_near(0));
+        contract.delete_note(U64(0));
+
+        assert!(get_created_receipts().is_empty());
+        assert!(!contract.unpaid_notes.contains(&0));
+
+        set_context(account_id.as_str(), NearToken::from_near(0));
+        contract.delete_note(U64(1));
+
+        assert_eq!(get_created_receipts().len(), 1);
+    }
+
+    #[test]
+    fn remove_all_notes_forgets_ids() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        set_context("account_id1", NearToken::from_near(1));
+        contract.add_note("title".into(), "body".into());
+        contract.remove_all_notes();
+
+        set_context("account_id2", NearToken::from_near(1));
+        contract.add_note("title".into(), "body".into());
+        contract.remove_all_notes_correct();
+
+        assert!(contract.notes_by_id.get(&0).is_none());
+        assert!(contract.notes_by_id.get(&1).is_none());
+    }
+
+    /// Two `store::IterableSet` handles with the same prefix each keep their
+    /// own cache and their own `len`, and only write on drop.
+    #[test]
+    fn sets_sharing_prefix_flush_on_drop() {
+        set_context("account_id1", NearToken::from_near(0));
+
+        let timestamp = "1700000000000";
+        let jar = MoneyJar::new(U128(2), U128(11));
+        let jar_2 = MoneyJar::new(U128(7), U128(1));
+
+        {
+            let mut first =
+                IterableSet::new(StorageKey::Jars(timestamp.into()));
+            let mut second =
+                IterableSet::new(StorageKey::Jars(timestamp.into()));
+
+            first.insert(jar.clone());
+            // Nothing is flushed yet, `second` sees an empty set
+            assert!(!second.contains(&jar));
+
+            second.insert(jar_2.clone());
+
+            assert_eq!(first.len(), 1);
+            assert_eq!(second.len(), 1);
+
+            // Locals drop in reverse order: `second` flushes `elements[0] =
+            // jar_2`, then `first` overwrites it with `jar`.
+        }
+
+        let persisted: IterableSet<MoneyJar> =
+            IterableSet::new(StorageKey::Jars(timestamp.into()));
+
+        // Both index entries point at index 0, only the first declared
+        // handle's element survives, and the length lived in the dropped
+        // handles.
+        assert!(persisted.contains(&jar));
+        assert!(persisted.contains(&jar_2));
+        assert_eq!(persisted.len(), 0);
+        assert_eq!(
+            read_element(&StorageKey::Jars(timestamp.into()), 0),
+            Some(jar.clone())
+        );
+
+        // Dropping explicitly flips the winner
+        let mut first = IterableSet::new(StorageKey::Jars("1".into()));
+        let mut second = IterableSet::new(StorageKey::Jars("1".into()));
+
+        first.insert(jar.clone());
+        second.insert(jar_2.clone());
+
+        drop(first);
+        drop(second);
+
+        assert_eq!(read_element(&StorageKey::Jars("1".into()), 0), Some(jar_2));
+    }
+
+    #[test]
+    fn create_jar_timestamp_leaks_temp_sets() {
+        let mut contract =
+            Contract::new(vec!["some_acc.near".parse().unwrap()]);
+
+        let account_id = "account_id1";
+        set_context(account_id, NearToken::from_near(0));
+
+        let jar = MoneyJar::new(U128(2), U128(11));
+        let jar_2 = MoneyJar::new(U128(7), U128(1));
+
+        // Same context, same block timestamp
+        contract.create_jar_timestamp(jar.amount, jar.id);
+        contract.create_jar_timestamp(jar_2.amount, jar_2.id);
+
+        let timestamp = env::block_timestamp_ms().to_string();
+        let temp: IterableSet<MoneyJar> =
+            IterableSet::new(StorageKey::Jars(timestamp.clone()));
+
+        // The second call's set started at len 0 and overwrote the first
+        // call's element, while both index entries persisted.
+        assert_eq!(
+            read_element(&StorageKey::Jars(timestamp.clone()), 0),
+            Some(jar_2.clone())
+        );
+        assert_eq!(read_element(&StorageKey::Jars(timestamp.clone()), 1), None);
+        assert!(temp.contains(&jar));
+        assert!(temp.contains(&jar_2));
+
+        // Nothing references the temporary prefix, the user's jars live
+        // under `Jars(id ++ account ++ timestamp)`.
+        assert_eq!(
+            contract.get_jars(account_id.parse().unwrap()),
+            vec![&jar, &jar_2]
+        );
+    }
+
+    fn read_element(prefix: &StorageKey, index: u32) -> Option<MoneyJar> {
+        let key = [
+            borsh::to_vec(prefix).unwrap().as_slice(),
+            b"v",
+            &index.to_le_bytes(),
+        ]
+        .concat();
+
+        env::storage_read(&key).map(|raw| borsh::from_slice(&raw).unwrap())
+    }
+
     fn set_context(predecessor: &str, amount: NearToken) {
         let mut builder = VMContextBuilder::new();
         builder.predecessor_account_id(predecessor.parse().unwrap());
@@ -0,0 +1,177 @@
+use contract_common::impl_moderation;
+use near_sdk::{env, json_types::U64, log, near, require, AccountId};
+
+use crate::{Contract, ContractExt};
+
+impl_moderation!(Contract, moderation);
+
+#[near]
+impl Contract {
+    /// Removes a note of `account_id`, e.g. spam. Only managers can call it.
+    pub fn moderator_delete_note(&mut self, account_id: AccountId, id: U64) {
+        self.moderation.assert_manager();
+
+        self.internal_moderator_delete_note(&account_id, id);
+    }
+
+    /// Vulnerable: the manager check is missing, anyone can delete anyone's
+    /// notes.
+    pub fn moderator_delete_note_unchecked(
+        &mut self,
+        account_id: AccountId,
+        id: U64,
+    ) {
+        self.internal_moderator_delete_note(&account_id, id);
+    }
+
+    /// Vulnerable: checks that the account being frozen is a manager instead
+    /// of the caller, so anyone can freeze any manager, and managers can't
+    /// freeze regular users at all.
+    pub fn freeze_user_wrong_account(&mut self, account_id: AccountId) {
+        require!(
+            self.moderation.is_manager(&account_id),
+            "Only managers can moderate"
+        );
+
+        self.moderation.internal_freeze_user(account_id);
+    }
+}
+
+impl Contract {
+    pub(crate) fn assert_not_frozen(&self, account_id: &AccountId) {
+        self.moderation.assert_not_frozen(account_id);
+    }
+
+    fn internal_moderator_delete_note(
+        &mut self,
+        account_id: &AccountId,
+        id: U64,
+    ) {
+        let storage_usage = env::storage_usage();
+
+        let (owner_id, note) = self
+            .notes_by_id
+            .remove(&id.0)
+            .unwrap_or_else(|| env::panic_str("Note does not exist"));
+
+        require!(&owner_id == account_id, "Note does not belong to the user");
+
+        self.note_book
+            .get_mut(account_id)
+            .unwrap_or_else(|| env::panic_str("no entry"))
+            .remove(&note);
+
+        // The freed storage goes back to the author, as in `delete_note`.
+        let paid = !self.unpaid_notes.remove(&id.0);
+
+        self.internal_settle_storage(account_id, storage_usage, 0, paid);
+
+        log!(
+            "Moderator {} deleted note {} of {}",
+            env::predecessor_account_id(),
+            id.0,
+            account_id
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{
+        test_utils::{get_created_receipts, VMContextBuilder},
+        testing_env, NearToken,
+    };
+
+    use super::*;
+
+    const MANAGER: &str = "manager.near";
+    const USER: &str = "user.near";
+    const ATTACKER: &str = "attacker.near";
+
+    #[test]
+    fn manager_deletes_note() {
+        let mut contract = contract_with_note();
+
+        set_context(MANAGER);
+        contract.moderator_delete_note(USER.parse().unwrap(), U64(0));
+
+        assert!(contract
+            .get_notes(USER.parse().unwrap(), None, None)
+            .is_empty());
+
+        // The author is refunded for the storage of the note.
+        let receipts = get_created_receipts();
+
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].receiver_id.as_str(), USER);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only managers can moderate")]
+    fn user_cannot_delete_others_note() {
+        let mut contract = contract_with_note();
+
+        set_context(ATTACKER);
+        contract.moderator_delete_note(USER.parse().unwrap(), U64(0));
+    }
+
+    #[test]
+    fn unchecked_delete_lets_anyone_delete() {
+        let mut contract = contract_with_note();
+
+        set_context(ATTACKER);
+        contract.moderator_delete_note_unchecked(USER.parse().unwrap(), U64(0));
+
+        assert!(contract
+            .get_notes(USER.parse().unwrap(), None, None)
+            .is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "User is frozen")]
+    fn frozen_user_cannot_add_notes() {
+        let mut contract = contract_with_note();
+
+        set_context(MANAGER);
+        contract.freeze_user(USER.parse().unwrap());
+
+        set_context(USER);
+        contract.add_note("title2".into(), "body2".into());
+    }
+
+    #[test]
+    fn wrong_account_check_lets_anyone_freeze_managers() {
+        let mut contract = contract_with_note();
+
+        set_context(ATTACKER);
+        contract.freeze_user_wrong_account(MANAGER.parse().unwrap());
+
+        assert!(contract.is_frozen(MANAGER.parse().unwrap()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only managers can moderate")]
+    fn wrong_account_check_stops_managers() {
+        let mut contract = contract_with_note();
+
+        set_context(MANAGER);
+        contract.freeze_user_wrong_account(USER.parse().unwrap());
+    }
+
+    fn contract_with_note() -> Contract {
+        let mut contract = Contract::new(vec![MANAGER.parse().unwrap()]);
+
+        set_context(USER);
+        contract.add_note("title".into(), "body".into());
+
+        contract
+    }
+
+    fn set_context(predecessor: &str) {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor.parse().unwrap());
+        builder.attached_deposit(NearToken::from_near(1));
+
+        testing_env!(builder.build());
+    }
+}
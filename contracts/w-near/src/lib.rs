@@ -0,0 +1,305 @@
+use near_contract_standards::{
+    fungible_token::{
+        metadata::{
+            FungibleTokenMetadata, FungibleTokenMetadataProvider,
+            FT_METADATA_SPEC,
+        },
+        FungibleToken, FungibleTokenCore, FungibleTokenResolver,
+    },
+    storage_management::{
+        StorageBalance, StorageBalanceBounds, StorageManagement,
+    },
+};
+use near_sdk::{
+    assert_one_yocto, env, json_types::U128, log, near, require,
+    store::LazyOption, AccountId, BorshStorageKey, NearToken, PanicOnDefault,
+    Promise, PromiseOrValue,
+};
+
+#[near]
+#[derive(BorshStorageKey)]
+pub enum StorageKey {
+    FungibleToken,
+    Metadata,
+}
+
+/// Stand-in for `wrap.near`, so that tests don't have to import it from
+/// mainnet. Same NEP-141 interface and the same deposit and withdrawal
+/// rules: depositing registers an account that isn't registered yet,
+/// withdrawing requires one yoctoNEAR.
+#[near(contract_state)]
+#[derive(PanicOnDefault)]
+pub struct Contract {
+    token: FungibleToken,
+    metadata: LazyOption<FungibleTokenMetadata>,
+}
+
+#[near]
+impl Contract {
+    #[init]
+    pub fn new() -> Self {
+        let metadata = FungibleTokenMetadata {
+            spec: FT_METADATA_SPEC.to_string(),
+            name: "Wrapped NEAR fungible token".to_string(),
+            symbol: "wNEAR".to_string(),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: 24,
+        };
+
+        Self {
+            token: FungibleToken::new(StorageKey::FungibleToken),
+            metadata: LazyOption::new(StorageKey::Metadata, Some(metadata)),
+        }
+    }
+
+    /// Wraps the attached NEAR. An account that isn't registered yet pays
+    /// its storage from the deposit.
+    #[payable]
+    pub fn near_deposit(&mut self) {
+        let mut amount = env::attached_deposit().as_yoctonear();
+        let account_id = env::predecessor_account_id();
+
+        require!(amount > 0, "Requires positive attached deposit");
+
+        if !self.token.accounts.contains_key(&account_id) {
+            let min_balance =
+                self.token.storage_balance_bounds().min.as_yoctonear();
+
+            require!(amount >= min_balance, "ERR_DEPOSIT_TOO_SMALL");
+
+            self.token.internal_register_account(&account_id);
+            amount -= min_balance;
+        }
+
+        self.token.internal_deposit(&account_id, amount);
+
+        log!("Deposit {} NEAR to {}", amount, account_id);
+    }
+
+    /// Unwraps `amount` and sends it back with the attached yoctoNEAR.
+    #[payable]
+    pub fn near_withdraw(&mut self, amount: U128) -> Promise {
+        assert_one_yocto();
+
+        let account_id = env::predecessor_account_id();
+
+        self.token.internal_withdraw(&account_id, amount.0);
+
+        log!("Withdraw {} NEAR from {}", amount.0, account_id);
+
+        Promise::new(account_id)
+            .transfer(NearToken::from_yoctonear(amount.0 + 1))
+    }
+}
+
+#[near]
+impl FungibleTokenCore for Contract {
+    #[payable]
+    fn ft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+    ) {
+        self.token.ft_transfer(receiver_id, amount, memo)
+    }
+
+    #[payable]
+    fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        self.token.ft_transfer_call(receiver_id, amount, memo, msg)
+    }
+
+    fn ft_total_supply(&self) -> U128 {
+        self.token.ft_total_supply()
+    }
+
+    fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        self.token.ft_balance_of(account_id)
+    }
+}
+
+#[near]
+impl FungibleTokenResolver for Contract {
+    #[private]
+    fn ft_resolve_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128 {
+        let (used_amount, burned_amount) = self
+            .token
+            .internal_ft_resolve_transfer(&sender_id, receiver_id, amount);
+
+        if burned_amount > 0 {
+            log!("Account @{} burned {}", sender_id, burned_amount);
+        }
+
+        used_amount.into()
+    }
+}
+
+#[near]
+impl StorageManagement for Contract {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        self.token.storage_deposit(account_id, registration_only)
+    }
+
+    #[payable]
+    fn storage_withdraw(
+        &mut self,
+        amount: Option<NearToken>,
+    ) -> StorageBalance {
+        self.token.storage_withdraw(amount)
+    }
+
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        if let Some((account_id, balance)) =
+            self.token.internal_storage_unregister(force)
+        {
+            log!("Closed @{} with {}", account_id, balance);
+
+            true
+        } else {
+            false
+        }
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        self.token.storage_balance_bounds()
+    }
+
+    fn storage_balance_of(
+        &self,
+        account_id: AccountId,
+    ) -> Option<StorageBalance> {
+        self.token.storage_balance_of(account_id)
+    }
+}
+
+#[near]
+impl FungibleTokenMetadataProvider for Contract {
+    fn ft_metadata(&self) -> FungibleTokenMetadata {
+        self.metadata.get().clone().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::{test_utils::VMContextBuilder, testing_env};
+
+    use super::*;
+
+    const ALICE: &str = "alice.near";
+    const BOB: &str = "bob.near";
+
+    #[test]
+    fn near_deposit_registers_account() {
+        let mut contract = Contract::new();
+
+        let min_balance = contract.storage_balance_bounds().min;
+
+        set_context(ALICE, NearToken::from_near(1));
+        contract.near_deposit();
+
+        assert_eq!(
+            contract.ft_balance_of(ALICE.parse().unwrap()),
+            U128(
+                NearToken::from_near(1).as_yoctonear()
+                    - min_balance.as_yoctonear()
+            )
+        );
+        assert!(contract
+            .storage_balance_of(ALICE.parse().unwrap())
+            .is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_TOO_SMALL")]
+    fn near_deposit_too_small() {
+        let mut contract = Contract::new();
+
+        set_context(ALICE, NearToken::from_yoctonear(1));
+        contract.near_deposit();
+    }
+
+    #[test]
+    fn near_withdraw_burns_tokens() {
+        let mut contract = Contract::new();
+
+        set_context(ALICE, NearToken::from_near(1));
+        contract.near_deposit();
+
+        let balance = contract.ft_balance_of(ALICE.parse().unwrap());
+
+        set_context(ALICE, NearToken::from_yoctonear(1));
+        contract.near_withdraw(U128(balance.0 / 2));
+
+        assert_eq!(
+            contract.ft_balance_of(ALICE.parse().unwrap()),
+            U128(balance.0 - balance.0 / 2)
+        );
+        assert_eq!(contract.ft_total_supply(), U128(balance.0 - balance.0 / 2));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Requires attached deposit of exactly 1 yoctoNEAR"
+    )]
+    fn near_withdraw_requires_one_yocto() {
+        let mut contract = Contract::new();
+
+        set_context(ALICE, NearToken::from_near(1));
+        contract.near_deposit();
+
+        set_context(ALICE, NearToken::from_yoctonear(0));
+        contract.near_withdraw(U128(1));
+    }
+
+    #[test]
+    fn ft_transfer_between_accounts() {
+        let mut contract = Contract::new();
+
+        set_context(BOB, NearToken::from_near(1));
+        contract.near_deposit();
+
+        set_context(ALICE, NearToken::from_near(1));
+        contract.near_deposit();
+
+        let balance = contract.ft_balance_of(ALICE.parse().unwrap());
+
+        set_context(ALICE, NearToken::from_yoctonear(1));
+        contract.ft_transfer(BOB.parse().unwrap(), U128(10), None);
+
+        assert_eq!(
+            contract.ft_balance_of(ALICE.parse().unwrap()),
+            U128(balance.0 - 10)
+        );
+        assert_eq!(
+            contract.ft_balance_of(BOB.parse().unwrap()),
+            U128(balance.0 + 10)
+        );
+    }
+
+    fn set_context(predecessor: &str, amount: NearToken) {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor.parse().unwrap());
+        builder.attached_deposit(amount);
+
+        testing_env!(builder.build());
+    }
+}
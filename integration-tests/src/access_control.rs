@@ -1,98 +1,71 @@
 use near_sdk::{json_types::U128, AccountId, NearToken};
-use near_workspaces::{Account, Contract};
+use near_workspaces::{network::Sandbox, Account, Contract, Worker};
 use serde_json::json;
 
-const ACCESS_CONTROL_CONTRACT: &[u8] =
-    include_bytes!("../../res/access_control.wasm");
+use crate::{fixtures::Fixture, receipt_tree::ReceiptTree, report, wasm::wasm};
 
-const EXPLOIT_CONTRACT: &[u8] = include_bytes!("../../res/exploit.wasm");
+const ACCESS_CONTROL_CONTRACT: &str = "access-control";
+
+const EXPLOIT_CONTRACT: &str = "exploit";
+
+const W_NEAR_CONTRACT: &str = "w-near";
 
 struct Env {
+    sandbox: Worker<Sandbox>,
     owner: Account,
     malicious_actor: Account,
     access_control_contract: Contract,
     exploit_contract: Contract,
-    w_near: Contract,
 }
 
+/// Access-control contract paying out a local wNEAR, with 10 wNEAR for
+/// each account and contract.
 async fn prepare() -> color_eyre::Result<Env> {
-    let sandbox = near_workspaces::sandbox().await?;
-    let mainnet = near_workspaces::mainnet().await?;
-
-    let w_near = sandbox
-        .import_contract(&"wrap.near".parse()?, &mainnet)
-        .transact()
-        .await?;
-    let owner = sandbox.dev_create_account().await?;
-    let malicious_actor = sandbox.dev_create_account().await?;
-    let access_control_contract =
-        sandbox.dev_deploy(&ACCESS_CONTROL_CONTRACT).await?;
-
-    println!(
-        "ACCESS_CONTROL_CONTRACT_DEPLOYED: {}\n",
-        access_control_contract.id()
-    );
-
-    let exploit_contract = sandbox.dev_deploy(&EXPLOIT_CONTRACT).await?;
-
-    println!("EXPLOIT_CONTRACT_DEPLOYED: {}\n", exploit_contract.id());
-
-    access_control_contract
-        .call("init")
-        .args_json(json!({"owner": owner.id(), "w_near_contract": w_near.id()}))
-        .transact()
-        .await?
-        .into_result()?;
-
-    owner
-        .call(w_near.id(), "new")
-        .transact()
-        .await?
-        .into_result()?;
-
-    println!("w_near contract deployed: {}\n", w_near.id());
-
-    owner
-        .call(w_near.id(), "near_deposit")
-        .deposit(NearToken::from_near(10))
-        .transact()
-        .await?
-        .into_result()?;
-
-    malicious_actor
-        .call(w_near.id(), "near_deposit")
-        .deposit(NearToken::from_near(10))
-        .transact()
-        .await?
-        .into_result()?;
-
-    exploit_contract
-        .as_account()
-        .call(w_near.id(), "near_deposit")
-        .deposit(NearToken::from_near(10))
-        .transact()
-        .await?
-        .into_result()?;
-
-    access_control_contract
-        .as_account()
-        .call(w_near.id(), "near_deposit")
-        .deposit(NearToken::from_near(10))
-        .transact()
-        .await?
-        .into_result()?;
-
-    Ok(Env {
-        owner,
-        malicious_actor,
-        access_control_contract,
-        exploit_contract,
-        w_near,
-    })
+    let mut builder = Fixture::builder();
+
+    let owner = builder.account();
+    let malicious_actor = builder.account();
+    let w_near = builder.contract(wasm(W_NEAR_CONTRACT));
+    let access_control = builder.contract(wasm(ACCESS_CONTROL_CONTRACT));
+    let exploit = builder.contract(wasm(EXPLOIT_CONTRACT));
+
+    builder.init(w_near, "new", |_| json!({}));
+    builder.init(access_control, "init", move |fixture| {
+        json!({
+            "owner": fixture[owner].id(),
+            "w_near_contract": fixture[w_near].id(),
+        })
+    });
+
+    let fixture = builder.build().await?;
+    let w_near = fixture[w_near].clone();
+
+    let env = Env {
+        owner: fixture[owner].clone(),
+        malicious_actor: fixture[malicious_actor].clone(),
+        access_control_contract: fixture[access_control].clone(),
+        exploit_contract: fixture[exploit].clone(),
+        sandbox: fixture.sandbox,
+    };
+
+    for account in [
+        &env.owner,
+        &env.malicious_actor,
+        env.exploit_contract.as_account(),
+        env.access_control_contract.as_account(),
+    ] {
+        account
+            .call(w_near.id(), "near_deposit")
+            .deposit(NearToken::from_near(10))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+
+    Ok(env)
 }
 
-#[tokio::test]
-async fn pausable_access_control() -> color_eyre::Result<()> {
+pub async fn pausable_access_control() -> color_eyre::Result<()> {
     let Env {
         malicious_actor,
         access_control_contract,
@@ -123,8 +96,7 @@ async fn pausable_access_control() -> color_eyre::Result<()> {
     Ok(())
 }
 
-#[tokio::test]
-async fn pausable_exploit_signer() -> color_eyre::Result<()> {
+pub async fn pausable_exploit_signer() -> color_eyre::Result<()> {
     let Env {
         owner,
         malicious_actor,
@@ -141,6 +113,8 @@ async fn pausable_exploit_signer() -> color_eyre::Result<()> {
 
     assert_eq!(&data, owner.id());
 
+    report::state_before("owner", &data);
+
     owner
         .call(exploit_contract.id(), "exploit_signer")
         .args_json(json!({
@@ -159,12 +133,14 @@ async fn pausable_exploit_signer() -> color_eyre::Result<()> {
 
     assert_eq!(&data, malicious_actor.id());
 
+    report::state_after("owner", &data);
+
     Ok(())
 }
 
-#[tokio::test]
-async fn public_callback_exploit() -> color_eyre::Result<()> {
+pub async fn public_callback_exploit() -> color_eyre::Result<()> {
     let Env {
+        sandbox,
         malicious_actor,
         access_control_contract,
         exploit_contract,
@@ -179,6 +155,8 @@ async fn public_callback_exploit() -> color_eyre::Result<()> {
 
     assert_eq!(data, U128(0));
 
+    report::state_before("attacker points", data);
+
     malicious_actor
         .call(access_control_contract.id(), "resolve_withdraw")
         .args_json(json!({
@@ -201,10 +179,14 @@ async fn public_callback_exploit() -> color_eyre::Result<()> {
             "amount": 10000,
         }))
         .transact()
-        .await?
-        .into_result()?;
+        .await?;
 
-    println!("RES: {res:#?}");
+    report::gas_burnt(res.total_gas_burnt);
+
+    // panic → resolve_withdraw → exploit_callback
+    report::receipt_tree(&ReceiptTree::fetch(&sandbox, res.outcome()).await?);
+
+    res.into_result()?;
 
     let data = malicious_actor
         .view(access_control_contract.id(), "get_user_points")
@@ -214,5 +196,7 @@ async fn public_callback_exploit() -> color_eyre::Result<()> {
 
     assert_eq!(data.0, 10000);
 
+    report::state_after("attacker points", data);
+
     Ok(())
 }
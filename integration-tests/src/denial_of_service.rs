@@ -1,11 +1,23 @@
-use near_sdk::{env, json_types::U128, near, AccountId, NearToken};
+use near_sdk::{
+    env, json_types::U128, near, serde::Deserialize, AccountId, Gas, NearToken,
+};
 use near_workspaces::{
-    operations::Function, types::SecretKey, Account, Contract,
+    network::Sandbox, operations::Function, result::ExecutionFinalResult,
+    types::AccountDetails, Account, Contract, Worker,
 };
 use serde_json::json;
 
-const DENIAL_OF_SERVICE: &[u8] =
-    include_bytes!("../../res/denial_of_service.wasm");
+use crate::{
+    fixtures::{assert_fails_with, Fixture},
+    limits::{MAX_NUMBER_LOGS, MAX_TOTAL_LOG_LENGTH},
+    profiler::{profiles_dir, Limit, Metric, Profile, Profiler, Sample},
+    receipt_tree::ReceiptTree,
+    report,
+    wasm::wasm,
+};
+
+const DENIAL_OF_SERVICE: &str = "denial-of-service";
+const DENIAL_OF_SERVICE_FIXED: &str = "denial-of-service-fixed";
 
 #[near(serializers = [borsh, json])]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -14,58 +26,64 @@ pub struct MoneyJar {
     pub id: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: NearToken,
+    pub available: NearToken,
+}
+
 struct Env {
-    owner: Account,
+    sandbox: Worker<Sandbox>,
     malicious_actor: Account,
     malicious_actor2: Account,
     denial_of_service_contract: Contract,
+    denial_of_service_fixed: Contract,
 }
 
 async fn prepare() -> color_eyre::Result<Env> {
-    let sandbox = near_workspaces::sandbox().await?;
-
-    let owner = sandbox.dev_create_account().await?;
-    let malicious_actor = sandbox.dev_create_account().await?;
-
-    let malicious_actor2 = sandbox.dev_create_account().await?;
-
-    let storage_collisions_contract =
-        sandbox.dev_deploy(&DENIAL_OF_SERVICE).await?;
-
-    println!(
-        "STORAGE_KEY_COLLISION_CONTRACT: {}\n",
-        storage_collisions_contract.id()
+    let mut builder = Fixture::builder();
+
+    let owner = builder.account();
+    let malicious_actor = builder.account();
+    let malicious_actor2 = builder.account();
+    let denial_of_service = builder.contract(wasm(DENIAL_OF_SERVICE));
+    let denial_of_service_fixed =
+        builder.contract(wasm(DENIAL_OF_SERVICE_FIXED));
+
+    builder.init(
+        denial_of_service,
+        "new",
+        move |fixture| json!({"managers": [fixture[owner].id()]}),
     );
+    builder.init(denial_of_service_fixed, "new", |_| json!({}));
 
-    storage_collisions_contract
-        .call("new")
-        .args_json(json!({"managers": vec![owner.id()]}))
-        .transact()
-        .await?
-        .into_result()?;
+    let fixture = builder.build().await?;
 
     Ok(Env {
-        owner,
-        malicious_actor,
-        malicious_actor2,
-        denial_of_service_contract: storage_collisions_contract,
+        malicious_actor: fixture[malicious_actor].clone(),
+        malicious_actor2: fixture[malicious_actor2].clone(),
+        denial_of_service_contract: fixture[denial_of_service].clone(),
+        denial_of_service_fixed: fixture[denial_of_service_fixed].clone(),
+        sandbox: fixture.sandbox,
     })
 }
 
-#[tokio::test]
-async fn log_limit_dos() -> color_eyre::Result<()> {
+pub async fn log_limit_dos() -> color_eyre::Result<()> {
     let Env {
         malicious_actor,
         malicious_actor2,
         denial_of_service_contract,
-        owner,
+        ..
     } = prepare().await?;
 
-    let total_jars = (0..105)
-        .map(|i| (U128(NearToken::from_near(2).as_yoctonear()), U128(i)))
+    // One log per jar: the first actor alone fills the log limit, the second
+    // pushes the rewarding past it.
+    let total_jars = (0..MAX_NUMBER_LOGS + 5)
+        .map(|i| (U128(NearToken::from_near(2).as_yoctonear()), U128(i.into())))
         .collect::<Vec<_>>();
 
-    let (jars_1, jars_2) = total_jars.split_at(100);
+    let (jars_1, jars_2) = total_jars.split_at(MAX_NUMBER_LOGS as usize);
 
     malicious_actor
         .call(denial_of_service_contract.id(), "batch_create_jars")
@@ -83,20 +101,20 @@ async fn log_limit_dos() -> color_eyre::Result<()> {
         .await?
         .into_result()?;
 
-    denial_of_service_contract
+    let result = denial_of_service_contract
         .call("add_reward_to_each_jar")
         .args_json(
             json!({"account_ids": vec![(malicious_actor.id(), U128(10000)), (malicious_actor2.id(), U128(30000))]}),
         ).max_gas()
         .transact()
-        .await?
-        .into_result()?;
+        .await?;
+
+    assert_fails_with(result, "NumberOfLogsExceeded");
 
     Ok(())
 }
 
-#[tokio::test]
-async fn log_size_dos() -> color_eyre::Result<()> {
+pub async fn log_size_dos() -> color_eyre::Result<()> {
     let Env {
         malicious_actor,
         malicious_actor2,
@@ -126,20 +144,20 @@ async fn log_size_dos() -> color_eyre::Result<()> {
         .await?
         .into_result()?;
 
-    denial_of_service_contract
+    let result = denial_of_service_contract
         .call("add_reward_to_each_jar_log_size_limit")
         .args_json(
             json!({"account_ids": vec![(malicious_actor.id(), U128(10000)), (malicious_actor2.id(), U128(30000))]}),
         ).max_gas()
         .transact()
-        .await?
-        .into_result()?;
+        .await?;
+
+    assert_fails_with(result, "TotalLogLengthExceeded");
 
     Ok(())
 }
 
-#[tokio::test]
-async fn gas_limit_dos() -> color_eyre::Result<()> {
+pub async fn gas_limit_dos() -> color_eyre::Result<()> {
     let Env {
         malicious_actor,
         malicious_actor2,
@@ -179,81 +197,819 @@ async fn gas_limit_dos() -> color_eyre::Result<()> {
         .await?
         .into_result()?;
 
-    malicious_actor
+    // 8000 jars cost more than 300 Tgas to claim, they are stuck for good.
+    let result = malicious_actor
         .call(denial_of_service_contract.id(), "claim_all_jars")
         .args_json(json!({}))
         .max_gas()
         .transact()
+        .await?;
+
+    assert_fails_with(result, "GasExceeded");
+
+    Ok(())
+}
+
+pub async fn storage_bloating_dos() -> color_eyre::Result<()> {
+    let Env {
+        malicious_actor,
+        denial_of_service_contract: contract,
+        ..
+    } = prepare().await?;
+
+    // Transferring out most of the free balance to make exploitation faster
+    let spare = free_balance(contract.view_account().await?)
+        - NearToken::from_millinear(20).as_yoctonear();
+
+    contract
+        .as_account()
+        .transfer_near(malicious_actor.id(), NearToken::from_yoctonear(spare))
         .await?
         .into_result()?;
 
+    report::state_before(
+        "contract free balance",
+        U128(free_balance(contract.view_account().await?)),
+    );
+
+    let mut i = 0;
+
+    let error = loop {
+        assert!(i < 100, "The contract still pays for jars after {i}");
+
+        let storage_usage_before = contract.view_account().await?.storage_usage;
+
+        let result = malicious_actor
+            .call(contract.id(), "create_jar")
+            .args_json(json!({"amount": U128(NearToken::from_near(2).as_yoctonear()), "id": U128(i)}))
+            .transact()
+            .await?;
+
+        report::gas_burnt(result.total_gas_burnt);
+
+        let tokens_burnt = result.outcome().tokens_burnt.as_yoctonear();
+
+        if let Err(error) = result.into_result() {
+            break error;
+        }
+
+        let storage_usage_after = contract.view_account().await?.storage_usage;
+        let cost_per_storage_addition =
+            (storage_usage_after - storage_usage_before) as u128
+                * env::storage_byte_cost().as_yoctonear();
+
+        // The contract's share of the gas doesn't cover the bytes it locks.
+        assert!(tokens_burnt * 30 / 100 < cost_per_storage_addition);
+
+        i += 1;
+    };
+
+    report::state_after(
+        "contract free balance",
+        U128(free_balance(contract.view_account().await?)),
+    );
+
+    // The attacker's free jars used up the contract's balance, no one can
+    // write to it anymore.
+    assert!(i > 0);
+    assert!(format!("{error:?}").contains("LackBalanceForState"));
+
     Ok(())
 }
 
-#[tokio::test]
-async fn storage_bloating_dos() -> color_eyre::Result<()> {
+pub async fn storage_bloating_dos_fixed() -> color_eyre::Result<()> {
     let Env {
         malicious_actor,
-        denial_of_service_contract,
+        denial_of_service_fixed: contract,
         ..
     } = prepare().await?;
 
-    // Transferring out some NEAR to make exploitation faster
-    denial_of_service_contract
-        .as_account()
-        .transfer_near(
-            malicious_actor.id(),
-            NearToken::from_yoctonear(97_520_000_000_000_000_000_000_000),
-        )
+    // Writing without a storage balance is rejected outright.
+    let result = malicious_actor
+        .call(contract.id(), "create_jar")
+        .args_json(json!({"amount": U128(NearToken::from_near(2).as_yoctonear()), "id": U128(0)}))
+        .transact()
+        .await?;
+
+    assert!(format!("{:?}", result.into_result().unwrap_err())
+        .contains("The account is not registered"));
+
+    malicious_actor
+        .call(contract.id(), "storage_deposit")
+        .args_json(json!({}))
+        .deposit(NearToken::from_millinear(50))
+        .transact()
         .await?
         .into_result()?;
 
-    for i in 0..100 {
-        let storage_usage_before = denial_of_service_contract
-            .view_account()
+    let mut i = 0;
+
+    let error = loop {
+        let free_balance_before = free_balance(contract.view_account().await?);
+        let storage_balance_before = malicious_actor
+            .view(contract.id(), "storage_balance_of")
+            .args_json(json!({"account_id": malicious_actor.id()}))
             .await?
-            .storage_usage;
+            .json::<StorageBalance>()?;
 
-        let data = malicious_actor
-            .call(denial_of_service_contract.id(), "create_jar")
-            .args_json(json!({"amount": U128(NearToken::from_near(2).as_yoctonear()), "id": U128(i)}))
-            .transact().await?.into_result()?;
+        let result = malicious_actor
+            .call(contract.id(), "create_jar")
+            .args_json(json!({"amount": U128(1), "id": U128(i)}))
+            .deposit(NearToken::from_yoctonear(1))
+            .transact()
+            .await?;
 
-        let tokens_burnt = data.outcome().tokens_burnt.as_yoctonear();
-        let smart_contract_gain = tokens_burnt * 30 / 100;
+        if let Err(error) = result.into_result() {
+            break error;
+        }
 
-        let storage_usage_after = denial_of_service_contract
-            .view_account()
+        let storage_balance_after = malicious_actor
+            .view(contract.id(), "storage_balance_of")
+            .args_json(json!({"account_id": malicious_actor.id()}))
+            .await?
+            .json::<StorageBalance>()?;
+
+        // Every jar is paid from the attacker's deposit: the bytes locked on
+        // the contract are matched by the drop in the attacker's available
+        // storage balance, and gas rewards only add to the contract.
+        assert!(
+            storage_balance_after.available < storage_balance_before.available
+        );
+        assert!(
+            free_balance(contract.view_account().await?)
+                + storage_balance_after.available.as_yoctonear()
+                >= free_balance_before
+                    + storage_balance_before.available.as_yoctonear()
+        );
+
+        i += 1;
+    };
+
+    assert!(i > 0);
+    assert!(format!("{error:?}").contains("Insufficient storage balance"));
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Distribution {
+    pub account_index: u32,
+    pub jar_index: u32,
+    pub jars_rewarded: u64,
+    pub jars_total: u64,
+    pub finished: bool,
+}
+
+pub async fn reward_distribution_fixed() -> color_eyre::Result<()> {
+    let mut builder = Fixture::builder();
+
+    let owners = (0..10).map(|_| builder.account()).collect::<Vec<_>>();
+    let contract = builder.contract(wasm(DENIAL_OF_SERVICE_FIXED));
+    builder.init(contract, "new", |_| json!({}));
+
+    let fixture = builder.build().await?;
+    let contract = &fixture[contract];
+
+    let owners = owners
+        .into_iter()
+        .map(|owner| fixture[owner].clone())
+        .collect::<Vec<_>>();
+
+    // 10k jars over 10 accounts, the vulnerable contract already fails
+    // rewarding ~100.
+    for owner in &owners {
+        owner
+            .call(contract.id(), "storage_deposit")
+            .args_json(json!({}))
+            .deposit(NearToken::from_near(4))
+            .transact()
+            .await?
+            .into_result()?;
+
+        for chunk in (0..1000).collect::<Vec<u128>>().chunks(250) {
+            let jars = chunk
+                .iter()
+                .map(|id| (U128(100), U128(*id)))
+                .collect::<Vec<_>>();
+
+            owner
+                .call(contract.id(), "batch_create_jars")
+                .args_json(json!({"jars": jars}))
+                .deposit(NearToken::from_yoctonear(100 * jars.len() as u128))
+                .max_gas()
+                .transact()
+                .await?
+                .into_result()?;
+        }
+    }
+
+    contract
+        .call("start_reward_distribution")
+        .args_json(json!({"reward_per_jar": U128(10000)}))
+        .transact()
+        .await?
+        .into_result()?;
+
+    let cranker = &owners[0];
+
+    // A chunk that runs out of gas reverts and leaves the cursor in place.
+    cranker
+        .call(contract.id(), "distribute_rewards")
+        .args_json(json!({"limit": 500}))
+        .gas(Gas::from_tgas(10))
+        .transact()
+        .await?
+        .into_result()
+        .unwrap_err();
+
+    let before = contract
+        .view("get_reward_distribution")
+        .await?
+        .json::<Distribution>()?;
+
+    assert_eq!((before.account_index, before.jar_index), (0, 0));
+    assert_eq!(before.jars_rewarded, 0);
+    assert_eq!(before.jars_total, 10_000);
+
+    let mut calls = 0;
+
+    loop {
+        let result = cranker
+            .call(contract.id(), "distribute_rewards")
+            .args_json(json!({"limit": 500}))
+            .max_gas()
+            .transact()
+            .await?
+            .into_result()?;
+
+        calls += 1;
+
+        let distribution = result.json::<Distribution>()?;
+
+        if distribution.finished {
+            assert_eq!(distribution.jars_rewarded, 10_000);
+            break;
+        }
+    }
+
+    assert_eq!(calls, 20);
+
+    for owner in &owners {
+        let jars = contract
+            .view("get_jars")
+            .args_json(json!({"account_id": owner.id()}))
             .await?
-            .storage_usage;
+            .json::<Vec<MoneyJar>>()?;
 
-        let current_storage_cost_in_near = storage_usage_after as u128
-            * env::storage_byte_cost().as_yoctonear();
+        assert_eq!(jars.len(), 1000);
+        assert!(jars.iter().all(|jar| jar.amount.0 == 100 + 10000));
+    }
 
-        let bytes_per_storage_addition =
-            (storage_usage_after - storage_usage_before) as u128;
+    Ok(())
+}
 
-        let cost_per_storage_addition = bytes_per_storage_addition as u128
-            * env::storage_byte_cost().as_yoctonear();
+pub async fn double_claim_drain() -> color_eyre::Result<()> {
+    let Env {
+        malicious_actor,
+        denial_of_service_contract: contract,
+        ..
+    } = prepare().await?;
 
-        let free_balance = denial_of_service_contract
+    let jar_amount = NearToken::from_near(10);
+
+    malicious_actor
+        .call(contract.id(), "create_jar")
+        .args_json(
+            json!({"amount": U128(jar_amount.as_yoctonear()), "id": U128(0)}),
+        )
+        .transact()
+        .await?
+        .into_result()?;
+
+    report::state_before(
+        "contract balance",
+        contract.view_account().await?.balance,
+    );
+
+    // `claim_all_jars` zeroes clones, the stored jar keeps its amount and
+    // can be claimed again and again.
+    for _ in 0..3 {
+        let attacker_before = malicious_actor.view_account().await?.balance;
+        let contract_before = contract.view_account().await?.balance;
+
+        let result = malicious_actor
+            .call(contract.id(), "claim_all_jars")
+            .args_json(json!({}))
+            .max_gas()
+            .transact()
+            .await?;
+
+        report::gas_burnt(result.total_gas_burnt);
+        result.into_result()?;
+
+        let attacker_after = malicious_actor.view_account().await?.balance;
+
+        report::attacker_profit(
+            attacker_after.as_yoctonear() as i128
+                - attacker_before.as_yoctonear() as i128,
+        );
+
+        let attacker_gain = attacker_after.saturating_sub(attacker_before);
+        let contract_loss = contract_before
+            .saturating_sub(contract.view_account().await?.balance);
+
+        assert!(
+            attacker_gain
+                > jar_amount.saturating_sub(NearToken::from_millinear(10))
+        );
+        assert!(
+            contract_loss
+                > jar_amount.saturating_sub(NearToken::from_millinear(10))
+        );
+    }
+
+    let jars = contract
+        .view("get_jars")
+        .args_json(json!({"account_id": malicious_actor.id()}))
+        .await?
+        .json::<Vec<MoneyJar>>()?;
+
+    assert_eq!(jars[0].amount.0, jar_amount.as_yoctonear());
+
+    report::state_after(
+        "contract balance",
+        contract.view_account().await?.balance,
+    );
+
+    Ok(())
+}
+
+pub async fn double_claim_drain_fixed() -> color_eyre::Result<()> {
+    let Env {
+        malicious_actor,
+        denial_of_service_fixed: contract,
+        ..
+    } = prepare().await?;
+
+    let jar_amount = NearToken::from_near(5);
+
+    malicious_actor
+        .call(contract.id(), "storage_deposit")
+        .args_json(json!({}))
+        .deposit(NearToken::from_millinear(100))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // The jar amount has to be paid in.
+    let contract_before = contract.view_account().await?.balance;
+
+    malicious_actor
+        .call(contract.id(), "create_jar")
+        .args_json(
+            json!({"amount": U128(jar_amount.as_yoctonear()), "id": U128(0)}),
+        )
+        .deposit(jar_amount)
+        .transact()
+        .await?
+        .into_result()?;
+
+    assert!(
+        contract
             .view_account()
             .await?
             .balance
-            .as_yoctonear()
-            - current_storage_cost_in_near;
-
-        assert!(smart_contract_gain < cost_per_storage_addition);
-
-        println!(
-            "Cost per storage write {cost_per_storage_addition} || Attacker \
-             cost per iteration: {tokens_burnt} || Storage added: \
-             {bytes_per_storage_addition}\n || Contract Storage: \
-             {storage_usage_after} || Contract Storage Cost: \
-             {current_storage_cost_in_near}|| Contract Gained Per Iteration: \
-             {smart_contract_gain}\n || Free Balance: {free_balance}\n"
-        );
+            .saturating_sub(contract_before)
+            >= jar_amount
+    );
+
+    let attacker_before = malicious_actor.view_account().await?.balance;
+    let contract_before = contract.view_account().await?.balance;
+
+    malicious_actor
+        .call(contract.id(), "claim_all_jars")
+        .args_json(json!({}))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+
+    let attacker_after = malicious_actor.view_account().await?.balance;
+    let contract_after = contract.view_account().await?.balance;
+
+    // The first claim pays the jar out once, and no more than that.
+    assert!(
+        attacker_after.saturating_sub(attacker_before)
+            > jar_amount.saturating_sub(NearToken::from_millinear(10))
+    );
+    assert!(contract_before.saturating_sub(contract_after) <= jar_amount);
+
+    let result = malicious_actor
+        .call(contract.id(), "claim_all_jars")
+        .args_json(json!({}))
+        .max_gas()
+        .transact()
+        .await?;
+
+    assert!(format!("{:?}", result.into_result().unwrap_err())
+        .contains("No jars found for account"));
+
+    // The failed claim only cost the attacker gas.
+    assert!(malicious_actor.view_account().await?.balance < attacker_after);
+    assert!(contract.view_account().await?.balance >= contract_after);
+
+    let jars = contract
+        .view("get_jars")
+        .args_json(json!({"account_id": malicious_actor.id()}))
+        .await?
+        .json::<Vec<MoneyJar>>()?;
+
+    assert!(jars.is_empty());
+
+    Ok(())
+}
+
+/// Amount of every jar `contract_with_jars` creates.
+const JAR_AMOUNT: NearToken = NearToken::from_millinear(2);
+
+/// Deploys a fresh `denial-of-service` contract where one account owns
+/// `size` jars.
+async fn contract_with_jars(
+    sandbox: &Worker<Sandbox>,
+    size: usize,
+) -> color_eyre::Result<(Contract, Account)> {
+    let mut builder = Fixture::builder();
+
+    let owner = builder.account();
+    let contract = builder.contract(wasm(DENIAL_OF_SERVICE));
+    builder.init(
+        contract,
+        "new",
+        move |fixture| json!({"managers": [fixture[owner].id()]}),
+    );
+
+    let fixture = builder.build_in(sandbox.clone()).await?;
+    let contract = fixture[contract].clone();
+    let owner = fixture[owner].clone();
+
+    let jars = (0..size as u128)
+        .map(|i| (U128(JAR_AMOUNT.as_yoctonear()), U128(i)))
+        .collect::<Vec<_>>();
+
+    for chunk in jars.chunks(800) {
+        owner
+            .call(contract.id(), "batch_create_jars")
+            .args_json(json!({"jars": chunk}))
+            .max_gas()
+            .transact()
+            .await?
+            .into_result()?;
     }
 
+    Ok((contract, owner))
+}
+
+/// Profiles a method of the `denial-of-service` contract that rewards every
+/// jar of one account.
+async fn profile_reward_method(
+    method: &str,
+    sizes: &[usize],
+) -> color_eyre::Result<Profile> {
+    let sandbox = near_workspaces::sandbox().await?;
+
+    let profiler = Profiler::new(method, |size| {
+        let sandbox = sandbox.clone();
+
+        async move {
+            let (contract, owner) = contract_with_jars(&sandbox, size).await?;
+
+            let result = contract
+                .call(method)
+                .args_json(json!({"account_ids": [(owner.id(), U128(10000))]}))
+                .max_gas()
+                .transact()
+                .await?;
+
+            let storage_usage = contract.view_account().await?.storage_usage;
+
+            Ok(Sample::new(size, result, storage_usage))
+        }
+    });
+
+    let profile = profiler.run(sizes, 1).await?;
+    profile.write(profiles_dir())?;
+
+    Ok(profile)
+}
+
+pub async fn profile_log_limit_dos() -> color_eyre::Result<()> {
+    let profile =
+        profile_reward_method("add_reward_to_each_jar", &[10, 50, 90, 130])
+            .await?;
+
+    let breaking_point = profile.breaking_point.as_ref().unwrap();
+
+    // One log per jar, one jar past the log limit is one log too many.
+    assert_eq!(breaking_point.limit, Some(Limit::LogCount));
+    assert_eq!(breaking_point.last_success.size, MAX_NUMBER_LOGS as usize);
+    assert_eq!(
+        breaking_point.first_failure.size,
+        MAX_NUMBER_LOGS as usize + 1
+    );
+
+    let fit = profile.fit(Metric::LogCount).unwrap();
+
+    assert!((fit.slope - 1.0).abs() < 1e-9);
+    assert!(
+        (profile.prediction(Limit::LogCount).unwrap() - MAX_NUMBER_LOGS as f64)
+            .abs()
+            < 1.0
+    );
+
+    Ok(())
+}
+
+pub async fn profile_log_size_dos() -> color_eyre::Result<()> {
+    let profile = profile_reward_method(
+        "add_reward_to_each_jar_log_size_limit",
+        &[10, 40, 80, 160, 320],
+    )
+    .await?;
+
+    let breaking_point = profile.breaking_point.as_ref().unwrap();
+
+    assert_eq!(breaking_point.limit, Some(Limit::LogBytes));
+    assert_eq!(
+        breaking_point.first_failure.size,
+        breaking_point.last_success.size + 1
+    );
+    assert!(breaking_point.last_success.log_bytes <= MAX_TOTAL_LOG_LENGTH);
+
+    // A single log that grows by the same pretty-printed jar every time.
+    let fit = profile.fit(Metric::LogBytes).unwrap();
+    let predicted = profile.prediction(Limit::LogBytes).unwrap();
+
+    assert!(fit.r_squared > 0.99);
+    assert!((predicted - breaking_point.last_success.size as f64).abs() < 5.0);
+
+    Ok(())
+}
+
+pub async fn profile_gas_limit_dos() -> color_eyre::Result<()> {
+    let sandbox = near_workspaces::sandbox().await?;
+
+    let profiler = Profiler::new("claim_all_jars", |size| {
+        let sandbox = sandbox.clone();
+
+        async move {
+            let (contract, owner) = contract_with_jars(&sandbox, size).await?;
+
+            let result = owner
+                .call(contract.id(), "claim_all_jars")
+                .args_json(json!({}))
+                .max_gas()
+                .transact()
+                .await?;
+
+            let storage_usage = contract.view_account().await?.storage_usage;
+
+            Ok(Sample::new(size, result, storage_usage))
+        }
+    });
+
+    // Jars are created 800 a transaction, bisect to within 100 jars.
+    let profile = profiler.run(&[800, 1600, 3200, 6400, 9600], 100).await?;
+    profile.write(profiles_dir())?;
+
+    let breaking_point = profile.breaking_point.as_ref().unwrap();
+    let fit = profile.fit(Metric::GasBurnt).unwrap();
+    let predicted = profile.prediction(Limit::Gas).unwrap();
+
+    assert_eq!(breaking_point.limit, Some(Limit::Gas));
+    assert!(fit.r_squared > 0.99);
+    assert!(
+        (predicted - breaking_point.first_failure.size as f64).abs()
+            < 0.1 * breaking_point.first_failure.size as f64
+    );
+
+    Ok(())
+}
+
+/// Balance of `account` not locked for its storage.
+fn free_balance(account: AccountDetails) -> u128 {
+    account.balance.as_yoctonear()
+        - account.storage_usage as u128
+            * env::storage_byte_cost().as_yoctonear()
+}
+
+/// NEAR burnt for the gas of a transaction and all its receipts.
+fn tokens_burnt(result: &ExecutionFinalResult) -> u128 {
+    result
+        .outcomes()
+        .iter()
+        .map(|outcome| outcome.tokens_burnt.as_yoctonear())
+        .sum()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum FanOutOutcome {
+    /// Every jar was paid and removed.
+    Paid,
+    /// Every jar was paid, but some callbacks ran out of gas and left their
+    /// jars behind to be claimed again.
+    Inconsistent { paid: usize, left: usize },
+    /// The claim itself ran out of gas, nothing was paid.
+    Reverted,
+}
+
+pub async fn promise_fan_out_dos() -> color_eyre::Result<()> {
+    let sandbox = near_workspaces::sandbox().await?;
+
+    let mut outcomes = Vec::new();
+
+    for size in [5, 10, 20, 30, 40, 45, 50, 55, 60, 80] {
+        let (contract, owner) = contract_with_jars(&sandbox, size).await?;
+
+        let balance_before = owner.view_account().await?.balance;
+
+        let result = owner
+            .call(contract.id(), "claim_all_jars_per_jar")
+            .args_json(json!({}))
+            .max_gas()
+            .transact()
+            .await?;
+
+        let claim_succeeded = result.receipt_outcomes()[0].is_success();
+        let paid = result
+            .logs()
+            .iter()
+            .filter(|log| log.starts_with("Paid jar"))
+            .count();
+
+        let left = contract
+            .view("get_jars")
+            .args_json(json!({"account_id": owner.id()}))
+            .await?
+            .json::<Vec<MoneyJar>>()?
+            .len();
+
+        let balance_after = owner.view_account().await?.balance;
+
+        let outcome = if !claim_succeeded {
+            assert_eq!(left, size);
+            assert!(balance_after < balance_before);
+
+            FanOutOutcome::Reverted
+        } else if left == 0 {
+            FanOutOutcome::Paid
+        } else {
+            // All transfers went through, the jars of the failed callbacks
+            // were paid but are still there.
+            assert_eq!(paid + left, size);
+            assert_eq!(
+                balance_after.as_yoctonear() + tokens_burnt(&result),
+                balance_before.as_yoctonear()
+                    + JAR_AMOUNT.as_yoctonear() * size as u128
+            );
+
+            // Claiming again pays the leftover jars a second time.
+            let balance_before = balance_after;
+
+            let result = owner
+                .call(contract.id(), "claim_all_jars_per_jar")
+                .args_json(json!({}))
+                .max_gas()
+                .transact()
+                .await?;
+
+            assert!(result.receipt_outcomes()[0].is_success());
+
+            let balance_after = owner.view_account().await?.balance;
+
+            assert_eq!(
+                balance_after.as_yoctonear() + tokens_burnt(&result),
+                balance_before.as_yoctonear()
+                    + JAR_AMOUNT.as_yoctonear() * left as u128
+            );
+
+            FanOutOutcome::Inconsistent { paid, left }
+        };
+
+        outcomes.push((size, outcome));
+    }
+
+    assert_eq!(outcomes[0].1, FanOutOutcome::Paid);
+    assert_eq!(outcomes.last().unwrap().1, FanOutOutcome::Reverted);
+    assert!(outcomes.iter().any(|(_, outcome)| matches!(
+        outcome,
+        FanOutOutcome::Inconsistent { .. }
+    )));
+
+    Ok(())
+}
+
+pub async fn pull_payment_fixed() -> color_eyre::Result<()> {
+    let Env {
+        sandbox,
+        malicious_actor: owner,
+        denial_of_service_fixed: contract,
+        ..
+    } = prepare().await?;
+
+    let jar_amount = NearToken::from_millinear(1);
+    let jar_count = 1000;
+
+    owner
+        .call(contract.id(), "storage_deposit")
+        .args_json(json!({}))
+        .deposit(NearToken::from_near(3))
+        .transact()
+        .await?
+        .into_result()?;
+
+    for chunk in (0..jar_count).collect::<Vec<u128>>().chunks(250) {
+        let jars = chunk
+            .iter()
+            .map(|id| (U128(jar_amount.as_yoctonear()), U128(*id)))
+            .collect::<Vec<_>>();
+
+        owner
+            .call(contract.id(), "batch_create_jars")
+            .args_json(json!({"jars": jars}))
+            .deposit(jar_amount.saturating_mul(jars.len() as u128))
+            .max_gas()
+            .transact()
+            .await?
+            .into_result()?;
+    }
+
+    // Claiming is bounded and creates no promises, however many jars there
+    // are it takes a fixed number of calls.
+    let mut claims = 0;
+
+    let withdrawable = loop {
+        let result = owner
+            .call(contract.id(), "claim_jars")
+            .args_json(json!({"limit": 500}))
+            .max_gas()
+            .transact()
+            .await?
+            .into_result()?;
+
+        claims += 1;
+
+        // No promises: the claim is a single receipt on the contract, the
+        // tree leaves out the gas refund.
+        let tree = ReceiptTree::fetch(&sandbox, result.outcome()).await?;
+
+        assert_eq!(tree.root.receiver_id, contract.id().as_str());
+        assert!(tree.root.children.is_empty());
+
+        let withdrawable = result.json::<U128>()?;
+
+        let left = contract
+            .view("get_jars")
+            .args_json(json!({"account_id": owner.id()}))
+            .await?
+            .json::<Vec<MoneyJar>>()?
+            .len();
+
+        if left == 0 {
+            break withdrawable;
+        }
+    };
+
+    assert_eq!(claims, 2);
+    assert_eq!(withdrawable.0, jar_amount.as_yoctonear() * jar_count);
+
+    let balance_before = owner.view_account().await?.balance;
+
+    owner
+        .call(contract.id(), "withdraw")
+        .args_json(json!({}))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+
+    let balance_after = owner.view_account().await?.balance;
+
+    // One transfer for all 1000 jars.
+    assert!(
+        balance_after.saturating_sub(balance_before)
+            > NearToken::from_yoctonear(withdrawable.0)
+                .saturating_sub(NearToken::from_millinear(10))
+    );
+
+    let result = owner
+        .call(contract.id(), "withdraw")
+        .args_json(json!({}))
+        .transact()
+        .await?;
+
+    assert!(format!("{:?}", result.into_result().unwrap_err())
+        .contains("Nothing to withdraw"));
+
     Ok(())
 }
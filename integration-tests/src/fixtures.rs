@@ -0,0 +1,215 @@
+//! Shared setup of the PoCs: a sandbox with accounts and contracts,
+//! deployed and initialized by a builder.
+//!
+//! Adding an account or a contract returns a typed handle, indexing the
+//! built [`Fixture`] with it returns the account or contract. A handle
+//! can't name something the builder doesn't have, so the lookups can't fail.
+//!
+//! ```ignore
+//! let mut builder = Fixture::builder();
+//!
+//! let owner = builder.account();
+//! let notes = builder.contract(wasm(NOTES));
+//! builder.init(notes, "new", move |fixture| {
+//!     json!({"managers": [fixture[owner].id()]})
+//! });
+//!
+//! let fixture = builder.build().await?;
+//!
+//! let owner = &fixture[owner];
+//! let notes = &fixture[notes];
+//! ```
+//!
+//! Accounts are created first, then every contract is deployed, then the
+//! contracts are initialized in the order of the `init` calls. Init
+//! arguments can therefore refer to any account or contract of the fixture,
+//! including contracts added after the one being initialized. Every step
+//! that fails fails the build.
+
+use std::ops::Index;
+
+use near_sdk::{AccountId, NearToken};
+use near_workspaces::{
+    network::Sandbox,
+    result::ExecutionFinalResult,
+    types::{KeyType, SecretKey},
+    Account, Contract, Worker,
+};
+
+type InitArgs = Box<dyn FnOnce(&Fixture) -> serde_json::Value>;
+
+enum AccountSpec {
+    /// Random `dev-` account.
+    Dev,
+    /// Top-level account with the given id.
+    TopLevel(AccountId),
+    /// Sub-account of the sandbox's root account with the given name and
+    /// balance.
+    Funded(String, NearToken),
+}
+
+/// Account added by [`FixtureBuilder::account`] and its variants.
+#[derive(Debug, Clone, Copy)]
+pub struct AccountHandle(usize);
+
+/// Contract added by [`FixtureBuilder::contract`].
+#[derive(Debug, Clone, Copy)]
+pub struct ContractHandle(usize);
+
+/// Sandbox with the accounts and contracts of a [`FixtureBuilder`].
+pub struct Fixture {
+    pub sandbox: Worker<Sandbox>,
+    accounts: Vec<Account>,
+    contracts: Vec<Contract>,
+}
+
+impl Fixture {
+    pub fn builder() -> FixtureBuilder {
+        FixtureBuilder::default()
+    }
+}
+
+impl Index<AccountHandle> for Fixture {
+    type Output = Account;
+
+    fn index(&self, handle: AccountHandle) -> &Account {
+        &self.accounts[handle.0]
+    }
+}
+
+impl Index<ContractHandle> for Fixture {
+    type Output = Contract;
+
+    fn index(&self, handle: ContractHandle) -> &Contract {
+        &self.contracts[handle.0]
+    }
+}
+
+#[derive(Default)]
+pub struct FixtureBuilder {
+    accounts: Vec<AccountSpec>,
+    contracts: Vec<&'static [u8]>,
+    inits: Vec<(ContractHandle, String, InitArgs)>,
+}
+
+impl FixtureBuilder {
+    /// Adds a `dev-` account.
+    pub fn account(&mut self) -> AccountHandle {
+        self.add_account(AccountSpec::Dev)
+    }
+
+    /// Adds a top-level account with a chosen id, e.g. to collide with a
+    /// storage key.
+    pub fn top_level_account(&mut self, account_id: &str) -> AccountHandle {
+        let account_id = account_id
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid account id `{account_id}`"));
+
+        self.add_account(AccountSpec::TopLevel(account_id))
+    }
+
+    /// Adds an account with more balance than a `dev-` account has, named
+    /// `name` under the sandbox's root account.
+    pub fn funded_account(
+        &mut self,
+        name: &str,
+        balance: NearToken,
+    ) -> AccountHandle {
+        self.add_account(AccountSpec::Funded(name.to_string(), balance))
+    }
+
+    /// Deploys `wasm` to a `dev-` account.
+    pub fn contract(&mut self, wasm: &'static [u8]) -> ContractHandle {
+        self.contracts.push(wasm);
+
+        ContractHandle(self.contracts.len() - 1)
+    }
+
+    /// Initializes `contract` by calling `method` with the arguments `args`
+    /// returns.
+    pub fn init(
+        &mut self,
+        contract: ContractHandle,
+        method: &str,
+        args: impl FnOnce(&Fixture) -> serde_json::Value + 'static,
+    ) {
+        self.inits
+            .push((contract, method.to_string(), Box::new(args)));
+    }
+
+    /// Builds the fixture in a new sandbox.
+    pub async fn build(self) -> color_eyre::Result<Fixture> {
+        self.build_in(near_workspaces::sandbox().await?).await
+    }
+
+    /// Builds the fixture in an existing sandbox, e.g. one shared by the
+    /// runs of a profiler scenario.
+    pub async fn build_in(
+        self,
+        sandbox: Worker<Sandbox>,
+    ) -> color_eyre::Result<Fixture> {
+        let mut fixture = Fixture {
+            sandbox,
+            accounts: Vec::new(),
+            contracts: Vec::new(),
+        };
+
+        for spec in self.accounts {
+            let account = match spec {
+                AccountSpec::Dev => {
+                    fixture.sandbox.dev_create_account().await?
+                }
+                AccountSpec::TopLevel(account_id) => fixture
+                    .sandbox
+                    .create_tla(
+                        account_id,
+                        SecretKey::from_random(KeyType::ED25519),
+                    )
+                    .await?
+                    .into_result()?,
+                AccountSpec::Funded(name, balance) => fixture
+                    .sandbox
+                    .root_account()?
+                    .create_subaccount(&name)
+                    .initial_balance(balance)
+                    .transact()
+                    .await?
+                    .into_result()?,
+            };
+
+            fixture.accounts.push(account);
+        }
+
+        for wasm in self.contracts {
+            let contract = fixture.sandbox.dev_deploy(wasm).await?;
+
+            fixture.contracts.push(contract);
+        }
+
+        for (contract, method, args) in self.inits {
+            let args = args(&fixture);
+
+            fixture[contract]
+                .call(&method)
+                .args_json(args)
+                .transact()
+                .await?
+                .into_result()?;
+        }
+
+        Ok(fixture)
+    }
+
+    fn add_account(&mut self, spec: AccountSpec) -> AccountHandle {
+        self.accounts.push(spec);
+
+        AccountHandle(self.accounts.len() - 1)
+    }
+}
+
+/// Asserts that `result` failed with an error containing `message`.
+pub fn assert_fails_with(result: ExecutionFinalResult, message: &str) {
+    let error = format!("{:?}", result.into_result().unwrap_err());
+
+    assert!(error.contains(message), "{error}");
+}
@@ -0,0 +1,402 @@
+//! Oversized arguments to `add_note` and `batch_create_jars`.
+//!
+//! Neither method of the `denial-of-service` contract bounds its input. A
+//! single `batch_create_jars` call deserializes and stores as many jars as
+//! fit in the gas limit, none of which the caller pays storage for. Notes
+//! are bounded, but only by accident: a note is stored as a key of its
+//! `IterableSet`, so one that doesn't fit the storage key limit can't be
+//! stored at all. Notes right below that limit are enough to make an
+//! account's `get_notes` fail for every caller.
+
+use contract_common::limits::{
+    MAX_BODY_LENGTH, MAX_JARS_PER_BATCH, MAX_NOTES_PER_PAGE, MAX_TITLE_LENGTH,
+};
+use near_sdk::{env, json_types::U128, AccountId, Gas, NearToken};
+use near_workspaces::{
+    network::Sandbox, operations::Function, types::AccountDetails, Account,
+    Contract, Worker,
+};
+use serde_json::json;
+
+use crate::{
+    fixtures::Fixture,
+    limits::MAX_LENGTH_STORAGE_KEY,
+    profiler::{profiles_dir, Limit, Metric, Profiler, Sample},
+    report,
+    wasm::wasm,
+};
+
+const DENIAL_OF_SERVICE: &str = "denial-of-service";
+const DENIAL_OF_SERVICE_FIXED: &str = "denial-of-service-fixed";
+
+const NOTES_PER_BATCH: usize = 20;
+const TITLE: &str = "title";
+
+/// Deploys `package`, initialized with `args`, next to an account named
+/// `author` with enough balance to pay for the storage of a few thousand
+/// notes.
+pub async fn deploy_with_author(
+    sandbox: &Worker<Sandbox>,
+    package: &str,
+    args: serde_json::Value,
+    author: &str,
+) -> color_eyre::Result<(Contract, Account)> {
+    let mut builder = Fixture::builder();
+
+    let author = builder.funded_account(author, NearToken::from_near(1_000));
+    let contract = builder.contract(wasm(package));
+
+    builder.init(contract, "new", move |_| args);
+
+    let fixture = builder.build_in(sandbox.clone()).await?;
+
+    Ok((fixture[contract].clone(), fixture[author].clone()))
+}
+
+/// Adds `count` notes with the given body, `NOTES_PER_BATCH` a transaction.
+pub async fn add_notes(
+    author: &Account,
+    contract: &Contract,
+    count: usize,
+    body: &str,
+    deposit: NearToken,
+) -> color_eyre::Result<()> {
+    for batch in (0..count).collect::<Vec<_>>().chunks(NOTES_PER_BATCH) {
+        let mut transaction = author.batch(contract.id());
+
+        for id in batch {
+            transaction = transaction.call(
+                Function::new("add_note")
+                    .args_json(json!({
+                        "title": format!("{TITLE}{id}"),
+                        "body": body,
+                    }))
+                    .deposit(deposit)
+                    .gas(Gas::from_tgas(14)),
+            );
+        }
+
+        transaction.transact().await?.into_result()?;
+    }
+
+    Ok(())
+}
+
+/// Longest body of a note titled `title` that the `denial-of-service`
+/// contract can store for `account_id`. The note is a key of the account's
+/// set: the `StorageKey::Notes(account_id)` prefix, the `m` of the set's
+/// index, then the borsh encoded note.
+fn max_storable_body(account_id: &AccountId, title: &str) -> usize {
+    let prefix = 1 + 4 + account_id.len() + 1;
+    let note = (1 + 8) + (4 + title.len()) + 4;
+
+    MAX_LENGTH_STORAGE_KEY as usize - prefix - note
+}
+
+fn free_balance(account: &AccountDetails) -> u128 {
+    account.balance.as_yoctonear()
+        - account.storage_usage as u128
+            * env::storage_byte_cost().as_yoctonear()
+}
+
+pub async fn huge_jar_batch_dos() -> color_eyre::Result<()> {
+    let sandbox = near_workspaces::sandbox().await?;
+
+    let profiler = Profiler::new("batch_create_jars", |size| {
+        let sandbox = sandbox.clone();
+
+        async move {
+            let (contract, attacker) = deploy_with_author(
+                &sandbox,
+                DENIAL_OF_SERVICE,
+                json!({"managers": []}),
+                &format!("attacker{size}"),
+            )
+            .await?;
+
+            let jars = (0..size as u128)
+                .map(|id| (U128(1), U128(id)))
+                .collect::<Vec<_>>();
+
+            let result = attacker
+                .call(contract.id(), "batch_create_jars")
+                .args_json(json!({"jars": jars}))
+                .max_gas()
+                .transact()
+                .await?;
+
+            let storage_usage = contract.view_account().await?.storage_usage;
+
+            Ok(Sample::new(size, result, storage_usage))
+        }
+    });
+
+    let profile = profiler
+        .run(&[500, 1000, 2000, 4000, 8000, 16000], 1)
+        .await?;
+    profile.write(profiles_dir())?;
+
+    let breaking_point = profile.breaking_point.as_ref().unwrap();
+    let fit = profile.fit(Metric::GasBurnt).unwrap();
+
+    // The whole batch is deserialized and stored in one call, the only
+    // bound on its size is the gas limit.
+    assert_eq!(breaking_point.limit, Some(Limit::Gas));
+    assert_eq!(
+        breaking_point.first_failure.size,
+        breaking_point.last_success.size + 1
+    );
+    assert!(fit.r_squared > 0.99);
+
+    Ok(())
+}
+
+pub async fn huge_jar_batch_locks_balance() -> color_eyre::Result<()> {
+    let sandbox = near_workspaces::sandbox().await?;
+
+    let (contract, attacker) = deploy_with_author(
+        &sandbox,
+        DENIAL_OF_SERVICE,
+        json!({"managers": []}),
+        "attacker",
+    )
+    .await?;
+
+    let jars = (0..800u128)
+        .map(|id| (U128(1), U128(id)))
+        .collect::<Vec<_>>();
+
+    let free_balance_before = free_balance(&contract.view_account().await?);
+
+    report::state_before("contract free balance", U128(free_balance_before));
+
+    let result = attacker
+        .call(contract.id(), "batch_create_jars")
+        .args_json(json!({"jars": jars}))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?;
+
+    let free_balance_after = free_balance(&contract.view_account().await?);
+
+    report::gas_burnt(result.total_gas_burnt);
+    report::state_after("contract free balance", U128(free_balance_after));
+
+    let tokens_burnt = result
+        .outcomes()
+        .iter()
+        .map(|outcome| outcome.tokens_burnt.as_yoctonear())
+        .sum::<u128>();
+
+    assert!(free_balance_after < free_balance_before);
+
+    let locked = free_balance_before - free_balance_after;
+
+    // Gas rewards don't come close to the storage staked for the jars, the
+    // contract pays for the attacker's data with its own balance.
+    assert!(locked > tokens_burnt);
+
+    Ok(())
+}
+
+pub async fn huge_note_dos() -> color_eyre::Result<()> {
+    let sandbox = near_workspaces::sandbox().await?;
+
+    let (contract, attacker) = deploy_with_author(
+        &sandbox,
+        DENIAL_OF_SERVICE,
+        json!({"managers": []}),
+        "attacker",
+    )
+    .await?;
+
+    let profiler = Profiler::new("add_note", |size| {
+        let contract = contract.clone();
+        let attacker = attacker.clone();
+
+        async move {
+            let result = attacker
+                .call(contract.id(), "add_note")
+                .args_json(json!({"title": TITLE, "body": "b".repeat(size)}))
+                .deposit(NearToken::from_near(1))
+                .max_gas()
+                .transact()
+                .await?;
+
+            let storage_usage = contract.view_account().await?.storage_usage;
+
+            Ok(Sample::new(size, result, storage_usage))
+        }
+    });
+
+    let profile = profiler.run(&[256, 512, 1024, 2048, 4096], 1).await?;
+    profile.write(profiles_dir())?;
+
+    let breaking_point = profile.breaking_point.as_ref().unwrap();
+    let error = breaking_point.first_failure.error.as_deref().unwrap();
+
+    // A note is its own storage key, the body can only grow until the key
+    // hits the limit, long before deserializing it costs any real gas.
+    assert_eq!(breaking_point.limit, Some(Limit::Other));
+    assert!(error.contains("KeyLengthExceeded"), "{error}");
+    assert_eq!(
+        breaking_point.last_success.size,
+        max_storable_body(attacker.id(), TITLE)
+    );
+    assert_eq!(
+        breaking_point.first_failure.size,
+        breaking_point.last_success.size + 1
+    );
+
+    Ok(())
+}
+
+pub async fn get_notes_view_dos() -> color_eyre::Result<()> {
+    let sandbox = near_workspaces::sandbox().await?;
+
+    let (contract, attacker) = deploy_with_author(
+        &sandbox,
+        DENIAL_OF_SERVICE,
+        json!({"managers": []}),
+        "attacker",
+    )
+    .await?;
+
+    // The longest body that still fits next to the longest title
+    // `add_notes` uses.
+    let longest_title = format!("{TITLE}{}", NOTES_PER_BATCH - 1);
+    let body = "b".repeat(max_storable_body(attacker.id(), &longest_title));
+
+    let get_notes = |limit: Option<usize>| {
+        contract
+            .view("get_notes")
+            .args_json(json!({"account_id": attacker.id(), "limit": limit}))
+    };
+
+    let mut notes = 0;
+
+    loop {
+        add_notes(
+            &attacker,
+            &contract,
+            NOTES_PER_BATCH,
+            &body,
+            NearToken::from_near(1),
+        )
+        .await?;
+
+        notes += NOTES_PER_BATCH;
+
+        if get_notes(None).await.is_err() {
+            break;
+        }
+    }
+
+    // Bisect the page size that still fits between the last note count
+    // that could be viewed and the first that couldn't.
+    let (mut last_success, mut first_failure) =
+        (notes - NOTES_PER_BATCH, notes);
+
+    while first_failure - last_success > 1 {
+        let limit = last_success + (first_failure - last_success) / 2;
+
+        if get_notes(Some(limit)).await.is_ok() {
+            last_success = limit;
+        } else {
+            first_failure = limit;
+        }
+    }
+
+    assert!(get_notes(Some(last_success)).await.is_ok());
+    assert!(get_notes(Some(last_success + 1)).await.is_err());
+
+    // The owner can't do anything about it either, nobody can list the
+    // notes in one call anymore.
+    assert!(get_notes(None).await.is_err());
+
+    Ok(())
+}
+
+pub async fn large_inputs_fixed() -> color_eyre::Result<()> {
+    let sandbox = near_workspaces::sandbox().await?;
+
+    let (contract, author) = deploy_with_author(
+        &sandbox,
+        DENIAL_OF_SERVICE_FIXED,
+        json!({}),
+        "author",
+    )
+    .await?;
+
+    author
+        .call(contract.id(), "storage_deposit")
+        .args_json(json!({}))
+        .deposit(NearToken::from_near(10))
+        .transact()
+        .await?
+        .into_result()?;
+
+    let add_note = |title: String, body: String| {
+        author
+            .call(contract.id(), "add_note")
+            .args_json(json!({"title": title, "body": body}))
+            .transact()
+    };
+
+    add_note("t".repeat(MAX_TITLE_LENGTH), "b".repeat(MAX_BODY_LENGTH))
+        .await?
+        .into_result()?;
+
+    let result =
+        add_note("t".repeat(MAX_TITLE_LENGTH + 1), "body".into()).await?;
+
+    assert!(format!("{:?}", result.into_result().unwrap_err())
+        .contains("Note title is too long"));
+
+    let result =
+        add_note("title".into(), "b".repeat(MAX_BODY_LENGTH + 1)).await?;
+
+    assert!(format!("{:?}", result.into_result().unwrap_err())
+        .contains("Note body is too long"));
+
+    let create_jars = |count: usize| {
+        let jars = (0..count as u128)
+            .map(|id| (U128(1), U128(id)))
+            .collect::<Vec<_>>();
+
+        author
+            .call(contract.id(), "batch_create_jars")
+            .args_json(json!({"jars": jars}))
+            .deposit(NearToken::from_yoctonear(count as u128))
+            .max_gas()
+            .transact()
+    };
+
+    let result = create_jars(MAX_JARS_PER_BATCH + 1).await?;
+
+    assert!(format!("{:?}", result.into_result().unwrap_err())
+        .contains("Too many jars in a batch"));
+
+    create_jars(MAX_JARS_PER_BATCH).await?.into_result()?;
+
+    // A full page of the largest notes is still cheap to view.
+    add_notes(
+        &author,
+        &contract,
+        MAX_NOTES_PER_PAGE as usize,
+        &"b".repeat(MAX_BODY_LENGTH),
+        NearToken::from_yoctonear(0),
+    )
+    .await?;
+
+    let notes = contract
+        .view("get_notes")
+        .args_json(json!({"account_id": author.id()}))
+        .await?
+        .json::<Vec<serde_json::Value>>()?;
+
+    assert_eq!(notes.len(), MAX_NOTES_PER_PAGE as usize);
+
+    Ok(())
+}
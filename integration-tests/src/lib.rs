@@ -1,4 +1,18 @@
 mod access_control;
 mod denial_of_service;
+mod fixtures;
+mod large_inputs;
+mod limits;
+mod moderation;
+mod note_removal;
+mod notes;
+mod payout_queue;
+mod prefix_aliasing;
+mod profiler;
 mod race_condition;
+mod receipt_tree;
+pub mod report;
+pub mod scenarios;
+mod state_dump;
 mod storage_collisions;
+mod wasm;
@@ -0,0 +1,230 @@
+//! Runtime limits the PoCs rely on, in one place.
+//!
+//! The tests below call the `probe_*` methods of the `denial-of-service`
+//! contract exactly at each limit and one past it. If a protocol upgrade
+//! moves a limit, the sandbox stops agreeing with these constants and the
+//! tests fail, instead of the PoCs built on them silently passing.
+
+use near_workspaces::{result::ExecutionFinalResult, Contract};
+use serde_json::json;
+
+use crate::{fixtures::Fixture, wasm::wasm};
+
+/// Gas a single function call can burn.
+pub const MAX_GAS_BURNT: u64 = 300_000_000_000_000;
+/// Logs a single function call can emit.
+pub const MAX_NUMBER_LOGS: u64 = 100;
+/// Bytes all logs of a single function call can add up to.
+pub const MAX_TOTAL_LOG_LENGTH: u64 = 16_384;
+/// Bytes a function call can return.
+pub const MAX_LENGTH_RETURNED_DATA: u64 = 4 * 1024 * 1024;
+/// Bytes of arguments a function call action can carry.
+pub const MAX_ARGUMENTS_LENGTH: u64 = 4 * 1024 * 1024;
+/// Bytes a receipt created by a function call can take, arguments included.
+pub const MAX_RECEIPT_SIZE: u64 = 4 * 1024 * 1024;
+/// Bytes a storage key can have.
+pub const MAX_LENGTH_STORAGE_KEY: u64 = 2048;
+/// Promises a single function call can create.
+pub const MAX_PROMISES_PER_FUNCTION_CALL: u64 = 1024;
+
+const DENIAL_OF_SERVICE: &str = "denial-of-service";
+
+/// Upper bound of what an action receipt takes besides the arguments: the
+/// account ids, the signer key and the other action fields.
+const RECEIPT_OVERHEAD: u64 = 1024;
+
+async fn prepare() -> color_eyre::Result<Contract> {
+    let mut builder = Fixture::builder();
+
+    let contract = builder.contract(wasm(DENIAL_OF_SERVICE));
+
+    builder.init(
+        contract,
+        "new",
+        move |fixture| json!({"managers": [fixture[contract].id()]}),
+    );
+
+    Ok(builder.build().await?[contract].clone())
+}
+
+async fn probe(
+    contract: &Contract,
+    method: &str,
+    args: serde_json::Value,
+) -> color_eyre::Result<ExecutionFinalResult> {
+    Ok(contract
+        .call(method)
+        .args_json(args)
+        .max_gas()
+        .transact()
+        .await?)
+}
+
+/// Asserts that `result` failed on `error` with the given limit.
+fn assert_exceeded(result: ExecutionFinalResult, error: &str, limit: u64) {
+    let failure = format!("{:?}", result.into_result().unwrap_err());
+
+    assert!(failure.contains(error), "{failure}");
+    assert!(failure.contains(&format!("limit: {limit}")), "{failure}");
+}
+
+#[tokio::test]
+async fn number_of_logs_limit() -> color_eyre::Result<()> {
+    let contract = prepare().await?;
+
+    let result =
+        probe(&contract, "probe_logs", json!({"count": MAX_NUMBER_LOGS}))
+            .await?
+            .into_result()?;
+
+    assert_eq!(result.logs().len() as u64, MAX_NUMBER_LOGS);
+
+    let result = probe(
+        &contract,
+        "probe_logs",
+        json!({"count": MAX_NUMBER_LOGS + 1}),
+    )
+    .await?;
+
+    assert_exceeded(result, "NumberOfLogsExceeded", MAX_NUMBER_LOGS);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn total_log_length_limit() -> color_eyre::Result<()> {
+    let contract = prepare().await?;
+
+    let result = probe(
+        &contract,
+        "probe_log_bytes",
+        json!({"length": MAX_TOTAL_LOG_LENGTH}),
+    )
+    .await?
+    .into_result()?;
+
+    assert_eq!(result.logs()[0].len() as u64, MAX_TOTAL_LOG_LENGTH);
+
+    let result = probe(
+        &contract,
+        "probe_log_bytes",
+        json!({"length": MAX_TOTAL_LOG_LENGTH + 1}),
+    )
+    .await?;
+
+    assert_exceeded(result, "TotalLogLengthExceeded", MAX_TOTAL_LOG_LENGTH);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn returned_data_limit() -> color_eyre::Result<()> {
+    let contract = prepare().await?;
+
+    let result = probe(
+        &contract,
+        "probe_return_data",
+        json!({"length": MAX_LENGTH_RETURNED_DATA}),
+    )
+    .await?
+    .into_result()?;
+
+    assert_eq!(result.raw_bytes()?.len() as u64, MAX_LENGTH_RETURNED_DATA);
+
+    let result = probe(
+        &contract,
+        "probe_return_data",
+        json!({"length": MAX_LENGTH_RETURNED_DATA + 1}),
+    )
+    .await?;
+
+    assert_exceeded(
+        result,
+        "ReturnedValueLengthExceeded",
+        MAX_LENGTH_RETURNED_DATA,
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn receipt_size_limit() -> color_eyre::Result<()> {
+    let contract = prepare().await?;
+
+    // The arguments are capped at the size of the whole receipt that carries
+    // them, so the receipt limit is hit first and
+    // `FunctionCallArgumentsLengthExceeded` can't be reached by a contract.
+    assert_eq!(MAX_ARGUMENTS_LENGTH, MAX_RECEIPT_SIZE);
+
+    // Succeeds including the `probe_sink` call that receives the arguments.
+    probe(
+        &contract,
+        "probe_arguments",
+        json!({"length": MAX_RECEIPT_SIZE - RECEIPT_OVERHEAD}),
+    )
+    .await?
+    .into_result()?;
+
+    let result = probe(
+        &contract,
+        "probe_arguments",
+        json!({"length": MAX_ARGUMENTS_LENGTH}),
+    )
+    .await?;
+
+    assert_exceeded(result, "ReceiptSizeExceeded", MAX_RECEIPT_SIZE);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn storage_key_length_limit() -> color_eyre::Result<()> {
+    let contract = prepare().await?;
+
+    probe(
+        &contract,
+        "probe_storage_key",
+        json!({"length": MAX_LENGTH_STORAGE_KEY}),
+    )
+    .await?
+    .into_result()?;
+
+    let result = probe(
+        &contract,
+        "probe_storage_key",
+        json!({"length": MAX_LENGTH_STORAGE_KEY + 1}),
+    )
+    .await?;
+
+    assert_exceeded(result, "KeyLengthExceeded", MAX_LENGTH_STORAGE_KEY);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn promises_per_function_call_limit() -> color_eyre::Result<()> {
+    let contract = prepare().await?;
+
+    probe(
+        &contract,
+        "probe_promises",
+        json!({"count": MAX_PROMISES_PER_FUNCTION_CALL}),
+    )
+    .await?
+    .into_result()?;
+
+    let result = probe(
+        &contract,
+        "probe_promises",
+        json!({"count": MAX_PROMISES_PER_FUNCTION_CALL + 1}),
+    )
+    .await?;
+
+    assert_exceeded(
+        result,
+        "NumberPromisesExceeded",
+        MAX_PROMISES_PER_FUNCTION_CALL,
+    );
+
+    Ok(())
+}
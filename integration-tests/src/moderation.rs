@@ -0,0 +1,262 @@
+use near_sdk::{json_types::U64, NearToken};
+use near_workspaces::{Account, Contract};
+use serde_json::json;
+
+use crate::{
+    fixtures::{assert_fails_with, Fixture},
+    wasm::wasm,
+};
+
+const STORAGE_COLLISIONS_CONTRACT: &str = "storage-key-collisions";
+
+struct Env {
+    manager: Account,
+    user: Account,
+    attacker: Account,
+    notes_contract: Contract,
+}
+
+async fn prepare() -> color_eyre::Result<Env> {
+    let mut builder = Fixture::builder();
+
+    let manager = builder.account();
+    let user = builder.account();
+    let attacker = builder.account();
+    let notes_contract = builder.contract(wasm(STORAGE_COLLISIONS_CONTRACT));
+
+    builder.init(
+        notes_contract,
+        "new",
+        move |fixture| json!({"managers": [fixture[manager].id()]}),
+    );
+
+    let fixture = builder.build().await?;
+
+    let manager = fixture[manager].clone();
+    let user = fixture[user].clone();
+    let attacker = fixture[attacker].clone();
+    let notes_contract = fixture[notes_contract].clone();
+
+    user.call(notes_contract.id(), "add_note")
+        .args_json(json!({"title": "title", "body": "body"}))
+        .deposit(NearToken::from_millinear(10))
+        .transact()
+        .await?
+        .into_result()?;
+
+    Ok(Env {
+        manager,
+        user,
+        attacker,
+        notes_contract,
+    })
+}
+
+async fn note_count(
+    notes_contract: &Contract,
+    account: &Account,
+) -> color_eyre::Result<usize> {
+    Ok(notes_contract
+        .view("get_notes")
+        .args_json(json!({"account_id": account.id()}))
+        .await?
+        .json::<Vec<serde_json::Value>>()?
+        .len())
+}
+
+pub async fn moderator_delete_note() -> color_eyre::Result<()> {
+    let Env {
+        manager,
+        user,
+        attacker,
+        notes_contract,
+    } = prepare().await?;
+
+    let args = json!({"account_id": user.id(), "id": U64(0)});
+
+    let result = attacker
+        .call(notes_contract.id(), "moderator_delete_note")
+        .args_json(&args)
+        .transact()
+        .await?;
+
+    assert_fails_with(result, "Only managers can moderate");
+    assert_eq!(note_count(&notes_contract, &user).await?, 1);
+
+    manager
+        .call(notes_contract.id(), "moderator_delete_note")
+        .args_json(&args)
+        .transact()
+        .await?
+        .into_result()?;
+
+    assert_eq!(note_count(&notes_contract, &user).await?, 0);
+
+    Ok(())
+}
+
+pub async fn moderator_delete_note_unchecked() -> color_eyre::Result<()> {
+    let Env {
+        user,
+        attacker,
+        notes_contract,
+        ..
+    } = prepare().await?;
+
+    // No manager check: any account can wipe another user's notes.
+    attacker
+        .call(notes_contract.id(), "moderator_delete_note_unchecked")
+        .args_json(json!({"account_id": user.id(), "id": U64(0)}))
+        .transact()
+        .await?
+        .into_result()?;
+
+    assert_eq!(note_count(&notes_contract, &user).await?, 0);
+
+    Ok(())
+}
+
+pub async fn freeze_user() -> color_eyre::Result<()> {
+    let Env {
+        manager,
+        user,
+        attacker,
+        notes_contract,
+    } = prepare().await?;
+
+    let result = attacker
+        .call(notes_contract.id(), "freeze_user")
+        .args_json(json!({"account_id": user.id()}))
+        .transact()
+        .await?;
+
+    assert_fails_with(result, "Only managers can moderate");
+
+    manager
+        .call(notes_contract.id(), "freeze_user")
+        .args_json(json!({"account_id": user.id()}))
+        .transact()
+        .await?
+        .into_result()?;
+
+    let result = user
+        .call(notes_contract.id(), "add_note")
+        .args_json(json!({"title": "title2", "body": "body2"}))
+        .deposit(NearToken::from_millinear(10))
+        .transact()
+        .await?;
+
+    assert_fails_with(result, "User is frozen");
+
+    manager
+        .call(notes_contract.id(), "unfreeze_user")
+        .args_json(json!({"account_id": user.id()}))
+        .transact()
+        .await?
+        .into_result()?;
+
+    user.call(notes_contract.id(), "add_note")
+        .args_json(json!({"title": "title2", "body": "body2"}))
+        .deposit(NearToken::from_millinear(10))
+        .transact()
+        .await?
+        .into_result()?;
+
+    assert_eq!(note_count(&notes_contract, &user).await?, 2);
+
+    Ok(())
+}
+
+pub async fn freeze_user_wrong_account() -> color_eyre::Result<()> {
+    let Env {
+        manager,
+        user,
+        attacker,
+        notes_contract,
+    } = prepare().await?;
+
+    // The check runs against the target: the attacker freezes the manager...
+    attacker
+        .call(notes_contract.id(), "freeze_user_wrong_account")
+        .args_json(json!({"account_id": manager.id()}))
+        .transact()
+        .await?
+        .into_result()?;
+
+    assert!(notes_contract
+        .view("is_frozen")
+        .args_json(json!({"account_id": manager.id()}))
+        .await?
+        .json::<bool>()?);
+
+    // ...while the manager can't freeze a regular user.
+    let result = manager
+        .call(notes_contract.id(), "freeze_user_wrong_account")
+        .args_json(json!({"account_id": user.id()}))
+        .transact()
+        .await?;
+
+    assert_fails_with(result, "Only managers can moderate");
+
+    Ok(())
+}
+
+pub async fn manage_managers() -> color_eyre::Result<()> {
+    let Env {
+        manager,
+        user,
+        attacker,
+        notes_contract,
+    } = prepare().await?;
+
+    let result = attacker
+        .call(notes_contract.id(), "add_manager")
+        .args_json(json!({"account_id": attacker.id()}))
+        .transact()
+        .await?;
+
+    assert_fails_with(result, "Only managers can moderate");
+
+    let result = manager
+        .call(notes_contract.id(), "remove_manager")
+        .args_json(json!({"account_id": manager.id()}))
+        .transact()
+        .await?;
+
+    assert_fails_with(result, "Managers can't remove themselves");
+
+    manager
+        .call(notes_contract.id(), "add_manager")
+        .args_json(json!({"account_id": user.id()}))
+        .transact()
+        .await?
+        .into_result()?;
+
+    user.call(notes_contract.id(), "remove_manager")
+        .args_json(json!({"account_id": manager.id()}))
+        .transact()
+        .await?
+        .into_result()?;
+
+    for (account, is_manager) in [(&manager, false), (&user, true)] {
+        assert_eq!(
+            notes_contract
+                .view("is_manager")
+                .args_json(json!({"account_id": account.id()}))
+                .await?
+                .json::<bool>()?,
+            is_manager
+        );
+    }
+
+    // A removed manager loses its rights
+    let result = manager
+        .call(notes_contract.id(), "freeze_user")
+        .args_json(json!({"account_id": attacker.id()}))
+        .transact()
+        .await?;
+
+    assert_fails_with(result, "Only managers can moderate");
+
+    Ok(())
+}
@@ -0,0 +1,195 @@
+//! Clearing a note set in one call.
+//!
+//! `remove_all_notes_correct` of the `denial-of-service` contract removes
+//! the caller's whole `IterableSet` with `clear`, which reads and removes
+//! every note. Its gas grows with the number of notes, past the gas limit
+//! the call always fails and reverts, and the notes can never be deleted.
+
+use contract_common::limits::{MAX_BODY_LENGTH, MAX_NOTES_PER_REMOVAL};
+use near_sdk::NearToken;
+use near_workspaces::{Account, Contract};
+use serde_json::json;
+
+use crate::{
+    denial_of_service::StorageBalance,
+    large_inputs::{add_notes, deploy_with_author},
+    profiler::{profiles_dir, Limit, Profiler, Sample},
+};
+
+const DENIAL_OF_SERVICE: &str = "denial-of-service";
+const DENIAL_OF_SERVICE_FIXED: &str = "denial-of-service-fixed";
+
+/// Body of the notes, large notes make every removal more expensive.
+const BODY_LENGTH: usize = 1024;
+
+/// Notes of the fixed contract's author, more than any single call could
+/// remove.
+const NOTES: usize = 1000;
+
+async fn note_exists(
+    contract: &Contract,
+    author: &Account,
+    index: usize,
+) -> color_eyre::Result<bool> {
+    let notes = contract
+        .view("get_notes")
+        .args_json(json!({
+            "account_id": author.id(),
+            "from_index": index,
+            "limit": 1,
+        }))
+        .await?
+        .json::<Vec<serde_json::Value>>()?;
+
+    Ok(notes.len() == 1)
+}
+
+pub async fn clear_gas_trap() -> color_eyre::Result<()> {
+    let sandbox = near_workspaces::sandbox().await?;
+    let body = "b".repeat(BODY_LENGTH);
+
+    // Removal succeeds for a few notes, fit its gas to find where it stops.
+    let profiler = Profiler::new("remove_all_notes_correct", |size| {
+        let sandbox = sandbox.clone();
+        let body = body.clone();
+
+        async move {
+            let (contract, author) = deploy_with_author(
+                &sandbox,
+                DENIAL_OF_SERVICE,
+                json!({"managers": []}),
+                &format!("author{size}"),
+            )
+            .await?;
+
+            add_notes(&author, &contract, size, &body, NearToken::from_near(1))
+                .await?;
+
+            let result = author
+                .call(contract.id(), "remove_all_notes_correct")
+                .max_gas()
+                .transact()
+                .await?;
+
+            let storage_usage = contract.view_account().await?.storage_usage;
+
+            Ok(Sample::new(size, result, storage_usage))
+        }
+    });
+
+    let profile = profiler.run(&[20, 40, 80, 160], 1).await?;
+    profile.write(profiles_dir())?;
+
+    let predicted = profile.prediction(Limit::Gas).unwrap();
+
+    assert!(profile.samples.iter().all(Sample::is_success));
+
+    // Grow a note set a quarter past the predicted limit.
+    let size = (predicted * 1.25).ceil() as usize;
+
+    let (contract, author) = deploy_with_author(
+        &sandbox,
+        DENIAL_OF_SERVICE,
+        json!({"managers": []}),
+        "author",
+    )
+    .await?;
+
+    add_notes(&author, &contract, size, &body, NearToken::from_near(1)).await?;
+
+    // However often the author retries, the removal runs out of gas and
+    // reverts, the notes stay.
+    for _ in 0..2 {
+        let result = author
+            .call(contract.id(), "remove_all_notes_correct")
+            .max_gas()
+            .transact()
+            .await?;
+
+        let sample = Sample::new(size, result, 0);
+
+        assert_eq!(sample.limit, Some(Limit::Gas), "{:?}", sample.error);
+
+        assert!(note_exists(&contract, &author, 0).await?);
+        assert!(note_exists(&contract, &author, size - 1).await?);
+    }
+
+    Ok(())
+}
+
+pub async fn clear_gas_trap_fixed() -> color_eyre::Result<()> {
+    let sandbox = near_workspaces::sandbox().await?;
+
+    let (contract, author) = deploy_with_author(
+        &sandbox,
+        DENIAL_OF_SERVICE_FIXED,
+        json!({}),
+        "author",
+    )
+    .await?;
+
+    let registered = author
+        .call(contract.id(), "storage_deposit")
+        .args_json(json!({}))
+        .deposit(NearToken::from_near(50))
+        .transact()
+        .await?
+        .into_result()?
+        .json::<StorageBalance>()?;
+
+    add_notes(
+        &author,
+        &contract,
+        NOTES,
+        &"b".repeat(MAX_BODY_LENGTH),
+        NearToken::from_yoctonear(0),
+    )
+    .await?;
+
+    // Removing everything at once is refused up front instead of running
+    // out of gas.
+    let result = author
+        .call(contract.id(), "remove_all_notes")
+        .max_gas()
+        .transact()
+        .await?;
+
+    assert!(format!("{:?}", result.into_result().unwrap_err())
+        .contains("Too many notes to remove at once"));
+
+    let mut calls = 0;
+
+    loop {
+        let result = author
+            .call(contract.id(), "remove_notes_batch")
+            .args_json(json!({"limit": MAX_NOTES_PER_REMOVAL}))
+            .max_gas()
+            .transact()
+            .await?;
+
+        calls += 1;
+
+        let left = result.into_result()?.json::<u32>()?;
+
+        if left == 0 {
+            break;
+        }
+
+        assert!(note_exists(&contract, &author, left as usize - 1).await?);
+        assert!(!note_exists(&contract, &author, left as usize).await?);
+    }
+
+    assert_eq!(calls, NOTES.div_ceil(MAX_NOTES_PER_REMOVAL as usize));
+    assert!(!note_exists(&contract, &author, 0).await?);
+
+    // Every byte of the notes is released back to the storage balance.
+    let balance = contract
+        .view("storage_balance_of")
+        .args_json(json!({"account_id": author.id()}))
+        .await?
+        .json::<StorageBalance>()?;
+
+    assert_eq!(balance.available, registered.available);
+
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use near_sdk::{json_types::U64, near, AccountId, Gas, NearToken};
+use near_workspaces::{operations::Function, Account, Contract};
+use serde_json::json;
+
+use crate::{fixtures::Fixture, wasm::wasm};
+
+const STORAGE_COLLISIONS_CONTRACT: &str = "storage-key-collisions";
+
+const NOTES: u64 = 1_000;
+const NOTES_PER_BATCH: u64 = 20;
+
+#[near(serializers = [json])]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostedNote {
+    pub id: Option<U64>,
+    pub title: String,
+    pub body: String,
+}
+
+struct Env {
+    author: Account,
+    notes_contract: Contract,
+}
+
+async fn prepare() -> color_eyre::Result<Env> {
+    let mut builder = Fixture::builder();
+
+    let author = builder.account();
+    let notes_contract = builder.contract(wasm(STORAGE_COLLISIONS_CONTRACT));
+
+    builder.init(
+        notes_contract,
+        "new",
+        |_| json!({"managers": Vec::<AccountId>::new()}),
+    );
+
+    let fixture = builder.build().await?;
+
+    Ok(Env {
+        author: fixture[author].clone(),
+        notes_contract: fixture[notes_contract].clone(),
+    })
+}
+
+async fn add_notes(
+    author: &Account,
+    notes_contract: &Contract,
+    count: u64,
+) -> color_eyre::Result<()> {
+    for batch in 0..count / NOTES_PER_BATCH {
+        let mut transaction = author.batch(notes_contract.id());
+
+        for i in 0..NOTES_PER_BATCH {
+            let id = batch * NOTES_PER_BATCH + i;
+
+            transaction = transaction.call(
+                Function::new("add_note")
+                    .args_json(
+                        json!({"title": format!("title{id}"), "body": "body"}),
+                    )
+                    .deposit(NearToken::from_millinear(10))
+                    .gas(Gas::from_tgas(14)),
+            );
+        }
+
+        transaction.transact().await?.into_result()?;
+    }
+
+    Ok(())
+}
+
+pub async fn note_lookup_gas() -> color_eyre::Result<()> {
+    let Env {
+        author,
+        notes_contract,
+    } = prepare().await?;
+
+    add_notes(&author, &notes_contract, NOTES).await?;
+
+    let last_id = U64(NOTES - 1);
+
+    // Views don't report gas, call the same methods as transactions.
+    let scan = author
+        .call(notes_contract.id(), "get_note")
+        .args_json(json!({"account_id": author.id(), "id": last_id}))
+        .max_gas()
+        .transact()
+        .await?;
+
+    let indexed = author
+        .call(notes_contract.id(), "get_note_by_id")
+        .args_json(json!({"id": last_id}))
+        .transact()
+        .await?;
+
+    let scan_gas = scan.total_gas_burnt;
+    let indexed_gas = indexed.total_gas_burnt;
+
+    assert_eq!(
+        scan.into_result()?.json::<PostedNote>()?,
+        indexed.into_result()?.json::<PostedNote>()?
+    );
+
+    // The scan deserializes every note before the last one, the index reads
+    // a single entry.
+    assert!(scan_gas > indexed_gas.saturating_mul(5));
+
+    Ok(())
+}
+
+pub async fn edit_and_delete_note() -> color_eyre::Result<()> {
+    let Env {
+        author,
+        notes_contract,
+    } = prepare().await?;
+
+    add_notes(&author, &notes_contract, NOTES_PER_BATCH).await?;
+
+    let storage_usage = notes_contract.view_account().await?.storage_usage;
+
+    // Growing a note without a deposit can't pay for the new bytes.
+    let result = author
+        .call(notes_contract.id(), "edit_note")
+        .args_json(json!({"id": U64(3), "title": "title3", "body": "a much longer body"}))
+        .transact()
+        .await?;
+
+    assert!(format!("{:?}", result.into_result().unwrap_err())
+        .contains("not enough attached deposit"));
+
+    author
+        .call(notes_contract.id(), "edit_note")
+        .args_json(json!({"id": U64(3), "title": "title3", "body": "a much longer body"}))
+        .deposit(NearToken::from_millinear(10))
+        .transact()
+        .await?
+        .into_result()?;
+
+    let note = notes_contract
+        .view("get_note_by_id")
+        .args_json(json!({"id": U64(3)}))
+        .await?
+        .json::<PostedNote>()?;
+
+    assert_eq!(note.body, "a much longer body");
+    assert!(notes_contract.view_account().await?.storage_usage > storage_usage);
+
+    let storage_usage = notes_contract.view_account().await?.storage_usage;
+
+    author
+        .call(notes_contract.id(), "delete_note")
+        .args_json(json!({"id": U64(3)}))
+        .transact()
+        .await?
+        .into_result()?;
+
+    assert!(notes_contract.view_account().await?.storage_usage < storage_usage);
+
+    let notes = notes_contract
+        .view("get_notes")
+        .args_json(json!({"account_id": author.id()}))
+        .await?
+        .json::<Vec<PostedNote>>()?;
+
+    assert_eq!(notes.len() as u64, NOTES_PER_BATCH - 1);
+    assert!(notes.iter().all(|note| note.id != Some(U64(3))));
+
+    // Only the author can delete a note
+    assert!(notes_contract
+        .as_account()
+        .call(notes_contract.id(), "delete_note")
+        .args_json(json!({"id": U64(4)}))
+        .transact()
+        .await?
+        .is_failure());
+
+    Ok(())
+}
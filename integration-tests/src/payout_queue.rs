@@ -0,0 +1,267 @@
+use near_sdk::{json_types::U128, AccountId, NearToken};
+use near_workspaces::{Account, Contract};
+use serde_json::json;
+
+use crate::{fixtures::Fixture, wasm::wasm};
+
+const PAYOUT_QUEUE: &str = "payout-queue";
+
+const EXPLOIT_CONTRACT: &str = "exploit";
+
+const PAYOUT: NearToken = NearToken::from_near(1);
+
+struct Env {
+    owner: Account,
+    alice: Account,
+    bob: Account,
+    exploit_contract: Contract,
+    payout_queue: Contract,
+}
+
+async fn prepare() -> color_eyre::Result<Env> {
+    let mut builder = Fixture::builder();
+
+    let owner = builder.account();
+    let alice = builder.account();
+    let bob = builder.account();
+    let exploit = builder.contract(wasm(EXPLOIT_CONTRACT));
+    let payout_queue = builder.contract(wasm(PAYOUT_QUEUE));
+
+    builder.init(
+        payout_queue,
+        "new",
+        move |fixture| json!({"owner": fixture[owner].id()}),
+    );
+
+    let fixture = builder.build().await?;
+
+    Ok(Env {
+        owner: fixture[owner].clone(),
+        alice: fixture[alice].clone(),
+        bob: fixture[bob].clone(),
+        exploit_contract: fixture[exploit].clone(),
+        payout_queue: fixture[payout_queue].clone(),
+    })
+}
+
+async fn enqueue(
+    owner: &Account,
+    contract: &Contract,
+    beneficiary: &AccountId,
+    notify: bool,
+) -> color_eyre::Result<()> {
+    owner
+        .call(contract.id(), "enqueue")
+        .args_json(json!({"beneficiary": beneficiary, "notify": notify}))
+        .deposit(PAYOUT)
+        .transact()
+        .await?
+        .into_result()?;
+
+    Ok(())
+}
+
+/// Calls `pay_next` and returns whether the payout went through.
+async fn pay_next(
+    caller: &Account,
+    contract: &Contract,
+) -> color_eyre::Result<bool> {
+    Ok(caller
+        .call(contract.id(), "pay_next")
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?
+        .json::<bool>()?)
+}
+
+async fn get_next(contract: &Contract) -> color_eyre::Result<u32> {
+    Ok(contract.view("get_next").await?.json::<u32>()?)
+}
+
+async fn get_paid_out(contract: &Contract) -> color_eyre::Result<U128> {
+    Ok(contract.view("get_paid_out").await?.json::<U128>()?)
+}
+
+pub async fn reverting_receiver_blocks_queue() -> color_eyre::Result<()> {
+    let Env {
+        owner,
+        alice,
+        bob,
+        exploit_contract,
+        payout_queue: contract,
+    } = prepare().await?;
+
+    enqueue(&owner, &contract, alice.id(), false).await?;
+    enqueue(&owner, &contract, exploit_contract.id(), true).await?;
+    enqueue(&owner, &contract, bob.id(), false).await?;
+
+    assert!(pay_next(&owner, &contract).await?);
+    assert_eq!(get_next(&contract).await?, 1);
+
+    let bob_balance_before = bob.view_account().await?.balance;
+
+    // The exploit panics in `on_payout`, every retry pays it again and bob
+    // is never paid.
+    for _ in 0..3 {
+        assert!(!pay_next(&owner, &contract).await?);
+        assert_eq!(get_next(&contract).await?, 1);
+    }
+
+    assert_eq!(bob.view_account().await?.balance, bob_balance_before);
+    assert_eq!(get_paid_out(&contract).await?, U128(PAYOUT.as_yoctonear()));
+
+    Ok(())
+}
+
+pub async fn deleted_account_blocks_queue() -> color_eyre::Result<()> {
+    let Env {
+        owner,
+        alice,
+        bob,
+        payout_queue: contract,
+    } = prepare().await?;
+
+    let deleted_id = alice.id().clone();
+
+    alice.delete_account(owner.id()).await?.into_result()?;
+
+    enqueue(&owner, &contract, &deleted_id, false).await?;
+    enqueue(&owner, &contract, bob.id(), false).await?;
+
+    // A transfer to an account that doesn't exist fails, so the head of the
+    // queue can never be paid.
+    assert!(!pay_next(&owner, &contract).await?);
+    assert!(!pay_next(&owner, &contract).await?);
+
+    assert_eq!(get_next(&contract).await?, 0);
+    assert_eq!(get_paid_out(&contract).await?, U128(0));
+
+    Ok(())
+}
+
+pub async fn unchecked_payout_corrupts_queue() -> color_eyre::Result<()> {
+    let Env {
+        owner,
+        alice,
+        bob,
+        exploit_contract,
+        payout_queue: contract,
+    } = prepare().await?;
+
+    enqueue(&owner, &contract, alice.id(), false).await?;
+    enqueue(&owner, &contract, exploit_contract.id(), true).await?;
+    enqueue(&owner, &contract, bob.id(), false).await?;
+
+    let contract_balance_before = contract.view_account().await?.balance;
+    let exploit_balance_before = exploit_contract.view_account().await?.balance;
+
+    for _ in 0..3 {
+        owner
+            .call(contract.id(), "pay_next_unchecked")
+            .max_gas()
+            .transact()
+            .await?
+            .into_result()?;
+    }
+
+    let contract_balance_after = contract.view_account().await?.balance;
+    let exploit_balance_after = exploit_contract.view_account().await?.balance;
+
+    // The queue went through and counts all three payouts as paid.
+    assert_eq!(get_next(&contract).await?, 3);
+    assert_eq!(
+        get_paid_out(&contract).await?,
+        U128(PAYOUT.as_yoctonear() * 3)
+    );
+
+    // But the exploit's payout bounced back and is stuck in the contract,
+    // nothing in its state records that it is owed.
+    assert!(
+        exploit_balance_after.as_yoctonear()
+            < exploit_balance_before.as_yoctonear() + PAYOUT.as_yoctonear()
+    );
+    assert!(
+        contract_balance_after.as_yoctonear()
+            >= contract_balance_before.as_yoctonear()
+                - PAYOUT.as_yoctonear() * 2
+    );
+
+    Ok(())
+}
+
+pub async fn pull_payment_queue_fixed() -> color_eyre::Result<()> {
+    let Env {
+        owner,
+        alice,
+        bob,
+        exploit_contract,
+        payout_queue: contract,
+    } = prepare().await?;
+
+    let deleted = owner
+        .create_subaccount("deleted")
+        .initial_balance(NearToken::from_near(1))
+        .transact()
+        .await?
+        .into_result()?;
+    let deleted_id = deleted.id().clone();
+
+    deleted.delete_account(owner.id()).await?.into_result()?;
+
+    enqueue(&owner, &contract, alice.id(), false).await?;
+    enqueue(&owner, &contract, exploit_contract.id(), true).await?;
+    enqueue(&owner, &contract, &deleted_id, false).await?;
+    enqueue(&owner, &contract, bob.id(), false).await?;
+
+    // Releasing only credits balances, no beneficiary is called.
+    let released = owner
+        .call(contract.id(), "release")
+        .args_json(json!({}))
+        .max_gas()
+        .transact()
+        .await?
+        .into_result()?
+        .json::<u32>()?;
+
+    assert_eq!(released, 4);
+    assert_eq!(get_next(&contract).await?, 4);
+
+    // Each beneficiary pulls its own payout, the griefers only block
+    // themselves.
+    for beneficiary in [&alice, &bob] {
+        let balance_before = beneficiary.view_account().await?.balance;
+
+        beneficiary
+            .call(contract.id(), "withdraw")
+            .max_gas()
+            .transact()
+            .await?
+            .into_result()?;
+
+        let balance_after = beneficiary.view_account().await?.balance;
+
+        assert!(
+            balance_after.as_yoctonear()
+                > balance_before.as_yoctonear() + PAYOUT.as_yoctonear()
+                    - NearToken::from_millinear(10).as_yoctonear()
+        );
+    }
+
+    assert_eq!(
+        get_paid_out(&contract).await?,
+        U128(PAYOUT.as_yoctonear() * 2)
+    );
+
+    for account_id in [exploit_contract.id(), &deleted_id] {
+        let withdrawable = contract
+            .view("get_withdrawable")
+            .args_json(json!({"account_id": account_id}))
+            .await?
+            .json::<U128>()?;
+
+        assert_eq!(withdrawable, U128(PAYOUT.as_yoctonear()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use near_sdk::{json_types::U128, Gas, NearToken};
+use near_workspaces::{
+    operations::Function, result::ExecutionFinalResult, Account, Contract,
+};
+use serde_json::json;
+
+use crate::{fixtures::Fixture, report, wasm::wasm};
+
+const PREFIX_ALIASING_V1: &str = "prefix-aliasing-v1";
+const PREFIX_ALIASING_V2: &str = "prefix-aliasing-v2";
+
+const STAKE: NearToken = NearToken::from_near(5);
+
+struct Env {
+    victim: Account,
+    attacker: Account,
+    vault: Contract,
+}
+
+/// Deploys v1 and lets a victim and an attacker both stake `STAKE`.
+async fn prepare() -> color_eyre::Result<Env> {
+    let mut builder = Fixture::builder();
+
+    let victim = builder.account();
+    let attacker = builder.account();
+    let vault = builder.contract(wasm(PREFIX_ALIASING_V1));
+
+    builder.init(vault, "new", |_| json!({}));
+
+    let fixture = builder.build().await?;
+
+    let victim = fixture[victim].clone();
+    let attacker = fixture[attacker].clone();
+    let vault = fixture[vault].clone();
+
+    for account in [&victim, &attacker] {
+        account
+            .call(vault.id(), "deposit")
+            .deposit(STAKE)
+            .transact()
+            .await?
+            .into_result()?;
+
+        account
+            .call(vault.id(), "stake")
+            .args_json(json!({"amount": U128(STAKE.as_yoctonear())}))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+
+    Ok(Env {
+        victim,
+        attacker,
+        vault,
+    })
+}
+
+/// Deploys v2 and runs `migrate_method` in the same batch, so a failing
+/// migration reverts the deploy as well.
+async fn upgrade(
+    vault: &Contract,
+    migrate_method: &str,
+) -> color_eyre::Result<ExecutionFinalResult> {
+    Ok(vault
+        .as_account()
+        .batch(vault.id())
+        .deploy(wasm(PREFIX_ALIASING_V2))
+        .call(Function::new(migrate_method).gas(Gas::from_tgas(100)))
+        .transact()
+        .await?)
+}
+
+async fn view_u128(
+    vault: &Contract,
+    method: &str,
+    account: &Account,
+) -> color_eyre::Result<u128> {
+    Ok(vault
+        .view(method)
+        .args_json(json!({"account_id": account.id()}))
+        .await?
+        .json::<U128>()?
+        .0)
+}
+
+pub async fn upgrade_aliases_rewards_with_stakes() -> color_eyre::Result<()> {
+    let Env {
+        victim,
+        attacker,
+        vault,
+    } = prepare().await?;
+
+    upgrade(&vault, "migrate").await?.into_result()?;
+
+    // Nobody earned anything yet, but `rewards` reads the stakes.
+    for account in [&victim, &attacker] {
+        assert_eq!(
+            view_u128(&vault, "view_rewards", account).await?,
+            STAKE.as_yoctonear()
+        );
+    }
+
+    report::state_before(
+        "attacker stake",
+        U128(view_u128(&vault, "view_stake", &attacker).await?),
+    );
+
+    let balance_before = attacker.view_account().await?.balance;
+
+    let result = attacker
+        .call(vault.id(), "claim_rewards")
+        .transact()
+        .await?;
+
+    report::gas_burnt(result.total_gas_burnt);
+    result.into_result()?;
+
+    let balance_after = attacker.view_account().await?.balance;
+    let stake_after = view_u128(&vault, "view_stake", &attacker).await?;
+
+    // The "reward" is the attacker's own stake, claiming it removed the
+    // aliased entry. The attacker only moved the stake to its balance.
+    assert_eq!(stake_after, 0);
+
+    let profit = (balance_after.as_yoctonear() + stake_after) as i128
+        - (balance_before.as_yoctonear() + STAKE.as_yoctonear()) as i128;
+
+    report::attacker_profit(profit);
+    report::state_after("attacker stake", U128(stake_after));
+
+    assert!(profit <= 0);
+    assert!(profit > -(NearToken::from_millinear(10).as_yoctonear() as i128));
+
+    // The victim's stake is untouched, but it is still listed as a reward:
+    // every stake can be claimed as one and no real reward can be told
+    // apart from it.
+    assert_eq!(
+        view_u128(&vault, "view_stake", &victim).await?,
+        STAKE.as_yoctonear()
+    );
+    assert_eq!(
+        view_u128(&vault, "view_rewards", &victim).await?,
+        STAKE.as_yoctonear()
+    );
+
+    Ok(())
+}
+
+pub async fn checked_migration_rejects_aliasing() -> color_eyre::Result<()> {
+    let Env {
+        attacker, vault, ..
+    } = prepare().await?;
+
+    let result = upgrade(&vault, "migrate_checked").await?;
+
+    let error = format!("{:?}", result.into_result().unwrap_err());
+
+    assert!(
+        error.contains("Overlapping storage prefixes: `stakes` and `rewards`"),
+        "{error}"
+    );
+
+    // The deploy was reverted with the migration, v1 has no `view_rewards`.
+    assert!(vault
+        .view("view_rewards")
+        .args_json(json!({"account_id": attacker.id()}))
+        .await
+        .is_err());
+
+    assert_eq!(
+        view_u128(&vault, "view_stake", &attacker).await?,
+        STAKE.as_yoctonear()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,458 @@
+//! Runs a scenario over increasing collection sizes and records what each
+//! call costs, to turn "fails somewhere above N" into concrete numbers.
+//!
+//! A scenario is a closure that sets up a fresh contract with `size` entries,
+//! calls the method under test and returns the [`Sample`] of that call.
+
+use std::{fmt::Write as _, fs, future::Future, io, path::Path};
+
+use near_sdk::serde::Serialize;
+use near_workspaces::result::ExecutionFinalResult;
+
+use crate::limits::{MAX_GAS_BURNT, MAX_NUMBER_LOGS, MAX_TOTAL_LOG_LENGTH};
+
+/// Runtime limit a call ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Limit {
+    Gas,
+    LogCount,
+    LogBytes,
+    Other,
+}
+
+impl Limit {
+    fn from_error(error: &str) -> Self {
+        if error.contains("NumberOfLogsExceeded") {
+            Self::LogCount
+        } else if error.contains("TotalLogLengthExceeded") {
+            Self::LogBytes
+        } else if error.contains("GasLimitExceeded")
+            || error.contains("GasExceeded")
+        {
+            Self::Gas
+        } else {
+            Self::Other
+        }
+    }
+}
+
+/// Measurable quantity of a sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Metric {
+    GasBurnt,
+    LogCount,
+    LogBytes,
+    StorageUsage,
+}
+
+impl Metric {
+    /// Metric and limit value a call is stopped at, if any.
+    const LIMITED: [(Self, Limit, u64); 3] = [
+        (Self::GasBurnt, Limit::Gas, MAX_GAS_BURNT),
+        (Self::LogCount, Limit::LogCount, MAX_NUMBER_LOGS),
+        (Self::LogBytes, Limit::LogBytes, MAX_TOTAL_LOG_LENGTH),
+    ];
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Sample {
+    pub size: usize,
+    pub gas_burnt: u64,
+    pub log_count: u64,
+    pub log_bytes: u64,
+    /// Storage usage of the contract after the call.
+    pub storage_usage: u64,
+    pub error: Option<String>,
+    pub limit: Option<Limit>,
+}
+
+impl Sample {
+    pub fn new(
+        size: usize,
+        result: ExecutionFinalResult,
+        storage_usage: u64,
+    ) -> Self {
+        let logs = result.logs();
+
+        let log_count = logs.len() as u64;
+        let log_bytes = logs.iter().map(|log| log.len() as u64).sum();
+        let gas_burnt = result.total_gas_burnt.as_gas();
+
+        let error =
+            result.into_result().err().map(|error| format!("{error:?}"));
+        let limit = error.as_deref().map(Limit::from_error);
+
+        Self {
+            size,
+            gas_burnt,
+            log_count,
+            log_bytes,
+            storage_usage,
+            error,
+            limit,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    pub fn metric(&self, metric: Metric) -> u64 {
+        match metric {
+            Metric::GasBurnt => self.gas_burnt,
+            Metric::LogCount => self.log_count,
+            Metric::LogBytes => self.log_bytes,
+            Metric::StorageUsage => self.storage_usage,
+        }
+    }
+}
+
+/// Least squares fit of `metric = intercept + slope * size`.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Fit {
+    pub metric: Metric,
+    pub intercept: f64,
+    pub slope: f64,
+    /// Coefficient of determination, 1.0 is a perfect fit.
+    pub r_squared: f64,
+}
+
+impl Fit {
+    /// Fits successful samples only, `None` with fewer than two sizes.
+    pub fn linear(metric: Metric, samples: &[Sample]) -> Option<Self> {
+        let points = samples
+            .iter()
+            .filter(|sample| sample.is_success())
+            .map(|sample| (sample.size as f64, sample.metric(metric) as f64))
+            .collect::<Vec<_>>();
+
+        if points.len() < 2 {
+            return None;
+        }
+
+        let n = points.len() as f64;
+
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+        let ss_xx = points
+            .iter()
+            .map(|(x, _)| (x - mean_x).powi(2))
+            .sum::<f64>();
+        let ss_xy = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum::<f64>();
+
+        if ss_xx == 0.0 {
+            return None;
+        }
+
+        let slope = ss_xy / ss_xx;
+        let intercept = mean_y - slope * mean_x;
+
+        let ss_tot = points
+            .iter()
+            .map(|(_, y)| (y - mean_y).powi(2))
+            .sum::<f64>();
+        let ss_res = points
+            .iter()
+            .map(|(x, y)| (y - (intercept + slope * x)).powi(2))
+            .sum::<f64>();
+
+        let r_squared = if ss_tot == 0.0 {
+            1.0
+        } else {
+            1.0 - ss_res / ss_tot
+        };
+
+        Some(Self {
+            metric,
+            intercept,
+            slope,
+            r_squared,
+        })
+    }
+
+    /// Size at which the fitted metric reaches `value`.
+    pub fn size_at(&self, value: f64) -> Option<f64> {
+        (self.slope > 0.0).then(|| (value - self.intercept) / self.slope)
+    }
+}
+
+/// Smallest size that fails, and the largest one below it that doesn't.
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BreakingPoint {
+    pub limit: Option<Limit>,
+    pub last_success: Sample,
+    pub first_failure: Sample,
+}
+
+/// Size at which a fitted metric is expected to hit its limit.
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Prediction {
+    pub limit: Limit,
+    pub size: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Profile {
+    pub method: String,
+    pub samples: Vec<Sample>,
+    pub breaking_point: Option<BreakingPoint>,
+    pub fits: Vec<Fit>,
+    pub predictions: Vec<Prediction>,
+}
+
+impl Profile {
+    pub fn fit(&self, metric: Metric) -> Option<&Fit> {
+        self.fits.iter().find(|fit| fit.metric == metric)
+    }
+
+    pub fn prediction(&self, limit: Limit) -> Option<f64> {
+        self.predictions
+            .iter()
+            .find(|prediction| prediction.limit == limit)
+            .map(|prediction| prediction.size)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "size,gas_burnt,log_count,log_bytes,storage_usage,limit,error\n",
+        );
+
+        for sample in &self.samples {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},\"{}\"",
+                sample.size,
+                sample.gas_burnt,
+                sample.log_count,
+                sample.log_bytes,
+                sample.storage_usage,
+                sample
+                    .limit
+                    .map(|limit| format!("{limit:?}"))
+                    .unwrap_or_default(),
+                sample
+                    .error
+                    .as_deref()
+                    .unwrap_or_default()
+                    .replace('"', "\"\"")
+            )
+            .unwrap();
+        }
+
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Writes `<method>.csv` and `<method>.json` into `dir`.
+    pub fn write(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(format!("{}.csv", self.method)), self.to_csv())?;
+        fs::write(dir.join(format!("{}.json", self.method)), self.to_json())?;
+
+        Ok(())
+    }
+}
+
+/// Directory profiles are written to, `target/profiles` of the workspace.
+pub fn profiles_dir() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../target/profiles"))
+}
+
+pub struct Profiler<F> {
+    method: String,
+    scenario: F,
+}
+
+impl<F, Fut> Profiler<F>
+where
+    F: Fn(usize) -> Fut,
+    Fut: Future<Output = color_eyre::Result<Sample>>,
+{
+    pub fn new(method: &str, scenario: F) -> Self {
+        Self {
+            method: method.to_string(),
+            scenario,
+        }
+    }
+
+    /// Runs the scenario at each of `sizes`, which should be increasing.
+    /// Once a size fails, bisects down to the exact breaking point, to
+    /// within `precision` sizes.
+    pub async fn run(
+        &self,
+        sizes: &[usize],
+        precision: usize,
+    ) -> color_eyre::Result<Profile> {
+        let mut samples = Vec::new();
+
+        for &size in sizes {
+            let sample = (self.scenario)(size).await?;
+
+            let failed = !sample.is_success();
+            samples.push(sample);
+
+            if failed {
+                break;
+            }
+        }
+
+        let breaking_point = match samples.as_slice() {
+            [.., last_success, first_failure]
+                if last_success.is_success() && !first_failure.is_success() =>
+            {
+                Some(
+                    self.bisect(
+                        last_success.clone(),
+                        first_failure.clone(),
+                        precision,
+                    )
+                    .await?,
+                )
+            }
+            _ => None,
+        };
+
+        let fits = [
+            Metric::GasBurnt,
+            Metric::LogCount,
+            Metric::LogBytes,
+            Metric::StorageUsage,
+        ]
+        .into_iter()
+        .filter_map(|metric| Fit::linear(metric, &samples))
+        .collect::<Vec<_>>();
+
+        let predictions = Metric::LIMITED
+            .into_iter()
+            .filter_map(|(metric, limit, value)| {
+                let fit = fits.iter().find(|fit| fit.metric == metric)?;
+
+                Some(Prediction {
+                    limit,
+                    size: fit.size_at(value as f64)?,
+                })
+            })
+            .collect();
+
+        Ok(Profile {
+            method: self.method.clone(),
+            samples,
+            breaking_point,
+            fits,
+            predictions,
+        })
+    }
+
+    async fn bisect(
+        &self,
+        mut last_success: Sample,
+        mut first_failure: Sample,
+        precision: usize,
+    ) -> color_eyre::Result<BreakingPoint> {
+        while first_failure.size - last_success.size > precision.max(1) {
+            let size = last_success.size
+                + (first_failure.size - last_success.size) / 2;
+
+            let sample = (self.scenario)(size).await?;
+
+            if sample.is_success() {
+                last_success = sample;
+            } else {
+                first_failure = sample;
+            }
+        }
+
+        Ok(BreakingPoint {
+            limit: first_failure.limit,
+            last_success,
+            first_failure,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(size: usize, gas_burnt: u64) -> Sample {
+        Sample {
+            size,
+            gas_burnt,
+            log_count: size as u64,
+            log_bytes: 0,
+            storage_usage: 100,
+            error: None,
+            limit: None,
+        }
+    }
+
+    #[test]
+    fn linear_fit() {
+        let samples = [sample(10, 1_050), sample(20, 2_050), sample(40, 4_050)];
+
+        let fit = Fit::linear(Metric::GasBurnt, &samples).unwrap();
+
+        assert!((fit.slope - 100.0).abs() < 1e-9);
+        assert!((fit.intercept - 50.0).abs() < 1e-9);
+        assert!((fit.r_squared - 1.0).abs() < 1e-9);
+        assert!((fit.size_at(10_050.0).unwrap() - 100.0).abs() < 1e-9);
+
+        // Constant metrics are a perfect fit that never reaches a limit.
+        let fit = Fit::linear(Metric::StorageUsage, &samples).unwrap();
+
+        assert_eq!(fit.r_squared, 1.0);
+        assert_eq!(fit.size_at(200.0), None);
+    }
+
+    #[test]
+    fn limit_from_error() {
+        assert_eq!(
+            Limit::from_error("HostError(NumberOfLogsExceeded { limit: 100 })"),
+            Limit::LogCount
+        );
+        assert_eq!(
+            Limit::from_error("HostError(TotalLogLengthExceeded { .. })"),
+            Limit::LogBytes
+        );
+        assert_eq!(Limit::from_error("HostError(GasExceeded)"), Limit::Gas);
+        assert_eq!(Limit::from_error("Smart contract panicked"), Limit::Other);
+    }
+
+    #[test]
+    fn csv_quotes_errors() {
+        let mut failed = sample(30, 3_000);
+        failed.error = Some("panicked: \"no entry\"".into());
+        failed.limit = Some(Limit::Other);
+
+        let profile = Profile {
+            method: "method".into(),
+            samples: vec![sample(10, 1_000), failed],
+            breaking_point: None,
+            fits: Vec::new(),
+            predictions: Vec::new(),
+        };
+
+        assert_eq!(
+            profile.to_csv(),
+            "size,gas_burnt,log_count,log_bytes,storage_usage,limit,error\n\
+             10,1000,10,0,100,,\"\"\n\
+             30,3000,30,0,100,Other,\"panicked: \"\"no entry\"\"\"\n"
+        );
+    }
+}
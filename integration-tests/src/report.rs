@@ -0,0 +1,363 @@
+//! Vulnerability reports generated from scenario runs.
+//!
+//! A scenario records what it observes with [`state_before`],
+//! [`state_after`], [`attacker_profit`], [`gas_burnt`] and
+//! [`receipt_tree`]. Outside of an
+//! [`Observer`] these do nothing, so the same PoC runs unchanged under
+//! `cargo test`. The runner combines the observations with the scenario's
+//! metadata and the result of its remediation into a [`Report`], written as
+//! JSON for tooling and as Markdown for audit reports.
+
+use std::{
+    cell::RefCell, fmt::Write as _, fs, future::Future, io, path::Path, rc::Rc,
+    time::Duration,
+};
+
+use near_sdk::{
+    serde::{Serialize, Serializer},
+    Gas,
+};
+use serde_json::Value;
+
+use crate::{receipt_tree::ReceiptTree, scenarios::Scenario};
+
+tokio::task_local! {
+    static OBSERVER: Observer;
+}
+
+/// What a scenario observed while it ran.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Observations {
+    pub state: Vec<StateChange>,
+    /// Net NEAR the attacker gained, negative if the attack only cost gas.
+    #[serde(serialize_with = "serialize_yocto")]
+    pub attacker_profit: Option<i128>,
+    pub gas_burnt: Gas,
+    /// Receipts of the attacker transactions, as [`ReceiptTree::to_json`].
+    pub receipt_trees: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StateChange {
+    pub name: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Collects the observations of one scenario, also if it panics.
+#[derive(Clone, Default)]
+pub struct Observer(Rc<RefCell<Observations>>);
+
+impl Observer {
+    /// Runs `future` with this observer recording.
+    pub fn scope<F: Future>(
+        &self,
+        future: F,
+    ) -> impl Future<Output = F::Output> {
+        OBSERVER.scope(self.clone(), future)
+    }
+
+    pub fn take(&self) -> Observations {
+        self.0.take()
+    }
+}
+
+fn observe(f: impl FnOnce(&mut Observations)) {
+    let _ = OBSERVER.try_with(|observer| f(&mut observer.0.borrow_mut()));
+}
+
+fn state_change(name: &str, value: impl Serialize, after: bool) {
+    let value = serde_json::to_value(value).unwrap_or(Value::Null);
+
+    observe(|observations| {
+        let index = match observations
+            .state
+            .iter()
+            .position(|change| change.name == name)
+        {
+            Some(index) => index,
+            None => {
+                observations.state.push(StateChange {
+                    name: name.to_string(),
+                    before: None,
+                    after: None,
+                });
+
+                observations.state.len() - 1
+            }
+        };
+
+        let change = &mut observations.state[index];
+
+        if after {
+            change.after = Some(value);
+        } else {
+            change.before = Some(value);
+        }
+    });
+}
+
+/// Records `name` before the attack.
+pub fn state_before(name: &str, value: impl Serialize) {
+    state_change(name, value, false);
+}
+
+/// Records `name` after the attack.
+pub fn state_after(name: &str, value: impl Serialize) {
+    state_change(name, value, true);
+}
+
+/// Adds `yocto` to the attacker's profit.
+pub fn attacker_profit(yocto: i128) {
+    observe(|observations| {
+        *observations.attacker_profit.get_or_insert(0) += yocto;
+    });
+}
+
+/// Adds the gas of an attacker transaction.
+pub fn gas_burnt(gas: Gas) {
+    observe(|observations| {
+        observations.gas_burnt = observations.gas_burnt.saturating_add(gas);
+    });
+}
+
+/// Adds the receipts of an attacker transaction.
+pub(crate) fn receipt_tree(tree: &ReceiptTree) {
+    let tree = serde_json::to_value(tree).unwrap_or(Value::Null);
+
+    observe(|observations| observations.receipt_trees.push(tree));
+}
+
+fn serialize_yocto<S: Serializer>(
+    yocto: &Option<i128>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    // Amounts are strings in NEAR's JSON, like `U128`.
+    yocto.map(|yocto| yocto.to_string()).serialize(serializer)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Remediation {
+    pub id: &'static str,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Report {
+    pub id: &'static str,
+    pub class: &'static str,
+    pub category: &'static str,
+    pub severity: &'static str,
+    pub contract: &'static str,
+    pub method: &'static str,
+    pub exploit: Option<&'static str>,
+    pub description: &'static str,
+    pub preconditions: &'static [&'static str],
+    pub steps: &'static [&'static str],
+    pub passed: bool,
+    pub error: Option<String>,
+    pub duration_ms: u128,
+    pub observations: Observations,
+    pub remediation: Option<Remediation>,
+}
+
+impl Report {
+    pub fn new(
+        scenario: &'static Scenario,
+        error: Option<String>,
+        duration: Duration,
+        observations: Observations,
+        remediation: Option<Remediation>,
+    ) -> Self {
+        Self {
+            id: scenario.id,
+            class: scenario.class,
+            category: scenario.category.as_str(),
+            severity: scenario.severity.as_str(),
+            contract: scenario.victim,
+            method: scenario.method,
+            exploit: scenario.exploit,
+            description: scenario.description,
+            preconditions: scenario.preconditions,
+            steps: scenario.steps,
+            passed: error.is_none(),
+            error,
+            duration_ms: duration.as_millis(),
+            observations,
+            remediation,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+
+        writeln!(md, "# {}: {}\n", self.id, self.class).unwrap();
+        writeln!(md, "{}\n", self.description).unwrap();
+        writeln!(md, "| | |\n|---|---|").unwrap();
+        writeln!(md, "| Category | {} |", self.category).unwrap();
+        writeln!(md, "| Severity | {} |", self.severity).unwrap();
+        writeln!(md, "| Contract | `{}` |", self.contract).unwrap();
+        writeln!(md, "| Method | `{}` |", self.method).unwrap();
+
+        if let Some(exploit) = self.exploit {
+            writeln!(md, "| Exploit contract | `{exploit}` |").unwrap();
+        }
+
+        writeln!(
+            md,
+            "| Result | {} in {} ms |",
+            pass_or_fail(self.passed),
+            self.duration_ms
+        )
+        .unwrap();
+
+        if let Some(error) = &self.error {
+            writeln!(md, "\n```text\n{error}\n```").unwrap();
+        }
+
+        writeln!(md, "\n## Preconditions\n").unwrap();
+
+        for precondition in self.preconditions {
+            writeln!(md, "- {precondition}").unwrap();
+        }
+
+        writeln!(md, "\n## Attacker steps\n").unwrap();
+
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(md, "{}. {step}", i + 1).unwrap();
+        }
+
+        writeln!(md, "\n## Observed state\n").unwrap();
+
+        if self.observations.state.is_empty() {
+            writeln!(md, "Not recorded by this scenario.").unwrap();
+        } else {
+            writeln!(md, "| State | Before | After |\n|---|---|---|").unwrap();
+
+            for change in &self.observations.state {
+                writeln!(
+                    md,
+                    "| {} | {} | {} |",
+                    change.name,
+                    render_value(&change.before),
+                    render_value(&change.after)
+                )
+                .unwrap();
+            }
+        }
+
+        if !self.observations.receipt_trees.is_empty() {
+            writeln!(md, "\n## Receipts\n").unwrap();
+
+            for tree in &self.observations.receipt_trees {
+                let tree = serde_json::to_string_pretty(tree).unwrap();
+
+                writeln!(md, "```json\n{tree}\n```").unwrap();
+            }
+        }
+
+        writeln!(md, "\n## Impact\n").unwrap();
+
+        match self.observations.attacker_profit {
+            Some(profit) => {
+                writeln!(md, "- Attacker profit: {profit} yoctoNEAR").unwrap()
+            }
+            None => writeln!(md, "- Attacker profit: not measured").unwrap(),
+        }
+
+        writeln!(
+            md,
+            "- Gas burnt by the attacker: {} Tgas",
+            self.observations.gas_burnt.as_tgas()
+        )
+        .unwrap();
+
+        writeln!(md, "\n## Remediation\n").unwrap();
+
+        match &self.remediation {
+            Some(remediation) => writeln!(
+                md,
+                "`{}`: {}",
+                remediation.id,
+                pass_or_fail(remediation.passed)
+            )
+            .unwrap(),
+            None => writeln!(md, "No remediation scenario.").unwrap(),
+        }
+
+        md
+    }
+
+    /// Writes `<id>.json` and `<id>.md` into `dir`.
+    pub fn write(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(format!("{}.json", self.id)), self.to_json())?;
+        fs::write(dir.join(format!("{}.md", self.id)), self.to_markdown())?;
+
+        Ok(())
+    }
+}
+
+/// Directory reports are written to, `target/reports` of the workspace.
+pub fn reports_dir() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../target/reports"))
+}
+
+fn pass_or_fail(passed: bool) -> &'static str {
+    if passed {
+        "PASS"
+    } else {
+        "FAIL"
+    }
+}
+
+fn render_value(value: &Option<Value>) -> String {
+    match value {
+        Some(value) => format!("`{value}`"),
+        None => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn observer_records_changes() {
+        let observer = Observer::default();
+
+        observer
+            .scope(async {
+                state_before("owner", "alice.near");
+                attacker_profit(5);
+                state_after("owner", "bob.near");
+                attacker_profit(-2);
+                gas_burnt(Gas::from_tgas(3));
+            })
+            .await;
+
+        let observations = observer.take();
+
+        assert_eq!(observations.state.len(), 1);
+        assert_eq!(observations.state[0].before, Some("alice.near".into()));
+        assert_eq!(observations.state[0].after, Some("bob.near".into()));
+        assert_eq!(observations.attacker_profit, Some(3));
+        assert_eq!(observations.gas_burnt, Gas::from_tgas(3));
+    }
+
+    #[test]
+    fn nothing_is_recorded_outside_an_observer() {
+        state_before("owner", "alice.near");
+        attacker_profit(5);
+    }
+}
@@ -0,0 +1,1042 @@
+//! Registry of the PoCs, for the `near-pocs` runner and for `cargo test`.
+//!
+//! Every scenario is an async function of a PoC module. The [`scenarios!`]
+//! list below records what it demonstrates, for the runner's listing and
+//! for the reports, and generates its test, so a PoC added to the list runs
+//! under both. The generated test keeps the PoC's name, so a PoC that was a
+//! `#[tokio::test]` of its module before it was registered still runs with
+//! `cargo test <name>`.
+//!
+//! Every PoC is registered. The only tests left in the PoC modules are the
+//! probes of `limits`, which check the runtime limits the DoS PoCs rely on
+//! rather than a contract, and the unit tests of the helpers.
+
+use std::{fmt, future::Future, pin::Pin, str::FromStr};
+
+pub type ScenarioFuture = Pin<Box<dyn Future<Output = color_eyre::Result<()>>>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    AccessControl,
+    Dos,
+    Race,
+    Storage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+pub struct Scenario {
+    pub id: &'static str,
+    pub category: Category,
+    pub severity: Severity,
+    /// Vulnerability class, e.g. "Public callback".
+    pub class: &'static str,
+    pub description: &'static str,
+    /// Contract crate under attack, or its fixed version.
+    pub victim: &'static str,
+    /// Affected method of the victim, e.g. `StatusMessage::set_owner`.
+    pub method: &'static str,
+    /// Contract crate the attacker deploys, if the PoC needs one.
+    pub exploit: Option<&'static str>,
+    /// Scenario running the same attack against the fix.
+    pub remediation: Option<&'static str>,
+    pub preconditions: &'static [&'static str],
+    pub steps: &'static [&'static str],
+    pub run: fn() -> ScenarioFuture,
+}
+
+impl Category {
+    pub const ALL: [Self; 4] =
+        [Self::AccessControl, Self::Dos, Self::Race, Self::Storage];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AccessControl => "access-control",
+            Self::Dos => "dos",
+            Self::Race => "race",
+            Self::Storage => "storage",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|category| category.as_str() == s)
+            .ok_or_else(|| format!("unknown category `{s}`"))
+    }
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// Scenario with the id `id`.
+pub fn find(id: &str) -> Option<&'static Scenario> {
+    SCENARIOS.iter().find(|scenario| scenario.id == id)
+}
+
+macro_rules! scenarios {
+    ($(
+        $module:ident::$id:ident {
+            category: $category:ident,
+            severity: $severity:ident,
+            class: $class:literal,
+            victim: $victim:literal,
+            method: $method:literal,
+            exploit: $exploit:expr,
+            remediation: $remediation:expr,
+            description: $description:literal,
+            preconditions: [$($precondition:literal),* $(,)?],
+            steps: [$($step:literal),* $(,)?] $(,)?
+        }
+    ),* $(,)?) => {
+        pub const SCENARIOS: &[Scenario] = &[$(
+            Scenario {
+                id: stringify!($id),
+                category: Category::$category,
+                severity: Severity::$severity,
+                class: $class,
+                description: $description,
+                victim: $victim,
+                method: $method,
+                exploit: $exploit,
+                remediation: $remediation,
+                preconditions: &[$($precondition),*],
+                steps: &[$($step),*],
+                run: {
+                    fn run() -> ScenarioFuture {
+                        Box::pin(crate::$module::$id())
+                    }
+
+                    run
+                },
+            },
+        )*];
+
+        #[cfg(test)]
+        mod tests {
+            use super::*;
+
+            // Duplicate ids already fail to compile as duplicate tests.
+            #[test]
+            fn remediations_exist() {
+                for scenario in SCENARIOS {
+                    if let Some(remediation) = scenario.remediation {
+                        assert!(
+                            find(remediation).is_some(),
+                            "Unknown remediation `{remediation}` of `{}`",
+                            scenario.id
+                        );
+                    }
+                }
+            }
+
+            $(
+                #[tokio::test]
+                async fn $id() -> color_eyre::Result<()> {
+                    crate::$module::$id().await
+                }
+            )*
+        }
+    };
+}
+
+scenarios! {
+    access_control::pausable_access_control {
+        category: AccessControl,
+        severity: High,
+        class: "Missing access control",
+        victim: "access-control",
+        method: "StatusMessage::toggle_pause",
+        exploit: None,
+        remediation: None,
+        description: "Anyone can toggle the pause flag and pause the \
+                      contract for every user.",
+        preconditions: [
+            "The contract is deployed and not paused.",
+        ],
+        steps: [
+            "Call `toggle_pause` from any account.",
+            "Every call to `get_data` now fails.",
+        ],
+    },
+    access_control::pausable_exploit_signer {
+        category: AccessControl,
+        severity: Critical,
+        class: "Authorization by signer",
+        victim: "access-control",
+        method: "StatusMessage::set_owner",
+        exploit: Some("exploit"),
+        remediation: None,
+        description: "The owner check trusts the signer, so a contract the \
+                      owner calls can hand ownership to the attacker.",
+        preconditions: [
+            "The owner can be tricked into calling the attacker's contract.",
+        ],
+        steps: [
+            "The owner calls `exploit_signer` on the exploit contract.",
+            "The exploit calls `set_owner` with the attacker as the new \
+             owner, signed by the owner.",
+        ],
+    },
+    access_control::public_callback_exploit {
+        category: AccessControl,
+        severity: Critical,
+        class: "Public callback",
+        victim: "access-control",
+        method: "StatusMessage::resolve_withdraw",
+        exploit: Some("exploit"),
+        remediation: None,
+        description: "`resolve_withdraw` isn't private, the exploit calls it \
+                      behind a failed promise and credits itself points.",
+        preconditions: [
+            "The attacker has no points.",
+        ],
+        steps: [
+            "Call `exploit_public_callback` on the exploit contract.",
+            "The exploit chains a panicking promise with `resolve_withdraw` \
+             on the target.",
+            "`resolve_withdraw` sees a failed withdrawal and refunds points \
+             that were never taken.",
+        ],
+    },
+    moderation::moderator_delete_note_unchecked {
+        category: AccessControl,
+        severity: High,
+        class: "Missing authorization",
+        victim: "storage-key-collisions",
+        method: "Contract::moderator_delete_note_unchecked",
+        exploit: None,
+        remediation: Some("moderator_delete_note"),
+        description: "Deleting a note as a moderator doesn't check the \
+                      caller is a manager, anyone wipes anyone's notes.",
+        preconditions: [
+            "The victim has a note.",
+        ],
+        steps: [
+            "Call `moderator_delete_note_unchecked` with the victim's note \
+             from any account.",
+        ],
+    },
+    moderation::moderator_delete_note {
+        category: AccessControl,
+        severity: High,
+        class: "Missing authorization",
+        victim: "storage-key-collisions",
+        method: "Contract::moderator_delete_note",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: only managers can delete other users' notes.",
+        preconditions: [
+            "The victim has a note.",
+        ],
+        steps: [
+            "The attacker's delete fails, the manager's succeeds.",
+        ],
+    },
+    moderation::freeze_user_wrong_account {
+        category: AccessControl,
+        severity: High,
+        class: "Authorization of the wrong account",
+        victim: "storage-key-collisions",
+        method: "Contract::freeze_user_wrong_account",
+        exploit: None,
+        remediation: Some("freeze_user"),
+        description: "The manager check runs against the account to freeze \
+                      instead of the caller, anyone can freeze the managers.",
+        preconditions: [
+            "The contract has a manager.",
+        ],
+        steps: [
+            "Call `freeze_user_wrong_account` with the manager from any \
+             account, the manager is frozen.",
+            "The manager can't freeze a regular user.",
+        ],
+    },
+    moderation::freeze_user {
+        category: AccessControl,
+        severity: High,
+        class: "Authorization of the wrong account",
+        victim: "storage-key-collisions",
+        method: "Contract::freeze_user",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: freezing checks the caller, frozen users can't \
+                      add notes until unfrozen.",
+        preconditions: [
+            "The victim has a note.",
+        ],
+        steps: [
+            "The attacker's freeze fails.",
+            "The manager freezes and unfreezes the victim.",
+        ],
+    },
+    moderation::manage_managers {
+        category: AccessControl,
+        severity: High,
+        class: "Missing authorization",
+        victim: "storage-key-collisions",
+        method: "Contract::add_manager",
+        exploit: None,
+        remediation: None,
+        description: "Only managers add and remove managers, and no manager \
+                      can remove itself.",
+        preconditions: [
+            "The contract has a manager.",
+        ],
+        steps: [
+            "The attacker's `add_manager` fails.",
+            "The manager adds a second one, who removes the first.",
+        ],
+    },
+    notes::note_lookup_gas {
+        category: Dos,
+        severity: Medium,
+        class: "Unbounded iteration",
+        victim: "storage-key-collisions",
+        method: "Contract::get_note",
+        exploit: None,
+        remediation: None,
+        description: "Looking a note up by scanning the author's notes costs \
+                      gas linear in their count, the id index reads one \
+                      entry.",
+        preconditions: [
+            "The author has 1000 notes.",
+        ],
+        steps: [
+            "Fetch the last note with `get_note` and `get_note_by_id`.",
+            "The scan burns more than five times the gas of the lookup.",
+        ],
+    },
+    notes::edit_and_delete_note {
+        category: Storage,
+        severity: Medium,
+        class: "Storage refund",
+        victim: "storage-key-collisions",
+        method: "Contract::edit_note",
+        exploit: None,
+        remediation: None,
+        description: "Growing a note has to be paid for and deleting one \
+                      frees its storage.",
+        preconditions: [
+            "The author has notes.",
+        ],
+        steps: [
+            "Grow a note without a deposit, the edit fails.",
+            "Grow it with a deposit, then delete it.",
+        ],
+    },
+    denial_of_service::log_limit_dos {
+        category: Dos,
+        severity: High,
+        class: "Unbounded log emission",
+        victim: "denial-of-service",
+        method: "Contract::add_reward_to_each_jar",
+        exploit: None,
+        remediation: Some("reward_distribution_fixed"),
+        description: "Rewarding logs once per jar, past 100 jars the call \
+                      hits the log count limit and always fails.",
+        preconditions: [
+            "Anyone can create jars.",
+        ],
+        steps: [
+            "Create more than 100 jars in batches.",
+            "Every `add_reward_to_each_jar` by the manager now fails.",
+        ],
+    },
+    denial_of_service::log_size_dos {
+        category: Dos,
+        severity: High,
+        class: "Unbounded log emission",
+        victim: "denial-of-service",
+        method: "Contract::add_reward_to_each_jar_log_size_limit",
+        exploit: None,
+        remediation: Some("reward_distribution_fixed"),
+        description: "Long logs per jar push rewarding past the total log \
+                      length limit.",
+        preconditions: [
+            "Anyone can create jars.",
+        ],
+        steps: [
+            "Create jars until their logs exceed 16 KiB.",
+            "Every reward call by the manager now fails.",
+        ],
+    },
+    denial_of_service::gas_limit_dos {
+        category: Dos,
+        severity: High,
+        class: "Unbounded iteration",
+        victim: "denial-of-service",
+        method: "Contract::claim_all_jars",
+        exploit: None,
+        remediation: Some("pull_payment_fixed"),
+        description: "Claiming iterates over every jar of the account and \
+                      runs out of gas once it owns enough of them.",
+        preconditions: [
+            "Anyone can create jars.",
+        ],
+        steps: [
+            "Create jars until claiming all of them exceeds 300 Tgas.",
+            "Every `claim_all_jars` of the account now fails, its jars are \
+             stuck.",
+        ],
+    },
+    denial_of_service::profile_log_limit_dos {
+        category: Dos,
+        severity: High,
+        class: "Unbounded log emission",
+        victim: "denial-of-service",
+        method: "Contract::add_reward_to_each_jar",
+        exploit: None,
+        remediation: Some("reward_distribution_fixed"),
+        description: "Profiles rewarding over growing jar counts, the call \
+                      breaks at exactly one jar per allowed log.",
+        preconditions: [
+            "Anyone can create jars.",
+        ],
+        steps: [
+            "Reward 10 to 130 jars, one log each.",
+            "Rewarding fails from the first jar past the log count limit.",
+        ],
+    },
+    denial_of_service::profile_log_size_dos {
+        category: Dos,
+        severity: High,
+        class: "Unbounded log emission",
+        victim: "denial-of-service",
+        method: "Contract::add_reward_to_each_jar_log_size_limit",
+        exploit: None,
+        remediation: Some("reward_distribution_fixed"),
+        description: "Profiles the single rewarding log over growing jar \
+                      counts and predicts where it exceeds 16 KiB.",
+        preconditions: [
+            "Anyone can create jars.",
+        ],
+        steps: [
+            "Reward 10 to 320 jars and bisect the breaking point.",
+            "The log grows linearly and breaks where the fit predicts.",
+        ],
+    },
+    denial_of_service::profile_gas_limit_dos {
+        category: Dos,
+        severity: High,
+        class: "Unbounded iteration",
+        victim: "denial-of-service",
+        method: "Contract::claim_all_jars",
+        exploit: None,
+        remediation: Some("pull_payment_fixed"),
+        description: "Profiles claiming over growing jar counts and predicts \
+                      the count that runs out of gas.",
+        preconditions: [
+            "One account owns many jars.",
+        ],
+        steps: [
+            "Claim 800 to 9600 jars and bisect the breaking point.",
+            "Gas grows linearly and runs out where the fit predicts.",
+        ],
+    },
+
+    denial_of_service::storage_bloating_dos {
+        category: Dos,
+        severity: Medium,
+        class: "Storage staking drain",
+        victim: "denial-of-service",
+        method: "Contract::create_jar",
+        exploit: None,
+        remediation: Some("storage_bloating_dos_fixed"),
+        description: "The contract pays for the storage of every jar, an \
+                      attacker locks its balance by creating jars.",
+        preconditions: [
+            "Creating a jar doesn't require a storage deposit.",
+        ],
+        steps: [
+            "Create jars without paying for their storage.",
+            "Each jar locks more of the contract's balance, until no one \
+             can write to the contract.",
+        ],
+    },
+    denial_of_service::storage_bloating_dos_fixed {
+        category: Dos,
+        severity: Medium,
+        class: "Storage staking drain",
+        victim: "denial-of-service-fixed",
+        method: "Contract::create_jar",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: every account pays for its jars from a storage \
+                      deposit.",
+        preconditions: [
+            "Accounts register with `storage_deposit`.",
+        ],
+        steps: [
+            "Create jars past the storage balance.",
+            "Creation fails once the deposit is used up, the contract \
+             balance is untouched.",
+        ],
+    },
+    denial_of_service::reward_distribution_fixed {
+        category: Dos,
+        severity: High,
+        class: "Unbounded iteration",
+        victim: "denial-of-service-fixed",
+        method: "Contract::distribute_rewards",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: rewards for 10k jars are distributed in bounded \
+                      pages.",
+        preconditions: [
+            "10 accounts own 10k jars.",
+        ],
+        steps: [
+            "The manager rewards the jars page by page until the \
+             distribution finishes.",
+        ],
+    },
+    denial_of_service::double_claim_drain {
+        category: Dos,
+        severity: Critical,
+        class: "State written to a copy",
+        victim: "denial-of-service",
+        method: "Contract::claim_all_jars",
+        exploit: None,
+        remediation: Some("double_claim_drain_fixed"),
+        description: "`claim_all_jars` zeroes copies of the jars, the stored \
+                      jars can be claimed again and again.",
+        preconditions: [
+            "The contract holds NEAR of other users.",
+        ],
+        steps: [
+            "Create a jar.",
+            "Call `claim_all_jars` repeatedly, each call pays the jar out \
+             again.",
+        ],
+    },
+    denial_of_service::double_claim_drain_fixed {
+        category: Dos,
+        severity: Critical,
+        class: "State written to a copy",
+        victim: "denial-of-service-fixed",
+        method: "Contract::claim_all_jars",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: a jar is paid out once, later claims fail.",
+        preconditions: [
+            "The jar amount is paid in.",
+        ],
+        steps: [
+            "Create a jar.",
+            "Claim it twice, the second claim fails.",
+        ],
+    },
+    denial_of_service::promise_fan_out_dos {
+        category: Dos,
+        severity: High,
+        class: "Unbounded promise fan-out",
+        victim: "denial-of-service",
+        method: "Contract::claim_all_jars_per_jar",
+        exploit: None,
+        remediation: Some("pull_payment_fixed"),
+        description: "One transfer promise per jar, past the gas limit the \
+                      claim reverts or leaves jars to be claimed again.",
+        preconditions: [
+            "One account owns many jars.",
+        ],
+        steps: [
+            "Call `claim_all_jars_per_jar` with growing numbers of jars.",
+            "Past the gas limit callbacks fail and the claim is inconsistent \
+             or reverted.",
+        ],
+    },
+    denial_of_service::pull_payment_fixed {
+        category: Dos,
+        severity: High,
+        class: "Unbounded promise fan-out",
+        victim: "denial-of-service-fixed",
+        method: "Contract::claim_jars",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: claims credit a balance that is withdrawn in \
+                      one transfer.",
+        preconditions: [
+            "One account owns 1000 jars.",
+        ],
+        steps: [
+            "Claim the jars in bounded batches.",
+            "Withdraw the credited balance in one transfer.",
+        ],
+    },
+    payout_queue::reverting_receiver_blocks_queue {
+        category: Dos,
+        severity: High,
+        class: "Push payment to untrusted receiver",
+        victim: "payout-queue",
+        method: "Contract::pay_next",
+        exploit: Some("exploit"),
+        remediation: Some("pull_payment_queue_fixed"),
+        description: "A beneficiary that panics on payout blocks every \
+                      payout queued after it.",
+        preconditions: [
+            "The attacker's contract is a beneficiary in the queue.",
+        ],
+        steps: [
+            "Make `on_payout` panic.",
+            "Every `pay_next` fails at the attacker's payout and nobody \
+             after it is paid.",
+        ],
+    },
+    payout_queue::deleted_account_blocks_queue {
+        category: Dos,
+        severity: High,
+        class: "Push payment to untrusted receiver",
+        victim: "payout-queue",
+        method: "Contract::pay_next",
+        exploit: None,
+        remediation: Some("pull_payment_queue_fixed"),
+        description: "A deleted beneficiary can never be paid and blocks the \
+                      queue.",
+        preconditions: [
+            "The attacker's account is a beneficiary in the queue.",
+        ],
+        steps: [
+            "Delete the account.",
+            "Every `pay_next` fails at its payout.",
+        ],
+    },
+    payout_queue::unchecked_payout_corrupts_queue {
+        category: Dos,
+        severity: Medium,
+        class: "Unchecked promise result",
+        victim: "payout-queue",
+        method: "Contract::pay_next_unchecked",
+        exploit: Some("exploit"),
+        remediation: Some("pull_payment_queue_fixed"),
+        description: "Skipping the result check counts a bounced payout as \
+                      paid and strands its funds.",
+        preconditions: [
+            "The attacker's contract is a beneficiary in the queue.",
+        ],
+        steps: [
+            "Make `on_payout` panic.",
+            "`pay_next_unchecked` advances anyway, the refund is stuck in \
+             the contract.",
+        ],
+    },
+    payout_queue::pull_payment_queue_fixed {
+        category: Dos,
+        severity: High,
+        class: "Push payment to untrusted receiver",
+        victim: "payout-queue",
+        method: "Contract::release",
+        exploit: Some("exploit"),
+        remediation: None,
+        description: "Fixed: payouts are credited and every beneficiary \
+                      withdraws its own.",
+        preconditions: [
+            "A reverting and a deleted beneficiary are in the queue.",
+        ],
+        steps: [
+            "Release the queue.",
+            "Honest beneficiaries withdraw, the griefers only block \
+             themselves.",
+        ],
+    },
+    large_inputs::huge_jar_batch_dos {
+        category: Dos,
+        severity: Medium,
+        class: "Unbounded input",
+        victim: "denial-of-service",
+        method: "Contract::batch_create_jars",
+        exploit: None,
+        remediation: Some("large_inputs_fixed"),
+        description: "An unbounded jar batch runs out of gas, the profile \
+                      shows where.",
+        preconditions: [
+            "Batches have no size limit.",
+        ],
+        steps: [
+            "Send jar batches of growing size.",
+            "Past the predicted size the batch runs out of gas.",
+        ],
+    },
+    large_inputs::huge_jar_batch_locks_balance {
+        category: Dos,
+        severity: Medium,
+        class: "Unbounded input",
+        victim: "denial-of-service",
+        method: "Contract::batch_create_jars",
+        exploit: None,
+        remediation: Some("large_inputs_fixed"),
+        description: "One large batch of jars locks contract balance for \
+                      their storage.",
+        preconditions: [
+            "Batches have no size limit and no storage deposit.",
+        ],
+        steps: [
+            "Create 800 jars in one batch.",
+            "The contract's free balance drops by their storage cost.",
+        ],
+    },
+    large_inputs::huge_note_dos {
+        category: Dos,
+        severity: Low,
+        class: "Unbounded input",
+        victim: "denial-of-service",
+        method: "Contract::add_note",
+        exploit: None,
+        remediation: Some("large_inputs_fixed"),
+        description: "Note size is only bounded by the storage key limit.",
+        preconditions: [
+            "Notes have no size limit.",
+        ],
+        steps: [
+            "Add notes of growing size until storage rejects the key.",
+        ],
+    },
+    large_inputs::get_notes_view_dos {
+        category: Dos,
+        severity: Medium,
+        class: "Unbounded view",
+        victim: "denial-of-service",
+        method: "Contract::get_notes",
+        exploit: None,
+        remediation: Some("large_inputs_fixed"),
+        description: "Enough large notes make an unbounded `get_notes` view \
+                      fail.",
+        preconditions: [
+            "`get_notes` has no page limit.",
+        ],
+        steps: [
+            "Add near-maximum notes until the view fails.",
+            "Find the largest limit that still works.",
+        ],
+    },
+    large_inputs::large_inputs_fixed {
+        category: Dos,
+        severity: Medium,
+        class: "Unbounded input",
+        victim: "denial-of-service-fixed",
+        method: "Contract::add_note",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: note sizes, pages and jar batches are bounded.",
+        preconditions: [
+            "Accounts register with `storage_deposit`.",
+        ],
+        steps: [
+            "Send oversized notes, pages and batches, each is rejected.",
+        ],
+    },
+    note_removal::clear_gas_trap {
+        category: Dos,
+        severity: Medium,
+        class: "Unbounded iteration",
+        victim: "denial-of-service",
+        method: "Contract::remove_all_notes_correct",
+        exploit: None,
+        remediation: Some("clear_gas_trap_fixed"),
+        description: "Past the gas limit a note set can't be cleared in one \
+                      call and its notes can never be removed.",
+        preconditions: [
+            "The author has more notes than one call can clear.",
+        ],
+        steps: [
+            "Call `remove_all_notes_correct`, it runs out of gas and reverts \
+             every time.",
+        ],
+    },
+    note_removal::clear_gas_trap_fixed {
+        category: Dos,
+        severity: Medium,
+        class: "Unbounded iteration",
+        victim: "denial-of-service-fixed",
+        method: "Contract::remove_notes_batch",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: notes are removed in bounded batches.",
+        preconditions: [
+            "The author has 1000 notes.",
+        ],
+        steps: [
+            "`remove_all_notes` refuses, `remove_notes_batch` removes them \
+             in 4 calls.",
+        ],
+    },
+    race_condition::exploit_race_condition {
+        category: Race,
+        severity: Critical,
+        class: "Check before cross-contract call",
+        victim: "deposit_contract",
+        method: "Contract::stake",
+        exploit: None,
+        remediation: None,
+        description: "Two stakes batched in one transaction both pass the \
+                      balance check, the deposit is staked twice.",
+        preconditions: [
+            "The attacker deposited 20 NEAR.",
+        ],
+        steps: [
+            "Batch two `stake` calls of the full deposit.",
+            "Both pass the check before either callback deducts it.",
+            "Withdraw twice the deposit from the staking contract.",
+        ],
+    },
+    race_condition::callback_failure_does_not_revert_staking {
+        category: Race,
+        severity: High,
+        class: "Callback without rollback",
+        victim: "deposit_contract",
+        method: "Contract::resolve_staking_tracked",
+        exploit: None,
+        remediation: Some("callback_failure_fixed"),
+        description: "A panicking callback doesn't revert the stake, the \
+                      same deposit is paid out twice.",
+        preconditions: [
+            "The attacker deposited 20 NEAR.",
+        ],
+        steps: [
+            "Stake with a callback that panics.",
+            "Withdraw the deposit and the stake.",
+        ],
+    },
+    race_condition::callback_failure_fixed {
+        category: Race,
+        severity: High,
+        class: "Callback without rollback",
+        victim: "deposit_contract",
+        method: "Contract::resolve_staking_safe",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: a failed stake is refunded by the callback \
+                      instead of panicking.",
+        preconditions: [
+            "The attacker deposited 20 NEAR.",
+        ],
+        steps: [
+            "Stake, then withdraw, the deposit can only be spent once.",
+        ],
+    },
+    race_condition::approve_front_running {
+        category: Race,
+        severity: High,
+        class: "Approve front-running",
+        victim: "deposit_contract",
+        method: "Contract::approve",
+        exploit: None,
+        remediation: Some("approve_front_running_fixed"),
+        description: "A spender front-runs a lowered allowance and spends \
+                      the old and the new one.",
+        preconditions: [
+            "The owner approved 10 NEAR and sends an `approve` of 5 NEAR.",
+        ],
+        steps: [
+            "While the `approve` is pending, get a batch spending the whole \
+             old allowance in ahead of it.",
+            "Spend the new allowance as well.",
+        ],
+    },
+    race_condition::approve_front_running_fixed {
+        category: Race,
+        severity: High,
+        class: "Approve front-running",
+        victim: "deposit_contract",
+        method: "Contract::decrease_allowance",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: relative allowance changes can't be front-run.",
+        preconditions: [
+            "The owner approved 10 NEAR and sends a decrease of 5 NEAR.",
+        ],
+        steps: [
+            "While the decrease is pending, get a batch spending the whole \
+             allowance in ahead of it.",
+            "The decrease and any further spending fail, the spender gets \
+             10 NEAR.",
+        ],
+    },
+    storage_collisions::storage_key_collision {
+        category: Storage,
+        severity: Critical,
+        class: "Storage key collision",
+        victim: "storage-key-collisions",
+        method: "Contract::create_jar",
+        exploit: None,
+        remediation: Some("storage_key_collision_fixed"),
+        description: "`id ++ account` keys collide across accounts, one \
+                      account overwrites another's jar.",
+        preconditions: [
+            "The attacker controls `1account_id`, the victim is `account_id`.",
+        ],
+        steps: [
+            "The victim creates jar `11`.",
+            "The attacker creates jar `1`, rendering the same key.",
+        ],
+    },
+    storage_collisions::storage_key_collision_timestamp {
+        category: Storage,
+        severity: High,
+        class: "Storage key collision",
+        victim: "storage-key-collisions",
+        method: "Contract::create_jar_timestamp",
+        exploit: None,
+        remediation: Some("storage_key_collision_fixed"),
+        description: "Timestamp keys collide for jars created in the same \
+                      block.",
+        preconditions: [
+            "Jar keys contain the block timestamp.",
+        ],
+        steps: [
+            "Create two jars in one batch.",
+        ],
+    },
+    storage_collisions::storage_key_collision_fixed {
+        category: Storage,
+        severity: Critical,
+        class: "Storage key collision",
+        victim: "storage-key-collisions-fixed",
+        method: "Contract::create_jar",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: Borsh-serialized storage keys can't collide.",
+        preconditions: [
+            "The same accounts as the vulnerable case.",
+        ],
+        steps: [
+            "Create the same jars, both accounts keep their own.",
+        ],
+    },
+    storage_collisions::remove_all_notes_leaks_storage {
+        category: Storage,
+        severity: Medium,
+        class: "Orphaned storage",
+        victim: "storage-key-collisions",
+        method: "Contract::remove_all_notes",
+        exploit: None,
+        remediation: None,
+        description: "Removing the set entry without clearing it orphans the \
+                      notes in storage.",
+        preconditions: [
+            "Users have notes.",
+        ],
+        steps: [
+            "Call `remove_all_notes`.",
+            "The notes stay in state and the contract keeps paying for them.",
+        ],
+    },
+    storage_collisions::legacy_set_length_lost {
+        category: Storage,
+        severity: Medium,
+        class: "Unpersisted collection",
+        victim: "storage-key-collisions",
+        method: "Contract::add_note_collection",
+        exploit: None,
+        remediation: None,
+        description: "A legacy set's length isn't written back, notes \
+                      overwrite each other and the limit never applies.",
+        preconditions: [
+            "Notes are kept in a legacy `UnorderedSet`.",
+        ],
+        steps: [
+            "Add more notes than the limit allows.",
+        ],
+    },
+    storage_collisions::legacy_set_migration {
+        category: Storage,
+        severity: Medium,
+        class: "Unpersisted collection",
+        victim: "storage-key-collisions",
+        method: "Contract::migrate_note_collections",
+        exploit: None,
+        remediation: None,
+        description: "Migrating legacy note sets loses the notes whose \
+                      length was never written back.",
+        preconditions: [
+            "Notes were added through both collection methods.",
+        ],
+        steps: [
+            "The owner migrates the sets, the overwritten notes are gone.",
+        ],
+    },
+    storage_collisions::create_jar_timestamp_aliasing {
+        category: Storage,
+        severity: High,
+        class: "Storage prefix aliasing",
+        victim: "storage-key-collisions",
+        method: "Contract::create_jar_timestamp",
+        exploit: None,
+        remediation: None,
+        description: "Two jars created in one receipt share a timestamp \
+                      prefix, the second overwrites the first.",
+        preconditions: [
+            "Jar collections are prefixed with the block timestamp.",
+        ],
+        steps: [
+            "Create two jars in one batch.",
+        ],
+    },
+    prefix_aliasing::upgrade_aliases_rewards_with_stakes {
+        category: Storage,
+        severity: High,
+        class: "Storage prefix aliasing",
+        victim: "prefix-aliasing-v2",
+        method: "Contract::claim_rewards",
+        exploit: None,
+        remediation: Some("checked_migration_rejects_aliasing"),
+        description: "An enum variant added in the middle reuses the stakes \
+                      prefix, stakes and rewards become the same entries.",
+        preconditions: [
+            "The victim and the attacker staked in v1.",
+            "The contract is upgraded to v2.",
+        ],
+        steps: [
+            "Call `claim_rewards`, the attacker's stake is paid out as a \
+             reward and its stake is gone with it.",
+            "Every other stake still reads as a claimable reward.",
+        ],
+    },
+    prefix_aliasing::checked_migration_rejects_aliasing {
+        category: Storage,
+        severity: High,
+        class: "Storage prefix aliasing",
+        victim: "prefix-aliasing-v2",
+        method: "Contract::migrate_checked",
+        exploit: None,
+        remediation: None,
+        description: "Fixed: the migration checks the old prefix is empty \
+                      and rejects the upgrade.",
+        preconditions: [
+            "The victim and the attacker staked in v1.",
+        ],
+        steps: [
+            "Upgrade with `migrate_checked`, the upgrade reverts.",
+        ],
+    },
+}
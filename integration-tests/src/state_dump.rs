@@ -0,0 +1,177 @@
+//! Raw contract state inspection.
+//!
+//! [`StateDump`] reads every key of a contract via `view_state` and checks
+//! which nested collections are still reachable from the contract struct.
+//! Entries whose prefix isn't referenced by any live parent are orphaned:
+//! the contract can never read or delete them again, but the account keeps
+//! paying for their storage.
+
+use std::{collections::BTreeMap, fmt};
+
+use near_sdk::borsh::BorshDeserialize;
+use near_workspaces::Contract;
+
+const STATE_KEY: &[u8] = b"STATE";
+
+/// How the values of a top-level collection reference nested collections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nested {
+    /// Values are plain data, e.g. a `LookupSet` or a map of amounts.
+    None,
+    /// `store::IterableMap` whose values are `store::IterableSet` or
+    /// `store::IterableMap`. Values live under `prefix ++ 'm'`.
+    StoreIterableMap,
+    /// `collections::LookupMap` whose values are `collections::UnorderedSet`.
+    /// Values live under `prefix`.
+    LegacyLookupMap,
+}
+
+/// A collection created directly by the contract struct.
+#[derive(Debug, Clone)]
+pub struct Root {
+    pub prefix: Vec<u8>,
+    pub nested: Nested,
+}
+
+impl Root {
+    pub fn new(prefix: impl Into<Vec<u8>>, nested: Nested) -> Self {
+        Self {
+            prefix: prefix.into(),
+            nested,
+        }
+    }
+}
+
+pub struct StateDump {
+    pub entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl StateDump {
+    pub async fn fetch(contract: &Contract) -> color_eyre::Result<Self> {
+        let entries = contract.view_state().await?.into_iter().collect();
+
+        Ok(Self { entries })
+    }
+
+    /// Prefixes of the nested collections referenced by live parent values.
+    pub fn live_prefixes(&self, roots: &[Root]) -> Vec<Vec<u8>> {
+        let mut prefixes = Vec::new();
+
+        for root in roots {
+            let values_prefix = match root.nested {
+                Nested::None => continue,
+                Nested::StoreIterableMap => {
+                    [root.prefix.as_slice(), b"m"].concat()
+                }
+                Nested::LegacyLookupMap => root.prefix.clone(),
+            };
+
+            for (key, value) in &self.entries {
+                if !key.starts_with(&values_prefix) {
+                    continue;
+                }
+
+                let nested = match root.nested {
+                    Nested::StoreIterableMap => store_nested_prefixes(value),
+                    Nested::LegacyLookupMap => legacy_nested_prefixes(value),
+                    Nested::None => unreachable!(),
+                };
+
+                prefixes.extend(nested.unwrap_or_else(|| {
+                    panic!(
+                        "value of {} is not a nested collection",
+                        render_bytes(key)
+                    )
+                }));
+            }
+        }
+
+        prefixes
+    }
+
+    /// Entries that are neither the contract struct, a root collection nor a
+    /// nested collection referenced by a live parent.
+    pub fn orphans(&self, roots: &[Root]) -> Vec<&[u8]> {
+        let live = self.live_prefixes(roots);
+
+        self.entries
+            .keys()
+            .filter(|key| key.as_slice() != STATE_KEY)
+            .filter(|key| {
+                !roots.iter().any(|root| key.starts_with(&root.prefix))
+            })
+            .filter(|key| !live.iter().any(|prefix| key.starts_with(prefix)))
+            .map(Vec::as_slice)
+            .collect()
+    }
+
+    /// Keys starting with `prefix`, e.g. a nested collection's prefix.
+    pub fn keys_with_prefix<'a>(
+        &'a self,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = &'a [u8]> {
+        self.entries
+            .keys()
+            .filter(move |key| key.starts_with(prefix))
+            .map(Vec::as_slice)
+    }
+}
+
+impl fmt::Display for StateDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in &self.entries {
+            writeln!(f, "{} ({} bytes)", render_bytes(key), value.len())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits `key` into a decoded storage key and the collection suffix behind
+/// it, e.g. `Notes(alice.test.near) ++ "v" ++ [0, 0, 0, 0]`.
+pub fn describe_key<K>(key: &[u8]) -> String
+where
+    K: BorshDeserialize + fmt::Debug,
+{
+    let mut rest = key;
+
+    match K::deserialize(&mut rest) {
+        Ok(storage_key) if rest.is_empty() => format!("{storage_key:?}"),
+        Ok(storage_key) => {
+            format!("{storage_key:?} ++ {}", render_bytes(rest))
+        }
+        Err(_) => render_bytes(key),
+    }
+}
+
+/// `store::IterableSet` and `store::IterableMap` both serialize as a
+/// `Vector { len: u32, prefix }` followed by a `LookupMap { prefix }`. Inside
+/// an `IterableMap` the value is followed by its `u32` key index.
+fn store_nested_prefixes(value: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut rest = value;
+
+    let _len = u32::deserialize(&mut rest).ok()?;
+    let vector_prefix = Vec::<u8>::deserialize(&mut rest).ok()?;
+    let map_prefix = Vec::<u8>::deserialize(&mut rest).ok()?;
+
+    Some(vec![vector_prefix, map_prefix])
+}
+
+/// `collections::UnorderedSet` serializes as the index prefix followed by a
+/// `Vector { len: u64, prefix }`.
+fn legacy_nested_prefixes(value: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut rest = value;
+
+    let index_prefix = Vec::<u8>::deserialize(&mut rest).ok()?;
+    let _len = u64::deserialize(&mut rest).ok()?;
+    let elements_prefix = Vec::<u8>::deserialize(&mut rest).ok()?;
+
+    Some(vec![index_prefix, elements_prefix])
+}
+
+fn render_bytes(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) if s.chars().all(|c| c.is_ascii_graphic()) => format!("{s:?}"),
+        _ => format!("{bytes:?}"),
+    }
+}
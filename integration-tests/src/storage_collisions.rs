@@ -1,11 +1,20 @@
-use near_sdk::{json_types::U128, near, AccountId, NearToken};
-use near_workspaces::{
-    operations::Function, types::SecretKey, Account, Contract,
+use near_sdk::{
+    borsh::{self, BorshDeserialize},
+    json_types::U128,
+    near, AccountId, NearToken,
 };
+use near_workspaces::{operations::Function, Account, Contract};
 use serde_json::json;
 
-const STORAGE_COLLISIONS_CONTRACT: &[u8] =
-    include_bytes!("../../res/storage_key_collisions.wasm");
+use crate::{
+    fixtures::Fixture,
+    report,
+    state_dump::{describe_key, Nested, Root, StateDump},
+    wasm::wasm,
+};
+
+const STORAGE_COLLISIONS_CONTRACT: &str = "storage-key-collisions";
+const STORAGE_COLLISIONS_FIXED_CONTRACT: &str = "storage-key-collisions-fixed";
 
 #[near(serializers = [borsh, json])]
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -14,57 +23,101 @@ pub struct MoneyJar {
     pub id: String,
 }
 
+/// Mirror of the contract's `StorageKey`, used to decode raw state keys.
+#[near(serializers = [borsh])]
+#[derive(Debug)]
+enum StorageKey {
+    NotesPerUser,
+    JarsPerUser,
+    Notes(AccountId),
+    Jars(String),
+    Managers,
+    UserPoints,
+    NotesById,
+    FrozenUsers,
+}
+
+fn roots() -> Vec<Root> {
+    vec![
+        Root::new(
+            borsh::to_vec(&StorageKey::NotesPerUser).unwrap(),
+            Nested::StoreIterableMap,
+        ),
+        Root::new(
+            borsh::to_vec(&StorageKey::JarsPerUser).unwrap(),
+            Nested::StoreIterableMap,
+        ),
+        Root::new(b"mm".to_vec(), Nested::LegacyLookupMap),
+        Root::new(borsh::to_vec(&StorageKey::Managers).unwrap(), Nested::None),
+        Root::new(borsh::to_vec(&StorageKey::NotesById).unwrap(), Nested::None),
+        Root::new(
+            borsh::to_vec(&StorageKey::FrozenUsers).unwrap(),
+            Nested::None,
+        ),
+    ]
+}
+
+/// Mirror of the fixed contract's `StorageKey`.
+#[near(serializers = [borsh])]
+#[derive(Debug)]
+enum FixedStorageKey {
+    JarsPerUser,
+    Jars { account_hash: [u8; 32] },
+    Accounts,
+}
+
+fn fixed_roots() -> Vec<Root> {
+    vec![
+        Root::new(
+            borsh::to_vec(&FixedStorageKey::JarsPerUser).unwrap(),
+            Nested::StoreIterableMap,
+        ),
+        Root::new(
+            borsh::to_vec(&FixedStorageKey::Accounts).unwrap(),
+            Nested::None,
+        ),
+    ]
+}
+
 struct Env {
     owner: Account,
     malicious_actor: Account,
     malicious_actor2: Account,
     storage_collisions_contract: Contract,
+    storage_collisions_fixed_contract: Contract,
 }
 
 async fn prepare() -> color_eyre::Result<Env> {
-    let sandbox = near_workspaces::sandbox().await?;
-
-    let owner = sandbox.dev_create_account().await?;
-    let malicious_actor = sandbox
-        .create_tla(
-            "account_id".parse().unwrap(),
-            SecretKey::from_random(near_workspaces::types::KeyType::ED25519),
-        )
-        .await?
-        .into_result()?;
-    let malicious_actor2 = sandbox
-        .create_tla(
-            "1account_id".parse().unwrap(),
-            SecretKey::from_random(near_workspaces::types::KeyType::ED25519),
-        )
-        .await?
-        .into_result()?;
+    let mut builder = Fixture::builder();
 
-    let storage_collisions_contract =
-        sandbox.dev_deploy(&STORAGE_COLLISIONS_CONTRACT).await?;
+    let owner = builder.account();
+    let malicious_actor = builder.top_level_account("account_id");
+    let malicious_actor2 = builder.top_level_account("1account_id");
+    let storage_collisions =
+        builder.contract(wasm(STORAGE_COLLISIONS_CONTRACT));
+    let storage_collisions_fixed =
+        builder.contract(wasm(STORAGE_COLLISIONS_FIXED_CONTRACT));
 
-    println!(
-        "STORAGE_KEY_COLLISION_CONTRACT: {}\n",
-        storage_collisions_contract.id()
+    builder.init(
+        storage_collisions,
+        "new",
+        move |fixture| json!({"managers": [fixture[owner].id()]}),
     );
+    builder.init(storage_collisions_fixed, "new", |_| json!({}));
 
-    storage_collisions_contract
-        .call("new")
-        .args_json(json!({"managers": vec![owner.id()]}))
-        .transact()
-        .await?
-        .into_result()?;
+    let fixture = builder.build().await?;
 
     Ok(Env {
-        owner,
-        malicious_actor,
-        malicious_actor2,
-        storage_collisions_contract,
+        owner: fixture[owner].clone(),
+        malicious_actor: fixture[malicious_actor].clone(),
+        malicious_actor2: fixture[malicious_actor2].clone(),
+        storage_collisions_contract: fixture[storage_collisions].clone(),
+        storage_collisions_fixed_contract: fixture[storage_collisions_fixed]
+            .clone(),
     })
 }
 
-#[tokio::test]
-async fn storage_key_collision() -> color_eyre::Result<()> {
+pub async fn storage_key_collision() -> color_eyre::Result<()> {
     let Env {
         malicious_actor,
         malicious_actor2,
@@ -77,6 +130,17 @@ async fn storage_key_collision() -> color_eyre::Result<()> {
         .args_json(json!({"amount": U128(NearToken::from_near(2).as_yoctonear()), "id": "11"}))
         .transact().await?.into_result()?;
 
+    let jars = storage_collisions_contract
+        .view("get_jars")
+        .args_json(json!({"account_id": malicious_actor.id()}))
+        .await?
+        .json::<Vec<MoneyJar>>()?;
+
+    report::state_before(
+        "jars of account_id",
+        jars.iter().map(|jar| jar.amount).collect::<Vec<_>>(),
+    );
+
     malicious_actor2
         .call(storage_collisions_contract.id(), "create_jar")
         .args_json(json!({"amount": U128(NearToken::from_near(6).as_yoctonear()), "id": "1"}))
@@ -98,6 +162,11 @@ async fn storage_key_collision() -> color_eyre::Result<()> {
 
     println!("JAR2: {jar_2:#?}");
 
+    report::state_after(
+        "jars of account_id",
+        jar_1.iter().map(|jar| jar.amount).collect::<Vec<_>>(),
+    );
+
     assert_eq!(jar_1.len(), 1);
     assert_eq!(jar_2.len(), 1);
 
@@ -113,8 +182,7 @@ async fn storage_key_collision() -> color_eyre::Result<()> {
     Ok(())
 }
 
-#[tokio::test]
-async fn storage_key_collision_timestamp() -> color_eyre::Result<()> {
+pub async fn storage_key_collision_timestamp() -> color_eyre::Result<()> {
     let Env {
         malicious_actor,
         storage_collisions_contract,
@@ -145,3 +213,363 @@ async fn storage_key_collision_timestamp() -> color_eyre::Result<()> {
 
     Ok(())
 }
+
+pub async fn storage_key_collision_fixed() -> color_eyre::Result<()> {
+    let Env {
+        malicious_actor,
+        malicious_actor2,
+        storage_collisions_fixed_contract,
+        ..
+    } = prepare().await?;
+
+    for account in [&malicious_actor, &malicious_actor2] {
+        account
+            .call(storage_collisions_fixed_contract.id(), "storage_deposit")
+            .args_json(json!({}))
+            .deposit(NearToken::from_millinear(100))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+
+    // Same account/id pairs as `storage_key_collision`
+    malicious_actor
+        .call(storage_collisions_fixed_contract.id(), "create_jar")
+        .args_json(json!({"amount": U128(NearToken::from_near(2).as_yoctonear()), "id": "11"}))
+        .transact().await?.into_result()?;
+
+    malicious_actor2
+        .call(storage_collisions_fixed_contract.id(), "create_jar")
+        .args_json(json!({"amount": U128(NearToken::from_near(6).as_yoctonear()), "id": "1"}))
+        .transact().await?.into_result()?;
+
+    let jar_1 = storage_collisions_fixed_contract
+        .view("get_jars")
+        .args_json(json!({"account_id": malicious_actor.id()}))
+        .await?
+        .json::<Vec<MoneyJar>>()?;
+
+    let jar_2 = storage_collisions_fixed_contract
+        .view("get_jars")
+        .args_json(json!({"account_id": malicious_actor2.id()}))
+        .await?
+        .json::<Vec<MoneyJar>>()?;
+
+    assert_eq!(
+        jar_1,
+        vec![MoneyJar {
+            amount: U128(NearToken::from_near(2).as_yoctonear()),
+            id: "11".to_string(),
+        }]
+    );
+    assert_eq!(
+        jar_2,
+        vec![MoneyJar {
+            amount: U128(NearToken::from_near(6).as_yoctonear()),
+            id: "1".to_string(),
+        }]
+    );
+
+    Ok(())
+}
+
+pub async fn remove_all_notes_leaks_storage() -> color_eyre::Result<()> {
+    let Env {
+        malicious_actor,
+        malicious_actor2,
+        storage_collisions_contract,
+        ..
+    } = prepare().await?;
+
+    for account in [&malicious_actor, &malicious_actor2] {
+        account
+            .batch(storage_collisions_contract.id())
+            .call(
+                Function::new("add_note")
+                    .args_json(json!({"title": "title", "body": "body"}))
+                    .deposit(NearToken::from_near(1)),
+            )
+            .call(
+                Function::new("add_note")
+                    .args_json(json!({"title": "title 2", "body": "body 2"}))
+                    .deposit(NearToken::from_near(1)),
+            )
+            .transact()
+            .await?
+            .into_result()?;
+    }
+
+    let dump = StateDump::fetch(&storage_collisions_contract).await?;
+    assert!(dump.orphans(&roots()).is_empty());
+
+    malicious_actor
+        .call(storage_collisions_contract.id(), "remove_all_notes")
+        .transact()
+        .await?
+        .into_result()?;
+
+    malicious_actor2
+        .call(storage_collisions_contract.id(), "remove_all_notes_correct")
+        .transact()
+        .await?
+        .into_result()?;
+
+    let dump = StateDump::fetch(&storage_collisions_contract).await?;
+    let orphans = dump.orphans(&roots());
+
+    report::state_after(
+        "orphaned keys",
+        orphans
+            .iter()
+            .map(|key| describe_key::<StorageKey>(key))
+            .collect::<Vec<_>>(),
+    );
+
+    let leaked_prefix =
+        borsh::to_vec(&StorageKey::Notes(malicious_actor.id().clone()))?;
+    let cleared_prefix =
+        borsh::to_vec(&StorageKey::Notes(malicious_actor2.id().clone()))?;
+
+    // Both notes of the set, each one an element and an index entry.
+    assert_eq!(orphans.len(), 4);
+    assert!(orphans.iter().all(|key| key.starts_with(&leaked_prefix)));
+
+    assert_eq!(dump.keys_with_prefix(&cleared_prefix).count(), 0);
+
+    Ok(())
+}
+
+pub async fn legacy_set_length_lost() -> color_eyre::Result<()> {
+    let Env {
+        malicious_actor,
+        malicious_actor2,
+        storage_collisions_contract,
+        ..
+    } = prepare().await?;
+
+    // The contract allows 3 notes per user, but the limit is checked
+    // against a `len` that is never persisted.
+    for i in 0..5 {
+        malicious_actor
+            .call(storage_collisions_contract.id(), "add_note_collection")
+            .args_json(json!({"title": format!("title{i}"), "body": "body"}))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+
+    let notes = storage_collisions_contract
+        .view("get_notes_collection")
+        .args_json(json!({"account_id": malicious_actor.id()}))
+        .await?
+        .json::<Vec<serde_json::Value>>()?;
+
+    assert!(notes.is_empty());
+
+    // Fixed variant: the set is written back after every insert.
+    for i in 0..3 {
+        malicious_actor2
+            .call(
+                storage_collisions_contract.id(),
+                "add_note_collection_correct",
+            )
+            .args_json(json!({"title": format!("title{i}"), "body": "body"}))
+            .transact()
+            .await?
+            .into_result()?;
+    }
+
+    let result = malicious_actor2
+        .call(
+            storage_collisions_contract.id(),
+            "add_note_collection_correct",
+        )
+        .args_json(json!({"title": "title3", "body": "body"}))
+        .transact()
+        .await?;
+
+    assert!(format!("{:?}", result.into_result().unwrap_err())
+        .contains("Too many notes"));
+
+    let notes = storage_collisions_contract
+        .view("get_notes_collection")
+        .args_json(json!({
+            "account_id": malicious_actor2.id(),
+            "from_index": 1,
+            "limit": 10,
+        }))
+        .await?
+        .json::<Vec<serde_json::Value>>()?;
+
+    assert_eq!(notes.len(), 2);
+
+    Ok(())
+}
+
+pub async fn legacy_set_migration() -> color_eyre::Result<()> {
+    let Env {
+        owner,
+        malicious_actor,
+        malicious_actor2,
+        storage_collisions_contract,
+        ..
+    } = prepare().await?;
+
+    for (method, account) in [
+        ("add_note_collection", &malicious_actor),
+        ("add_note_collection_correct", &malicious_actor2),
+    ] {
+        for title in ["first", "second"] {
+            account
+                .call(storage_collisions_contract.id(), method)
+                .args_json(json!({"title": title, "body": "body"}))
+                .transact()
+                .await?
+                .into_result()?;
+        }
+    }
+
+    let account_ids = json!({
+        "account_ids": [malicious_actor.id(), malicious_actor2.id()],
+    });
+
+    assert!(malicious_actor
+        .call(storage_collisions_contract.id(), "migrate_note_collections")
+        .args_json(&account_ids)
+        .transact()
+        .await?
+        .is_failure());
+
+    owner
+        .call(storage_collisions_contract.id(), "migrate_note_collections")
+        .args_json(&account_ids)
+        .transact()
+        .await?
+        .into_result()?;
+
+    let titles = |notes: Vec<serde_json::Value>| -> Vec<String> {
+        notes
+            .iter()
+            .map(|note| note["title"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let notes = storage_collisions_contract
+        .view("get_notes")
+        .args_json(json!({"account_id": malicious_actor.id()}))
+        .await?
+        .json::<Vec<serde_json::Value>>()?;
+
+    // "first" was overwritten at index 0 before it could be migrated.
+    assert_eq!(titles(notes), vec!["second"]);
+
+    let notes = storage_collisions_contract
+        .view("get_notes")
+        .args_json(json!({"account_id": malicious_actor2.id()}))
+        .await?
+        .json::<Vec<serde_json::Value>>()?;
+
+    assert_eq!(titles(notes), vec!["first", "second"]);
+
+    let dump = StateDump::fetch(&storage_collisions_contract).await?;
+
+    for account in [&malicious_actor, &malicious_actor2] {
+        let elements_prefix = [
+            borsh::to_vec(&StorageKey::Notes(account.id().clone()))?,
+            b"e".to_vec(),
+        ]
+        .concat();
+
+        assert_eq!(dump.keys_with_prefix(&elements_prefix).count(), 0);
+    }
+
+    Ok(())
+}
+
+pub async fn create_jar_timestamp_aliasing() -> color_eyre::Result<()> {
+    let Env {
+        malicious_actor,
+        storage_collisions_contract,
+        storage_collisions_fixed_contract,
+        ..
+    } = prepare().await?;
+
+    // Both calls run in one receipt, so they see the same block timestamp.
+    let create_jars = |contract: &Contract| {
+        malicious_actor
+            .batch(contract.id())
+            .call(Function::new("create_jar_timestamp").args_json(json!(
+                {"amount": U128(2), "id": U128(11)}
+            )))
+            .call(Function::new("create_jar_timestamp").args_json(json!(
+                {"amount": U128(7), "id": U128(1)}
+            )))
+            .transact()
+    };
+
+    create_jars(&storage_collisions_contract)
+        .await?
+        .into_result()?;
+
+    let dump = StateDump::fetch(&storage_collisions_contract).await?;
+    let orphans = dump.orphans(&roots());
+
+    report::state_after(
+        "orphaned keys",
+        orphans
+            .iter()
+            .map(|key| describe_key::<StorageKey>(key))
+            .collect::<Vec<_>>(),
+    );
+
+    let mut elements = Vec::new();
+    let mut index_entries = 0;
+
+    for key in &orphans {
+        let mut rest = *key;
+
+        assert!(matches!(
+            StorageKey::deserialize(&mut rest)?,
+            StorageKey::Jars(timestamp) if timestamp.parse::<u64>().is_ok()
+        ));
+
+        match rest.first() {
+            Some(b'v') => elements
+                .push(borsh::from_slice::<(u128, u128)>(&dump.entries[*key])?),
+            Some(b'm') => index_entries += 1,
+            _ => panic!("unexpected key {}", describe_key::<StorageKey>(key)),
+        }
+    }
+
+    // The second call's set restarted at len 0 and overwrote the first
+    // call's element, the index entries of both jars stay behind.
+    assert_eq!(elements, vec![(7, 1)]);
+    assert_eq!(index_entries, 2);
+
+    // Fixed variant: nothing is staged in persistent collections.
+    malicious_actor
+        .call(storage_collisions_fixed_contract.id(), "storage_deposit")
+        .args_json(json!({}))
+        .deposit(NearToken::from_millinear(100))
+        .transact()
+        .await?
+        .into_result()?;
+
+    create_jars(&storage_collisions_fixed_contract)
+        .await?
+        .into_result()?;
+
+    let dump = StateDump::fetch(&storage_collisions_fixed_contract).await?;
+
+    assert!(dump.orphans(&fixed_roots()).is_empty());
+
+    let jars = storage_collisions_fixed_contract
+        .view("get_jars")
+        .args_json(json!({"account_id": malicious_actor.id()}))
+        .await?
+        .json::<Vec<MoneyJar>>()?;
+
+    assert_eq!(jars.len(), 2);
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! Contract bytecode for the tests, compiled from the workspace's contract
+//! crates when a test first asks for it.
+//!
+//! The first call builds every default member of the workspace for wasm32,
+//! into its own target directory: the target directory of `cargo test`
+//! stays locked while the tests run. Cargo only recompiles crates that
+//! changed since the last run, so a test never runs an older version of a
+//! contract than its source, and the bytecode is read at most once per test
+//! binary.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Mutex, OnceLock},
+};
+
+const TARGET: &str = "wasm32-unknown-unknown";
+
+/// Bytecode of the contract crate `package`, e.g. `"denial-of-service"`.
+///
+/// Panics with cargo's output if the contracts don't build.
+pub fn wasm(package: &str) -> &'static [u8] {
+    static CACHE: OnceLock<Mutex<HashMap<String, &'static [u8]>>> =
+        OnceLock::new();
+
+    // Build and read before locking, a panic while holding the lock would
+    // poison the cache for the other tests.
+    let release_dir = release_dir();
+    let cache = CACHE.get_or_init(Default::default);
+
+    if let Some(bytes) = cache.lock().unwrap().get(package).copied() {
+        return bytes;
+    }
+
+    let file = format!("{}.wasm", package.replace('-', "_"));
+    let path = release_dir.join(file);
+    let bytes = fs::read(&path).unwrap_or_else(|error| {
+        panic!(
+            "No bytecode for `{package}` at {}: {error}, is it a default \
+             member of the workspace?",
+            path.display()
+        )
+    });
+
+    // Another test may have read the same file meanwhile, keep its copy.
+    *cache
+        .lock()
+        .unwrap()
+        .entry(package.to_string())
+        .or_insert_with(|| Vec::leak(bytes))
+}
+
+/// Builds the contracts once per test binary and returns where their
+/// bytecode is.
+fn release_dir() -> &'static Path {
+    static RELEASE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+    RELEASE_DIR.get_or_init(|| {
+        let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let target_dir = workspace.join("target").join("contracts");
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".into());
+
+        // Without `-p` cargo builds the workspace's default members, which
+        // are exactly the contracts.
+        let output = Command::new(cargo)
+            .current_dir(&workspace)
+            .args(["build", "--release", "--target", TARGET])
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .output()
+            .unwrap_or_else(|error| panic!("Failed to run cargo: {error}"));
+
+        assert!(
+            output.status.success(),
+            "Failed to build the contracts:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        target_dir.join(TARGET).join("release")
+    })
+}
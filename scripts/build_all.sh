@@ -1,2 +0,0 @@
-cargo build --target wasm32-unknown-unknown --release && \
-cp target/wasm32-unknown-unknown/release/*.wasm ./res/
@@ -0,0 +1,283 @@
+//! Offline search for storage key constructors that map different inputs to
+//! the same prefix, e.g. `format!("{}{}", id.0, account_id)` where id `11` +
+//! `account_id` and id `1` + `1account_id` both render `11account_id`.
+
+mod scheme;
+
+use std::collections::BTreeSet;
+
+use near_sdk::serde::Serialize;
+pub use scheme::{
+    is_valid_account_id, KeyScheme, ParseSchemeError, Segment, SegmentValue,
+};
+
+/// Top-level accounts shorter than this can only be created by the registrar
+/// on mainnet.
+const MIN_PERMISSIONLESS_TLA_LEN: usize = 32;
+const IMPLICIT_ACCOUNT_LEN: usize = 64;
+
+/// Inputs to enumerate. Every combination is rendered with the scheme and
+/// the resulting key is split back into every valid alternative input.
+#[derive(Debug, Clone, Default)]
+pub struct SearchSpace {
+    pub accounts: Vec<String>,
+    pub ids: Vec<u128>,
+    pub timestamps: Vec<u64>,
+}
+
+/// What a caller passes to the contract to produce a key. The account is the
+/// predecessor, whether or not it ends up in the key.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct KeyInput {
+    pub account_id: Option<String>,
+    /// Rendered as a string, the way `U128` arguments are passed in JSON.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<u64>,
+}
+
+impl KeyInput {
+    fn from_values(values: &[SegmentValue]) -> Self {
+        let mut input = Self {
+            account_id: None,
+            id: None,
+            timestamp_ms: None,
+        };
+
+        for value in values {
+            match value {
+                SegmentValue::DecimalId(id) => input.id = Some(id.to_string()),
+                SegmentValue::AccountId(account_id) => {
+                    input.account_id = Some(account_id.clone())
+                }
+                SegmentValue::TimestampMs(timestamp) => {
+                    input.timestamp_ms = Some(*timestamp)
+                }
+                SegmentValue::Literal(_) => {}
+            }
+        }
+
+        input
+    }
+}
+
+/// Two different inputs rendering the same key.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Collision {
+    pub key: String,
+    pub left: KeyInput,
+    pub right: KeyInput,
+    /// What it takes to obtain the accounts involved on mainnet.
+    pub notes: Vec<String>,
+}
+
+/// Renders every input combination in `space` and reports each alternative
+/// input that renders the same key.
+///
+/// Schemes without an account segment don't bind the key to the caller at
+/// all, so any two accounts in `space` sharing the other inputs (e.g. calling
+/// in the same block) are reported as well.
+pub fn search(scheme: &KeyScheme, space: &SearchSpace) -> Vec<Collision> {
+    let mut collisions = BTreeSet::new();
+
+    for values in combinations(scheme, space) {
+        let key = scheme.render(&values);
+        let left = KeyInput::from_values(&values);
+
+        for alternative in scheme.decompose(&key) {
+            if alternative == values {
+                continue;
+            }
+
+            let right = KeyInput::from_values(&alternative);
+            let notes = account_notes(right.account_id.as_deref());
+
+            collisions.insert(Collision {
+                key: key.clone(),
+                left: left.clone(),
+                right,
+                notes,
+            });
+        }
+
+        if !scheme.contains(&Segment::AccountId) {
+            let mut callers = space.accounts.iter();
+
+            if let (Some(first), Some(second)) =
+                (callers.next(), callers.next())
+            {
+                collisions.insert(Collision {
+                    key: key.clone(),
+                    left: KeyInput {
+                        account_id: Some(first.clone()),
+                        ..left.clone()
+                    },
+                    right: KeyInput {
+                        account_id: Some(second.clone()),
+                        ..left.clone()
+                    },
+                    notes: vec![
+                        "the key does not include the caller".to_string()
+                    ],
+                });
+            }
+        }
+    }
+
+    collisions.into_iter().collect()
+}
+
+fn combinations(
+    scheme: &KeyScheme,
+    space: &SearchSpace,
+) -> Vec<Vec<SegmentValue>> {
+    scheme
+        .segments()
+        .iter()
+        .fold(vec![Vec::new()], |prefixes, segment| {
+            let values: Vec<_> = match segment {
+                Segment::DecimalId => space
+                    .ids
+                    .iter()
+                    .map(|id| SegmentValue::DecimalId(*id))
+                    .collect(),
+                Segment::AccountId => space
+                    .accounts
+                    .iter()
+                    .map(|account| SegmentValue::AccountId(account.clone()))
+                    .collect(),
+                Segment::TimestampMs => space
+                    .timestamps
+                    .iter()
+                    .map(|timestamp| SegmentValue::TimestampMs(*timestamp))
+                    .collect(),
+                Segment::Literal(literal) => {
+                    vec![SegmentValue::Literal(literal.clone())]
+                }
+            };
+
+            prefixes
+                .iter()
+                .flat_map(|prefix| {
+                    values.iter().map(move |value| {
+                        let mut combination = prefix.clone();
+                        combination.push(value.clone());
+                        combination
+                    })
+                })
+                .collect()
+        })
+}
+
+fn account_notes(account_id: Option<&str>) -> Vec<String> {
+    let Some(account_id) = account_id else {
+        return Vec::new();
+    };
+
+    if let Some((_, parent)) = account_id.split_once('.') {
+        return vec![format!(
+            "{account_id} is a sub-account, only {parent} can create it"
+        )];
+    }
+
+    if account_id.len() == IMPLICIT_ACCOUNT_LEN
+        && account_id.bytes().all(|b| b.is_ascii_hexdigit())
+    {
+        return vec![format!(
+            "{account_id} is an implicit account, it is derived from a public \
+             key"
+        )];
+    }
+
+    if account_id.len() < MIN_PERMISSIONLESS_TLA_LEN {
+        return vec![format!(
+            "{account_id} is a short top-level account, on mainnet only the \
+             registrar can create it (sandbox: create_tla)"
+        )];
+    }
+
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_id_account_collision() {
+        let scheme: KeyScheme = "decimal id ++ account id".parse().unwrap();
+        let space = SearchSpace {
+            accounts: vec!["account_id".to_string()],
+            ids: vec![11],
+            ..Default::default()
+        };
+
+        let collisions = search(&scheme, &space);
+
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].key, "11account_id");
+        assert_eq!(
+            collisions[0].right,
+            KeyInput {
+                account_id: Some("1account_id".to_string()),
+                id: Some("1".to_string()),
+                timestamp_ms: None,
+            }
+        );
+    }
+
+    #[test]
+    fn separator_makes_split_unique() {
+        let scheme: KeyScheme =
+            r#"decimal id ++ ":" ++ account id"#.parse().unwrap();
+        let space = SearchSpace {
+            accounts: vec!["alice.near".to_string()],
+            ids: (0..100).collect(),
+            ..Default::default()
+        };
+
+        // ':' is not a valid account id character, so the split is unique.
+        assert!(search(&scheme, &space).is_empty());
+    }
+
+    #[test]
+    fn timestamp_only_key_is_shared_by_all_callers() {
+        let scheme: KeyScheme = "timestamp_ms".parse().unwrap();
+        let space = SearchSpace {
+            accounts: vec!["alice.near".to_string(), "bob.near".to_string()],
+            timestamps: vec![1_700_000_000_000],
+            ..Default::default()
+        };
+
+        let collisions = search(&scheme, &space);
+
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].key, "1700000000000");
+        assert_eq!(
+            collisions[0].left.account_id.as_deref(),
+            Some("alice.near")
+        );
+        assert_eq!(collisions[0].right.account_id.as_deref(), Some("bob.near"));
+    }
+
+    #[test]
+    fn account_digits_absorb_timestamp() {
+        let scheme: KeyScheme =
+            "decimal id ++ account id ++ timestamp_ms".parse().unwrap();
+        let space = SearchSpace {
+            accounts: vec!["bob".to_string()],
+            ids: vec![7],
+            timestamps: vec![1_700_000_000_000],
+        };
+
+        let collisions = search(&scheme, &space);
+
+        assert!(collisions.iter().any(|collision| {
+            collision.right.account_id.as_deref() == Some("bob1")
+                && collision.right.timestamp_ms == Some(700_000_000_000)
+        }));
+    }
+}
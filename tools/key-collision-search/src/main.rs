@@ -0,0 +1,143 @@
+use std::{env, ops::RangeInclusive, process::ExitCode, str::FromStr};
+
+use key_collision_search::{
+    is_valid_account_id, search, KeyScheme, SearchSpace,
+};
+
+const USAGE: &str = "\
+Usage: key-collision-search --scheme <SCHEME> [OPTIONS]
+
+Searches for different inputs that render the same storage key.
+
+Options:
+  --scheme <SCHEME>       Segments joined with `++`: `decimal id`,
+                          `account id`, `timestamp_ms` or a quoted literal,
+                          e.g. 'decimal id ++ \":\" ++ account id'
+  --account <ACCOUNT_ID>  Account to render keys for, repeatable
+  --ids <IDS>             Ids to try: `0..=100`, `0..100` or `1,11,111`
+                          [default: 0..=100]
+  --timestamps <MS>       Timestamps to try, same syntax as --ids
+                          [default: 1700000000000]
+  --format <FORMAT>       `json` or `text` [default: json]
+  -h, --help              Print this help";
+
+struct Args {
+    scheme: KeyScheme,
+    space: SearchSpace,
+    json: bool,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let collisions = search(&args.scheme, &args.space);
+
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&collisions)
+                .expect("collisions serialize to JSON")
+        );
+    } else {
+        for collision in &collisions {
+            println!("{}", collision.key);
+            println!("  left:  {:?}", collision.left);
+            println!("  right: {:?}", collision.right);
+
+            for note in &collision.notes {
+                println!("  note:  {note}");
+            }
+        }
+
+        println!("{} collision(s)", collisions.len());
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn parse_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<Option<Args>, String> {
+    let mut scheme: Option<KeyScheme> = None;
+    let mut space = SearchSpace {
+        accounts: Vec::new(),
+        ids: (0..=100).collect(),
+        timestamps: vec![1_700_000_000_000],
+    };
+    let mut json = true;
+
+    while let Some(arg) = args.next() {
+        let mut value =
+            || args.next().ok_or(format!("missing value for {arg}"));
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--scheme" => {
+                scheme = Some(value()?.parse().map_err(|err| format!("{err}"))?)
+            }
+            "--account" => {
+                let account = value()?;
+
+                if !is_valid_account_id(&account) {
+                    return Err(format!("invalid account id `{account}`"));
+                }
+
+                space.accounts.push(account);
+            }
+            "--ids" => space.ids = parse_values(&value()?)?,
+            "--timestamps" => space.timestamps = parse_values(&value()?)?,
+            "--format" => {
+                json = match value()?.as_str() {
+                    "json" => true,
+                    "text" => false,
+                    format => return Err(format!("unknown format `{format}`")),
+                }
+            }
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+
+    let scheme = scheme.ok_or("--scheme is required")?;
+
+    Ok(Some(Args {
+        scheme,
+        space,
+        json,
+    }))
+}
+
+/// Parses `a..b`, `a..=b` or a comma separated list.
+fn parse_values<T>(input: &str) -> Result<Vec<T>, String>
+where
+    T: FromStr + Copy + PartialEq,
+    RangeInclusive<T>: Iterator<Item = T>,
+{
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<T>()
+            .map_err(|_| format!("invalid number `{value}`"))
+    };
+
+    if let Some((start, end)) = input.split_once("..=") {
+        return Ok((parse(start)?..=parse(end)?).collect());
+    }
+
+    if let Some((start, end)) = input.split_once("..") {
+        let (start, end) = (parse(start)?, parse(end)?);
+
+        return Ok((start..=end).filter(|value| *value != end).collect());
+    }
+
+    input.split(',').map(parse).collect()
+}
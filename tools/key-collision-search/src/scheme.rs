@@ -0,0 +1,282 @@
+use std::{fmt, str::FromStr};
+
+use near_sdk::AccountId;
+
+/// Longest decimal representation of a `u128`.
+const MAX_U128_DIGITS: usize = 39;
+/// Longest decimal representation of a `u64`.
+const MAX_U64_DIGITS: usize = 20;
+const MIN_ACCOUNT_ID_LEN: usize = 2;
+const MAX_ACCOUNT_ID_LEN: usize = 64;
+
+/// One piece of a key constructor such as `format!("{}{}", id.0,
+/// account_id)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// `u128` formatted with `{}`, e.g. a `U128` jar id.
+    DecimalId,
+    /// A valid NEAR account id.
+    AccountId,
+    /// `env::block_timestamp_ms()` formatted with `{}`.
+    TimestampMs,
+    /// A fixed separator written by the contract, e.g. `":"`.
+    Literal(String),
+}
+
+/// The value a [`Segment`] took in a concrete key.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SegmentValue {
+    DecimalId(u128),
+    AccountId(String),
+    TimestampMs(u64),
+    Literal(String),
+}
+
+impl fmt::Display for SegmentValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DecimalId(id) => write!(f, "{id}"),
+            Self::AccountId(account_id) => write!(f, "{account_id}"),
+            Self::TimestampMs(timestamp) => write!(f, "{timestamp}"),
+            Self::Literal(literal) => write!(f, "{literal}"),
+        }
+    }
+}
+
+/// A key constructor described as segments joined with `++`, for example
+/// `decimal id ++ account id` for `format!("{}{}", id.0, account_id)` or
+/// `decimal id ++ ":" ++ account id ++ ":" ++ timestamp_ms`.
+///
+/// The rendered string is what ends up inside a `StorageKey::Jars(String)`
+/// style variant. Borsh only length-prefixes the string as a whole, so two
+/// equal strings are two equal storage prefixes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyScheme {
+    segments: Vec<Segment>,
+}
+
+impl KeyScheme {
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn contains(&self, segment: &Segment) -> bool {
+        self.segments.contains(segment)
+    }
+
+    pub fn render(&self, values: &[SegmentValue]) -> String {
+        values.iter().map(ToString::to_string).collect()
+    }
+
+    /// Every way `key` can be produced by this scheme from valid inputs.
+    pub fn decompose(&self, key: &str) -> Vec<Vec<SegmentValue>> {
+        let mut decompositions = Vec::new();
+
+        self.decompose_from(0, key, &mut Vec::new(), &mut decompositions);
+
+        decompositions
+    }
+
+    fn decompose_from(
+        &self,
+        segment: usize,
+        rest: &str,
+        current: &mut Vec<SegmentValue>,
+        decompositions: &mut Vec<Vec<SegmentValue>>,
+    ) {
+        let Some(segment_kind) = self.segments.get(segment) else {
+            if rest.is_empty() {
+                decompositions.push(current.clone());
+            }
+
+            return;
+        };
+
+        for (value, len) in candidates(segment_kind, rest) {
+            current.push(value);
+            self.decompose_from(
+                segment + 1,
+                &rest[len..],
+                current,
+                decompositions,
+            );
+            current.pop();
+        }
+    }
+}
+
+/// Values `segment` can take at the start of `rest`, with the number of bytes
+/// each one consumes.
+fn candidates(segment: &Segment, rest: &str) -> Vec<(SegmentValue, usize)> {
+    match segment {
+        Segment::Literal(literal) => rest
+            .starts_with(literal.as_str())
+            .then(|| (SegmentValue::Literal(literal.clone()), literal.len()))
+            .into_iter()
+            .collect(),
+        Segment::DecimalId => decimal_prefixes(rest, MAX_U128_DIGITS)
+            .filter_map(|digits| {
+                let id = digits.parse().ok()?;
+
+                Some((SegmentValue::DecimalId(id), digits.len()))
+            })
+            .collect(),
+        Segment::TimestampMs => decimal_prefixes(rest, MAX_U64_DIGITS)
+            .filter_map(|digits| {
+                let timestamp = digits.parse().ok()?;
+
+                Some((SegmentValue::TimestampMs(timestamp), digits.len()))
+            })
+            .collect(),
+        Segment::AccountId => (MIN_ACCOUNT_ID_LEN
+            ..=MAX_ACCOUNT_ID_LEN.min(rest.len()))
+            .filter(|len| rest.is_char_boundary(*len))
+            .filter(|len| is_valid_account_id(&rest[..*len]))
+            .map(|len| (SegmentValue::AccountId(rest[..len].to_string()), len))
+            .collect(),
+    }
+}
+
+/// Prefixes of `rest` that `{}` formatting of an unsigned integer could have
+/// produced: digits only, and no leading zero unless the number is zero.
+fn decimal_prefixes(
+    rest: &str,
+    max_digits: usize,
+) -> impl Iterator<Item = &str> {
+    let digits = rest
+        .bytes()
+        .take(max_digits)
+        .take_while(u8::is_ascii_digit)
+        .count();
+
+    (1..=digits)
+        .map(move |len| &rest[..len])
+        .filter(|digits| digits.len() == 1 || !digits.starts_with('0'))
+}
+
+pub fn is_valid_account_id(account_id: &str) -> bool {
+    account_id.parse::<AccountId>().is_ok()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSchemeError(String);
+
+impl fmt::Display for ParseSchemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid key scheme: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseSchemeError {}
+
+impl FromStr for KeyScheme {
+    type Err = ParseSchemeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let segments = s
+            .split("++")
+            .map(|segment| {
+                let segment = segment.trim();
+
+                if let Some(literal) = segment
+                    .strip_prefix('"')
+                    .and_then(|segment| segment.strip_suffix('"'))
+                {
+                    return Ok(Segment::Literal(literal.to_string()));
+                }
+
+                match segment
+                    .to_ascii_lowercase()
+                    .replace(['_', '-'], " ")
+                    .as_str()
+                {
+                    "decimal id" | "id" => Ok(Segment::DecimalId),
+                    "account id" | "account" => Ok(Segment::AccountId),
+                    "timestamp ms" | "timestamp" => Ok(Segment::TimestampMs),
+                    _ => Err(ParseSchemeError(format!(
+                        "unknown segment `{segment}`"
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for segment in
+            [Segment::DecimalId, Segment::AccountId, Segment::TimestampMs]
+        {
+            if segments.iter().filter(|s| **s == segment).count() > 1 {
+                return Err(ParseSchemeError(format!(
+                    "{segment:?} appears more than once"
+                )));
+            }
+        }
+
+        if segments.iter().any(
+            |segment| matches!(segment, Segment::Literal(l) if l.is_empty()),
+        ) {
+            return Err(ParseSchemeError("empty literal".to_string()));
+        }
+
+        Ok(Self { segments })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_scheme() {
+        let scheme: KeyScheme =
+            r#"decimal id ++ ":" ++ account id ++ timestamp_ms"#
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            scheme.segments(),
+            &[
+                Segment::DecimalId,
+                Segment::Literal(":".to_string()),
+                Segment::AccountId,
+                Segment::TimestampMs,
+            ]
+        );
+
+        assert!("decimal id ++ decimal id".parse::<KeyScheme>().is_err());
+        assert!("block height".parse::<KeyScheme>().is_err());
+    }
+
+    #[test]
+    fn decompose_id_and_account() {
+        let scheme: KeyScheme = "decimal id ++ account id".parse().unwrap();
+
+        let decompositions = scheme.decompose("11account_id");
+
+        assert_eq!(
+            decompositions,
+            vec![
+                vec![
+                    SegmentValue::DecimalId(1),
+                    SegmentValue::AccountId("1account_id".to_string()),
+                ],
+                vec![
+                    SegmentValue::DecimalId(11),
+                    SegmentValue::AccountId("account_id".to_string()),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn decompose_skips_leading_zeros() {
+        let scheme: KeyScheme = "decimal id ++ account id".parse().unwrap();
+
+        // `{}` never renders 1 as "01", so the only split is 0 ++ "1alice".
+        assert_eq!(
+            scheme.decompose("01alice"),
+            vec![vec![
+                SegmentValue::DecimalId(0),
+                SegmentValue::AccountId("1alice".to_string()),
+            ]]
+        );
+    }
+}
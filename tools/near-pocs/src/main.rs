@@ -0,0 +1,290 @@
+use std::{
+    env,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use integration_tests::{
+    report::{self, Observations, Observer, Remediation, Report},
+    scenarios::{self, Category, Scenario, SCENARIOS},
+};
+use tokio::task::{self, JoinError, LocalSet};
+
+const USAGE: &str = "\
+Usage: near-pocs <COMMAND>
+
+Runs the PoC scenarios in a local sandbox. A scenario passes when the
+attack (or the fix) behaves as the PoC asserts. `run` also runs the
+remediation of each scenario and writes a JSON and a Markdown report per
+scenario to target/reports.
+
+Commands:
+  list [--category <CATEGORY>]       List the scenarios
+  run <ID>...                        Run the given scenarios in order
+  run --all [--category <CATEGORY>]  Run every scenario
+
+Categories: access-control, dos, race, storage
+
+Options:
+  -h, --help  Print this help";
+
+enum Command {
+    List(Vec<&'static Scenario>),
+    Run(Vec<&'static Scenario>),
+}
+
+struct Outcome {
+    scenario: &'static Scenario,
+    duration: Duration,
+    error: Option<color_eyre::Report>,
+    observations: Observations,
+}
+
+fn main() -> ExitCode {
+    let command = match parse_args(env::args().skip(1)) {
+        Ok(Some(command)) => command,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match command {
+        Command::List(scenarios) => {
+            list(&scenarios);
+
+            ExitCode::SUCCESS
+        }
+        Command::Run(scenarios) => {
+            let _ = color_eyre::install();
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("tokio runtime builds");
+
+            // Scenarios aren't `Send`, they run as local tasks so that a
+            // failed assertion is reported instead of aborting the run.
+            let local = LocalSet::new();
+
+            let outcomes = with_remediations(scenarios)
+                .into_iter()
+                .map(|scenario| local.block_on(&runtime, run(scenario)))
+                .collect::<Vec<_>>();
+
+            summarize(&outcomes);
+
+            if let Err(error) = write_reports(&outcomes) {
+                eprintln!("error: failed to write the reports: {error}");
+                return ExitCode::FAILURE;
+            }
+
+            if outcomes.iter().all(|outcome| outcome.error.is_none()) {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+fn parse_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<Option<Command>, String> {
+    let Some(command) = args.next() else {
+        return Ok(None);
+    };
+
+    let mut all = false;
+    let mut category: Option<Category> = None;
+    let mut ids = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--all" => all = true,
+            "--category" => {
+                let value =
+                    args.next().ok_or(format!("missing value for {arg}"))?;
+
+                category = Some(value.parse()?);
+            }
+            _ if arg.starts_with('-') => {
+                return Err(format!("unexpected argument `{arg}`"))
+            }
+            _ => ids.push(arg),
+        }
+    }
+
+    let by_category = || {
+        SCENARIOS
+            .iter()
+            .filter(|scenario| {
+                category.is_none_or(|category| scenario.category == category)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    match command.as_str() {
+        "-h" | "--help" => Ok(None),
+        "list" if ids.is_empty() && !all => {
+            Ok(Some(Command::List(by_category())))
+        }
+        "run" if all && ids.is_empty() => Ok(Some(Command::Run(by_category()))),
+        "run" if !all && category.is_none() && !ids.is_empty() => {
+            let scenarios = ids
+                .iter()
+                .map(|id| {
+                    scenarios::find(id)
+                        .ok_or(format!("unknown scenario `{id}`, see `list`"))
+                })
+                .collect::<Result<_, _>>()?;
+
+            Ok(Some(Command::Run(scenarios)))
+        }
+        "list" | "run" => Err(format!("invalid arguments for `{command}`")),
+        _ => Err(format!("unknown command `{command}`")),
+    }
+}
+
+fn list(scenarios: &[&Scenario]) {
+    for scenario in scenarios {
+        println!(
+            "{:<40} {:<14} {:<8} {}",
+            scenario.id, scenario.category, scenario.severity, scenario.victim
+        );
+        println!("    {}", scenario.description);
+    }
+
+    println!("{} scenario(s)", scenarios.len());
+}
+
+async fn run(scenario: &'static Scenario) -> Outcome {
+    println!(
+        "=== {} ({}, {})",
+        scenario.id, scenario.category, scenario.severity
+    );
+    println!("{}", scenario.description);
+
+    match scenario.exploit {
+        Some(exploit) => {
+            println!("victim: {}, exploit: {exploit}", scenario.victim)
+        }
+        None => println!("victim: {}", scenario.victim),
+    }
+
+    println!();
+
+    // Outside of the task, so that a panicking scenario keeps what it
+    // observed so far.
+    let observer = Observer::default();
+    let start = Instant::now();
+    let error = match task::spawn_local(observer.scope((scenario.run)())).await
+    {
+        Ok(result) => result.err(),
+        Err(error) => Some(panic_report(error)),
+    };
+    let duration = start.elapsed();
+
+    match &error {
+        None => println!("--- PASS {} in {duration:.1?}\n", scenario.id),
+        Some(error) => {
+            println!("--- FAIL {} in {duration:.1?}\n{error:?}\n", scenario.id)
+        }
+    }
+
+    Outcome {
+        scenario,
+        duration,
+        error,
+        observations: observer.take(),
+    }
+}
+
+/// `scenarios` with the remediation of each one queued right after it,
+/// unless it's already queued.
+fn with_remediations(
+    scenarios: Vec<&'static Scenario>,
+) -> Vec<&'static Scenario> {
+    let mut queue: Vec<&'static Scenario> = Vec::new();
+
+    for scenario in scenarios {
+        let remediation = scenario.remediation.and_then(scenarios::find);
+
+        for scenario in [Some(scenario), remediation].into_iter().flatten() {
+            if !queue.iter().any(|queued| queued.id == scenario.id) {
+                queue.push(scenario);
+            }
+        }
+    }
+
+    queue
+}
+
+fn panic_report(error: JoinError) -> color_eyre::Report {
+    let message = match error.try_into_panic() {
+        Ok(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string()),
+        Err(error) => error.to_string(),
+    };
+
+    color_eyre::eyre::eyre!("panicked: {message}")
+}
+
+fn summarize(outcomes: &[Outcome]) {
+    println!("Summary:");
+
+    for outcome in outcomes {
+        println!(
+            "  {} {:<40} {:<14} {:>8}",
+            if outcome.error.is_none() {
+                "PASS"
+            } else {
+                "FAIL"
+            },
+            outcome.scenario.id,
+            outcome.scenario.category,
+            format!("{:.1?}", outcome.duration)
+        );
+    }
+
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.error.is_some())
+        .count();
+
+    println!("{} passed, {failed} failed", outcomes.len() - failed);
+}
+
+fn write_reports(outcomes: &[Outcome]) -> std::io::Result<()> {
+    let dir = report::reports_dir();
+
+    for outcome in outcomes {
+        let remediation = outcome.scenario.remediation.map(|id| Remediation {
+            id,
+            passed: outcomes.iter().any(|remediation| {
+                remediation.scenario.id == id && remediation.error.is_none()
+            }),
+        });
+
+        Report::new(
+            outcome.scenario,
+            outcome.error.as_ref().map(|error| format!("{error:?}")),
+            outcome.duration,
+            outcome.observations.clone(),
+            remediation,
+        )
+        .write(dir)?;
+    }
+
+    println!("Reports written to {}", dir.display());
+
+    Ok(())
+}